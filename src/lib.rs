@@ -0,0 +1,3 @@
+//! iTerm MCP server implementation in Rust
+
+pub mod mcp;
@@ -1,12 +1,19 @@
-use std::env;
-use std::process;
+use std::path::PathBuf;
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use tracing::{info, Level};
+use clap::{Parser, ValueEnum};
+use tracing::{info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
-mod mcp;
+use rs_iterm::mcp;
+use rs_iterm::mcp::backend::kitty::KittyConfig;
+use rs_iterm::mcp::backend::screen::ScreenConfig;
+use rs_iterm::mcp::backend::tmux::TmuxConfig;
+use rs_iterm::mcp::backend::wezterm::WeztermConfig;
+use rs_iterm::mcp::framing::DEFAULT_MAX_MESSAGE_SIZE;
+use rs_iterm::mcp::iterm::api::{ApiConfig, ApiEndpoint};
+use rs_iterm::mcp::server::McpServer;
+use rs_iterm::mcp::types::{Backend, Framing, ServerConfig, Transport};
 
 /// iTerm MCP server implementation in Rust
 #[derive(Parser, Debug)]
@@ -23,6 +30,153 @@ struct Args {
     /// Address to bind to
     #[clap(long, default_value = "127.0.0.1")]
     address: String,
+
+    /// Transport used to exchange MCP messages
+    #[clap(long, value_enum, default_value = "tcp")]
+    transport: TransportArg,
+
+    /// Listen on a Unix domain socket (created with 0600 permissions) instead of TCP
+    #[clap(long, value_name = "PATH", conflicts_with = "transport")]
+    unix_socket: Option<PathBuf>,
+
+    /// Reject Unix socket clients whose uid differs from the server's
+    #[clap(long, requires = "unix_socket")]
+    require_same_uid: bool,
+
+    /// Accept the legacy `{id, function, arguments}` envelope instead of JSON-RPC 2.0
+    #[clap(long)]
+    legacy_protocol: bool,
+
+    /// Message framing on stream transports (tcp, unix, stdio)
+    #[clap(long, value_enum, default_value = "auto")]
+    framing: FramingArg,
+
+    /// Maximum size of a single message in bytes
+    #[clap(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_MESSAGE_SIZE)]
+    max_message_size: usize,
+
+    /// Terminal backend the tools act on
+    #[clap(long, value_enum, default_value = "auto")]
+    backend: BackendArg,
+
+    /// tmux socket name (`tmux -L`) used by the tmux backend
+    #[clap(long, value_name = "NAME")]
+    tmux_socket: Option<String>,
+
+    /// tmux pane the tmux backend acts on (e.g. `work:1.0`; default: the current pane)
+    #[clap(long, value_name = "TARGET")]
+    tmux_target: Option<String>,
+
+    /// screen session the screen backend acts on (`screen -S`; default: the only session)
+    #[clap(long, value_name = "NAME")]
+    screen_session: Option<String>,
+
+    /// screen window the screen backend acts on (`screen -p`; default: the current window)
+    #[clap(long, value_name = "WINDOW")]
+    screen_window: Option<String>,
+
+    /// iTerm2 API endpoint for the iterm2-api backend: `host:port` or a socket path
+    /// (default: iTerm2's private socket, else localhost:1912)
+    #[clap(long, value_name = "ENDPOINT")]
+    iterm2_api_endpoint: Option<String>,
+
+    /// WezTerm pane the wezterm backend acts on (`--pane-id`; default: the focused pane)
+    #[clap(long, value_name = "ID")]
+    wezterm_pane: Option<String>,
+
+    /// kitty remote control address (`kitty @ --to`, e.g. `unix:/tmp/kitty`)
+    #[clap(long, value_name = "ADDRESS")]
+    kitty_to: Option<String>,
+
+    /// kitty window the kitty backend acts on (id or match expression; default: the active window)
+    #[clap(long, value_name = "MATCH")]
+    kitty_window: Option<String>,
+
+    /// Record the AppleScript run by the iterm2 backend into a JSON cassette for offline replay
+    #[clap(long, value_name = "PATH")]
+    record_applescript: Option<PathBuf>,
+}
+
+/// Transport selectable from the command line
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TransportArg {
+    /// Listen for TCP connections on `--address:--port`
+    Tcp,
+    /// Serve a single session over stdin/stdout (logs go to stderr)
+    Stdio,
+    /// Serve MCP Streamable HTTP (POST + SSE) on `--address:--port`
+    Http,
+}
+
+impl From<TransportArg> for Transport {
+    fn from(arg: TransportArg) -> Self {
+        match arg {
+            TransportArg::Tcp => Transport::Tcp,
+            TransportArg::Stdio => Transport::Stdio,
+            TransportArg::Http => Transport::Http,
+        }
+    }
+}
+
+/// Message framing selectable from the command line
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum FramingArg {
+    /// Detect the framing from the first message of each connection
+    Auto,
+    /// One JSON message per line
+    Newline,
+    /// LSP-style `Content-Length` headers
+    ContentLength,
+}
+
+impl From<FramingArg> for Framing {
+    fn from(arg: FramingArg) -> Self {
+        match arg {
+            FramingArg::Auto => Framing::Auto,
+            FramingArg::Newline => Framing::Newline,
+            FramingArg::ContentLength => Framing::ContentLength,
+        }
+    }
+}
+
+/// Terminal backend selectable from the command line
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum BackendArg {
+    /// Detect the terminal the server runs in ($TMUX, $STY, kitty, WezTerm, a running iTerm2), else pty
+    Auto,
+    /// iTerm2 via AppleScript (macOS only)
+    #[value(alias = "iterm")]
+    Iterm2,
+    /// iTerm2 via its websocket API
+    Iterm2Api,
+    /// Shells spawned by the server in pseudo-terminals
+    Pty,
+    /// Like pty, with output rendered by a VT100/xterm emulator
+    Vt,
+    /// A pane of a running tmux server
+    Tmux,
+    /// A window of a running GNU screen session
+    Screen,
+    /// A WezTerm pane via `wezterm cli`
+    Wezterm,
+    /// A kitty window via remote control (`kitty @`)
+    Kitty,
+}
+
+impl From<BackendArg> for Backend {
+    fn from(arg: BackendArg) -> Self {
+        match arg {
+            BackendArg::Auto => Backend::Auto,
+            BackendArg::Iterm2 => Backend::ITerm2,
+            BackendArg::Iterm2Api => Backend::ITerm2Api,
+            BackendArg::Pty => Backend::Pty,
+            BackendArg::Vt => Backend::Vt,
+            BackendArg::Tmux => Backend::Tmux,
+            BackendArg::Screen => Backend::Screen,
+            BackendArg::Wezterm => Backend::Wezterm,
+            BackendArg::Kitty => Backend::Kitty,
+        }
+    }
 }
 
 #[tokio::main]
@@ -40,26 +194,81 @@ async fn main() -> Result<()> {
         _ => Level::INFO,
     };
     
+    // Logs always go to stderr so stdout stays clean for the stdio transport
     let subscriber = FmtSubscriber::builder()
         .with_max_level(log_level)
+        .with_writer(std::io::stderr)
         .finish();
     
     tracing::subscriber::set_global_default(subscriber)
         .context("Failed to set global logging subscriber")?;
     
-    info!("Starting iTerm MCP server on {}:{}", args.address, args.port);
+    let transport = match args.unix_socket {
+        Some(_) => Transport::Unix,
+        None => Transport::from(args.transport),
+    };
+    match &args.unix_socket {
+        Some(path) => info!("Starting iTerm MCP server on unix socket {}", path.display()),
+        None if transport == Transport::Stdio => info!("Starting iTerm MCP server on stdio"),
+        None if transport == Transport::Http => info!(
+            "Starting iTerm MCP server on http://{}:{}/mcp",
+            args.address, args.port
+        ),
+        None => info!("Starting iTerm MCP server on {}:{}", args.address, args.port),
+    }
     
-    // Check if we're running on macOS
-    if env::consts::OS != "macos" {
-        eprintln!("Error: This application only runs on macOS.");
-        process::exit(1);
+    let backend = Backend::from(args.backend);
+    info!("Requested the {:?} terminal backend", backend);
+    if backend == Backend::ITerm2 && !cfg!(target_os = "macos") {
+        warn!("iTerm2 is only available on macOS; terminal tools will fail");
     }
     
     // Initialize and start the MCP server
-    let server = mcp::server::start_server(args.address, args.port).await?;
-    
-    // Wait for the server to finish
-    server.await?;
+    let config = ServerConfig {
+        transport,
+        address: args.address,
+        port: args.port,
+        unix_socket: args.unix_socket,
+        require_same_uid: args.require_same_uid,
+        log_level: args.log_level,
+        legacy_protocol: args.legacy_protocol,
+        framing: Framing::from(args.framing),
+        max_message_size: args.max_message_size,
+        backend,
+        tmux: TmuxConfig {
+            socket_name: args.tmux_socket,
+            target: args.tmux_target,
+        },
+        screen: ScreenConfig {
+            session: args.screen_session,
+            window: args.screen_window,
+        },
+        iterm2_api: ApiConfig {
+            endpoint: args
+                .iterm2_api_endpoint
+                .as_deref()
+                .map(ApiEndpoint::parse)
+                .unwrap_or_default(),
+            ..ApiConfig::default()
+        },
+        wezterm: WeztermConfig {
+            pane_id: args.wezterm_pane,
+        },
+        kitty: KittyConfig {
+            to: args.kitty_to,
+            window: args.kitty_window,
+        },
+        record_applescript: args.record_applescript,
+    };
+    if transport == Transport::Stdio {
+        let stats = McpServer::with_config(config)?.run_stdio().await?;
+        info!("Processed {} messages over stdio", stats.total_messages);
+    } else {
+        let server = mcp::server::start_server_with_config(config).await?;
+
+        // Wait for the server to finish
+        server.await?;
+    }
     
     info!("iTerm MCP server has stopped");
     Ok(())
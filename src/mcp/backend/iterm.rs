@@ -0,0 +1,509 @@
+//! iTerm2 backend: AppleScript for writing, reading the session contents and
+//! queries, the session's TTY device for sending control characters.
+//!
+//! Tools act on the current session, or on the one their `session` selector
+//! names: a unique id, a TTY path or a name pattern (see [`select_session`]).
+//! Tab titles and badges are set with iTerm2's escape sequences, written to
+//! the session's TTY like control characters.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use super::{
+    foreground_process, select_session, BackendCapabilities, ControlKey, NewSession, SessionLayout, SessionUpdate,
+    TerminalBackend, TerminalSession,
+};
+use crate::mcp::iterm::applescript::{escape, in_session, with_session};
+use crate::mcp::iterm::{
+    CommandExecutor, ControlCharacterSender, OsascriptRunner, PersistentOsascriptRunner,
+    RecordingOsascriptRunner, ScreenReader,
+};
+use crate::mcp::types::{ProcessInfo, TerminalTarget};
+
+/// Timeout for AppleScript queries, in seconds
+const QUERY_TIMEOUT_SECS: u64 = 5;
+
+/// Lists every session as
+/// `id<TAB>name<TAB>tty<TAB>current<TAB>window<TAB>tab<TAB>columns<TAB>rows` lines,
+/// where `tab` is the 1-based index of the tab in its window.
+const LIST_SESSIONS_SCRIPT: &str = r#"tell application "iTerm2"
+    set output to ""
+    set currentId to ""
+    try
+        set currentId to id of current session of current window
+    end try
+    repeat with w in windows
+        set tabIndex to 0
+        repeat with t in tabs of w
+            set tabIndex to tabIndex + 1
+            repeat with s in sessions of t
+                set output to output & (id of s) & tab & (name of s) & tab & (tty of s) & tab & ((id of s) is currentId) & tab & (id of w) & tab & tabIndex & tab & (columns of s) & tab & (rows of s) & linefeed
+            end repeat
+        end repeat
+    end repeat
+    return output
+end tell"#;
+
+/// Returns the TTY of the active session.
+const ACTIVE_TTY_SCRIPT: &str =
+    r#"tell application "iTerm2" to return tty of current session of current window"#;
+
+/// The original iTerm2 behavior behind the [`TerminalBackend`] trait.
+pub struct ITermBackend {
+    runner: Arc<dyn OsascriptRunner>,
+    executor: Mutex<CommandExecutor>,
+    reader: ScreenReader,
+}
+
+impl Default for ITermBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ITermBackend {
+    /// Create a backend that runs scripts in a persistent `osascript` worker.
+    pub fn new() -> Self {
+        Self::with_runner(Arc::new(PersistentOsascriptRunner::new()), QUERY_TIMEOUT_SECS)
+    }
+
+    /// Create a backend that also records its scripts into the cassette at `path`.
+    pub fn recording(path: PathBuf) -> Self {
+        let runner = RecordingOsascriptRunner::new(Arc::new(PersistentOsascriptRunner::new()), path);
+        Self::with_runner(Arc::new(runner), QUERY_TIMEOUT_SECS)
+    }
+
+    /// Create a backend with an injected runner (useful for tests).
+    pub fn with_runner(runner: Arc<dyn OsascriptRunner>, timeout_secs: u64) -> Self {
+        ITermBackend {
+            executor: Mutex::new(CommandExecutor::new_with_runner(runner.clone(), timeout_secs)),
+            reader: ScreenReader::new_with_runner(runner.clone(), timeout_secs),
+            runner,
+        }
+    }
+
+    /// Run an AppleScript query off the async runtime.
+    async fn query(&self, script: impl Into<String>) -> Result<String> {
+        let runner = self.runner.clone();
+        let script = script.into();
+        tokio::task::spawn_blocking(move || runner.run(&[script.as_str()], QUERY_TIMEOUT_SECS))
+            .await
+            .map_err(|e| anyhow!("failed to join osascript thread: {}", e))?
+    }
+
+    /// The session `target` selects, or `None` for the current session.
+    async fn selected_session(&self, target: &TerminalTarget) -> Result<Option<TerminalSession>> {
+        if target.window.is_some() {
+            return Err(anyhow!(
+                "The iterm2 backend selects sessions with `session`; window selection is not supported"
+            ));
+        }
+        let Some(selector) = &target.session else {
+            return Ok(None);
+        };
+        let sessions = self.list_sessions().await?;
+        Ok(Some(select_session(&sessions, selector)?.clone()))
+    }
+
+    /// Unique id of the session `target` selects, `None` for the current one.
+    async fn session_id(&self, target: &TerminalTarget) -> Result<Option<String>> {
+        Ok(self.selected_session(target).await?.map(|session| session.id))
+    }
+
+    /// TTY of the session `target` selects, if iTerm2 reports one.
+    async fn session_tty(&self, target: &TerminalTarget) -> Result<Option<String>> {
+        let session = self.selected_session(target).await?;
+        self.tty_of(session.as_ref()).await
+    }
+
+    /// TTY of `session`, or of the current session when `None`.
+    async fn tty_of(&self, session: Option<&TerminalSession>) -> Result<Option<String>> {
+        let tty = match session {
+            Some(session) => session.tty.clone().unwrap_or_default(),
+            None => self.query(ACTIVE_TTY_SCRIPT).await?.trim().to_string(),
+        };
+        Ok(Some(tty).filter(|tty| !tty.is_empty()))
+    }
+
+    /// Write raw bytes to the TTY of `session`, or of the current session when `None`.
+    async fn write_to_tty(&self, session: Option<&TerminalSession>, bytes: &[u8]) -> Result<()> {
+        let tty = self
+            .tty_of(session)
+            .await?
+            .ok_or_else(|| anyhow!("iTerm2 reported no TTY for the session"))?;
+        ControlCharacterSender::new_for_tty(tty).send_bytes(bytes).await
+    }
+}
+
+#[async_trait]
+impl TerminalBackend for ITermBackend {
+    fn name(&self) -> &'static str {
+        "iterm2"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            select_session: true,
+            create_session: true,
+            manage_sessions: true,
+            ..BackendCapabilities::default()
+        }
+    }
+
+    async fn write_text(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()> {
+        let session = self.session_id(target).await?;
+        let mut executor = self.executor.lock().await;
+        executor.execute_in_session(session.as_deref(), text, cancel).await
+    }
+
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String> {
+        let session = self.session_id(target).await?;
+        self.reader.read_session_lines(session.as_deref(), lines).await
+    }
+
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()> {
+        let session = self.selected_session(target).await?;
+        self.write_to_tty(session.as_ref(), &[key.code]).await
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>> {
+        let output = self.query(LIST_SESSIONS_SCRIPT).await?;
+        Ok(parse_sessions(&output))
+    }
+
+    async fn query_process(&self, target: &TerminalTarget) -> Result<Option<ProcessInfo>> {
+        let Some(tty) = self.session_tty(target).await? else {
+            return Ok(None);
+        };
+
+        debug!("Querying foreground process on {}", tty);
+        tokio::task::spawn_blocking(move || foreground_process(&tty)).await?
+    }
+
+    async fn create_session(&self, target: &TerminalTarget, new: &NewSession) -> Result<String> {
+        let profile = match &new.profile {
+            Some(profile) => format!("with profile {}", escape(profile)),
+            None => "with default profile".to_string(),
+        };
+        let create = match new.layout {
+            SessionLayout::Window => format!(
+                "set w to (create window {})\nset newSession to current session of w",
+                profile
+            ),
+            SessionLayout::Tab => format!(
+                "tell w to set newTab to (create tab {})\nset newSession to current session of newTab",
+                profile
+            ),
+            SessionLayout::Split { vertical } => format!(
+                "tell s to set newSession to (split {} {})",
+                if vertical { "vertically" } else { "horizontally" },
+                profile
+            ),
+        };
+        let mut body = create;
+        if let Some(command) = &new.command {
+            body.push_str(&format!("\ntell newSession to write text {}", escape(command)));
+        }
+        body.push_str("\nreturn id of newSession");
+
+        let script = match new.layout {
+            // A window needs no session to sit next to
+            SessionLayout::Window if target.is_active() => {
+                let body: Vec<String> = body.lines().map(|line| format!("    {}", line)).collect();
+                format!("tell application \"iTerm2\"\n{}\nend tell", body.join("\n"))
+            }
+            SessionLayout::Window => {
+                return Err(anyhow!("New windows are not created next to a session; omit `session`"));
+            }
+            _ => with_session(self.session_id(target).await?.as_deref(), &body),
+        };
+
+        let id = self.query(script).await?.trim().to_string();
+        if id.is_empty() {
+            return Err(anyhow!("iTerm2 did not report the id of the new session"));
+        }
+        Ok(id)
+    }
+
+    async fn close_session(&self, target: &TerminalTarget) -> Result<()> {
+        let session = self.session_id(target).await?;
+        self.query(in_session(session.as_deref(), "close")).await?;
+        Ok(())
+    }
+
+    async fn update_session(&self, target: &TerminalTarget, update: &SessionUpdate) -> Result<()> {
+        // Resolved once: a name pattern may no longer match after the rename
+        let session = self.selected_session(target).await?;
+        if let Some(name) = &update.name {
+            let id = session.as_ref().map(|session| session.id.as_str());
+            let command = format!("set name to {}", escape(name));
+            self.query(in_session(id, &command)).await?;
+        }
+
+        let mut sequences = String::new();
+        if let Some(title) = &update.tab_title {
+            // OSC 1 sets the icon name, which iTerm2 shows as the tab title
+            sequences.push_str(&format!("\x1b]1;{}\x07", printable(title)));
+        }
+        if let Some(badge) = &update.badge {
+            let format = base64::engine::general_purpose::STANDARD.encode(badge);
+            sequences.push_str(&format!("\x1b]1337;SetBadgeFormat={}\x07", format));
+        }
+        if !sequences.is_empty() {
+            self.write_to_tty(session.as_ref(), sequences.as_bytes()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// `text` without control characters, which would end an escape sequence early.
+fn printable(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Parse the output of [`LIST_SESSIONS_SCRIPT`].
+fn parse_sessions(output: &str) -> Vec<TerminalSession> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let id = fields.next()?.trim().to_string();
+            let name = fields.next().unwrap_or_default().to_string();
+            let tty = fields
+                .next()
+                .map(str::trim)
+                .filter(|tty| !tty.is_empty())
+                .map(str::to_string);
+            let active = fields.next().map(str::trim) == Some("true");
+            let mut field = || fields.next().map(str::trim).filter(|field| !field.is_empty());
+            let window = field().map(str::to_string);
+            let tab = field().map(str::to_string);
+            let columns = field().and_then(|columns| columns.parse().ok());
+            let rows = field().and_then(|rows| rows.parse().ok());
+            Some(TerminalSession {
+                id,
+                name,
+                tty,
+                active,
+                window,
+                tab,
+                columns,
+                rows,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::iterm::cassette::{Cassette, Interaction};
+    use crate::mcp::iterm::{MockOsascriptRunner, ReplayOsascriptRunner};
+
+    fn interaction(script: &str, output: &str) -> Interaction {
+        Interaction {
+            script: vec![script.to_string()],
+            timeout_secs: 1,
+            output: Some(output.to_string()),
+            error: None,
+        }
+    }
+
+    fn selected(session: &str) -> TerminalTarget {
+        TerminalTarget {
+            session: Some(session.to_string()),
+            window: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn sessions_are_parsed_from_applescript_output() {
+        let runner = MockOsascriptRunner::new(vec![
+            "A1B2\tzsh\t/dev/ttys001\ttrue\t7\t1\t80\t24\nC3D4\tvim (notes)\t/dev/ttys002\tfalse\n".to_string(),
+        ]);
+        let backend = ITermBackend::with_runner(Arc::new(runner), 1);
+
+        let sessions = backend.list_sessions().await.unwrap();
+        assert_eq!(
+            sessions,
+            vec![
+                TerminalSession {
+                    id: "A1B2".to_string(),
+                    name: "zsh".to_string(),
+                    tty: Some("/dev/ttys001".to_string()),
+                    active: true,
+                    window: Some("7".to_string()),
+                    tab: Some("1".to_string()),
+                    columns: Some(80),
+                    rows: Some(24),
+                },
+                TerminalSession {
+                    id: "C3D4".to_string(),
+                    name: "vim (notes)".to_string(),
+                    tty: Some("/dev/ttys002".to_string()),
+                    active: false,
+                    ..TerminalSession::default()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn screen_is_read_from_the_session_contents() {
+        let runner = MockOsascriptRunner::new(vec!["$ echo hi\rhi\r$ \r\r".to_string()]);
+        let backend = ITermBackend::with_runner(Arc::new(runner), 1);
+
+        let screen = backend.read_screen(&TerminalTarget::default(), 2).await.unwrap();
+        assert_eq!(screen, "hi\n$ ");
+    }
+
+    #[tokio::test]
+    async fn tools_act_on_the_selected_session() {
+        let tty = tempfile::NamedTempFile::new().unwrap();
+        let tty_path = tty.path().to_str().unwrap().to_string();
+        let sessions = format!(
+            "A1B2\tzsh\t/dev/ttys001\ttrue\t7\t1\t80\t24\nC3D4\tvim notes.txt\t{}\tfalse\t7\t2\t80\t24\n",
+            tty_path
+        );
+        let replay = Arc::new(ReplayOsascriptRunner::new(Cassette {
+            interactions: vec![
+                interaction(LIST_SESSIONS_SCRIPT, &sessions),
+                interaction(&in_session(Some("C3D4"), "write text \"ls\""), ""),
+                interaction(LIST_SESSIONS_SCRIPT, &sessions),
+                interaction(&in_session(Some("C3D4"), "return contents"), "$ ls\rnotes.txt\r$ \r\r"),
+                interaction(LIST_SESSIONS_SCRIPT, &sessions),
+                interaction(LIST_SESSIONS_SCRIPT, &sessions),
+            ],
+        }));
+        let backend = ITermBackend::with_runner(replay.clone(), 1);
+
+        backend.write_text(&selected("C3D4"), "ls", &CancellationToken::new()).await.unwrap();
+        let screen = backend.read_screen(&selected("vim*"), 2).await.unwrap();
+        assert_eq!(screen, "notes.txt\n$ ");
+        backend
+            .send_control(&selected(&tty_path), ControlKey::parse("c").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(tty.path()).unwrap(), vec![3]);
+
+        let error = backend.read_screen(&selected("emacs"), 2).await.unwrap_err();
+        assert_eq!(error.to_string(), "No session matches 'emacs' by id, tty or name");
+        replay.assert_finished();
+
+        let window = TerminalTarget {
+            session: None,
+            window: Some("1".to_string()),
+        };
+        assert!(backend.read_screen(&window, 2).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn windows_tabs_and_splits_are_created() {
+        let runner = MockOsascriptRunner::new(vec![
+            "W1S1\n".to_string(),
+            "A1B2\tzsh\t/dev/ttys001\ttrue\nC3D4\tvim\t/dev/ttys002\tfalse\n".to_string(),
+            "T2S1\n".to_string(),
+            "P3S1\n".to_string(),
+        ]);
+        let backend = ITermBackend::with_runner(Arc::new(runner.clone()), 1);
+        let new = |layout, profile: Option<&str>, command: Option<&str>| NewSession {
+            layout,
+            profile: profile.map(str::to_string),
+            command: command.map(str::to_string),
+        };
+
+        let window = new(SessionLayout::Window, Some("Agent"), Some("cd ~/src && ls"));
+        assert_eq!(backend.create_session(&TerminalTarget::default(), &window).await.unwrap(), "W1S1");
+        let tab = new(SessionLayout::Tab, None, None);
+        assert_eq!(backend.create_session(&selected("vim"), &tab).await.unwrap(), "T2S1");
+        let split = new(SessionLayout::Split { vertical: false }, None, Some("htop"));
+        assert_eq!(backend.create_session(&TerminalTarget::default(), &split).await.unwrap(), "P3S1");
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(
+            calls[0][0],
+            "tell application \"iTerm2\"
+    set w to (create window with profile \"Agent\")
+    set newSession to current session of w
+    tell newSession to write text \"cd ~/src && ls\"
+    return id of newSession
+end tell"
+        );
+        assert_eq!(calls[1][0], LIST_SESSIONS_SCRIPT);
+        assert_eq!(
+            calls[2][0],
+            with_session(
+                Some("C3D4"),
+                "tell w to set newTab to (create tab with default profile)
+set newSession to current session of newTab
+return id of newSession"
+            )
+        );
+        assert_eq!(
+            calls[3][0],
+            with_session(
+                None,
+                "tell s to set newSession to (split horizontally with default profile)
+tell newSession to write text \"htop\"
+return id of newSession"
+            )
+        );
+
+        let error = backend.create_session(&selected("vim"), &window).await.unwrap_err();
+        assert!(error.to_string().contains("omit `session`"), "got: {}", error);
+    }
+
+    #[tokio::test]
+    async fn sessions_are_closed_renamed_and_badged() {
+        let tty = tempfile::NamedTempFile::new().unwrap();
+        let sessions = |name: &str| {
+            format!("A1B2\tzsh\t/dev/ttys001\ttrue\nC3D4\t{}\t{}\tfalse\n", name, tty.path().display())
+        };
+        let runner = MockOsascriptRunner::new(vec![
+            sessions("build"),
+            String::new(),
+            sessions("build"),
+            String::new(),
+            // After the rename `build` no longer matches, so the TTY must come from the first lookup
+            sessions("agent \"build\""),
+        ]);
+        let backend = ITermBackend::with_runner(Arc::new(runner.clone()), 1);
+
+        backend.close_session(&selected("A1B2")).await.unwrap();
+        let update = SessionUpdate {
+            name: Some("agent \"build\"".to_string()),
+            tab_title: Some("build\x07".to_string()),
+            badge: Some("agent-1".to_string()),
+        };
+        backend.update_session(&selected("build"), &update).await.unwrap();
+
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[1][0], in_session(Some("A1B2"), "close"));
+        assert_eq!(calls[3][0], in_session(Some("C3D4"), "set name to \"agent \\\"build\\\"\""));
+        assert_eq!(
+            std::fs::read_to_string(tty.path()).unwrap(),
+            "\x1b]1;build\x07\x1b]1337;SetBadgeFormat=YWdlbnQtMQ==\x07"
+        );
+    }
+
+    #[tokio::test]
+    async fn write_text_goes_through_the_runner() {
+        let runner = MockOsascriptRunner::new(vec![String::new()]);
+        let backend = ITermBackend::with_runner(Arc::new(runner), 1);
+
+        let active = TerminalTarget::default();
+        backend.write_text(&active, "ls", &CancellationToken::new()).await.unwrap();
+        // The queue is now empty, so a second write reaches the runner and fails
+        assert!(backend.write_text(&active, "ls", &CancellationToken::new()).await.is_err());
+    }
+}
@@ -0,0 +1,427 @@
+//! iTerm2 backend speaking the websocket API instead of AppleScript.
+//!
+//! One long-lived connection replaces an `osascript` process per call, and
+//! the screen is read from iTerm2's own buffer rather than the TTY device.
+//! Sessions are addressed by their unique id; without one, the active
+//! session of the selected tab of the current (or targeted) window is used.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use tokio::sync::{broadcast, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+use super::{foreground_process, last_captured_lines, BackendCapabilities, ControlKey, TerminalBackend, TerminalSession};
+use crate::mcp::iterm::api::proto::{
+    Continuation, FocusChangedNotification, FocusRequest, GetBufferRequest, GetBufferStatus, LineContents, LineRange,
+    ListSessionsRequest, ListSessionsResponse, Notification, Request, Response, SendTextRequest, SendTextStatus,
+    VariableRequest, VariableStatus, WindowStatus,
+};
+use crate::mcp::iterm::api::{connect_with_default_credentials, unexpected_response, ApiClient, ApiConfig};
+use crate::mcp::iterm::{OsascriptRunner, SystemOsascriptRunner};
+use crate::mcp::types::{ProcessInfo, TerminalTarget};
+
+/// How long `write_text` waits for the screen to reflect the text
+const SCREEN_UPDATE_WAIT: Duration = Duration::from_millis(250);
+
+/// iTerm2 through its websocket API.
+pub struct ITermApiBackend {
+    config: ApiConfig,
+    /// Asks iTerm2 for API credentials when none are configured
+    runner: Arc<dyn OsascriptRunner>,
+    client: Mutex<Option<Arc<ApiClient>>>,
+}
+
+impl Default for ITermApiBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ITermApiBackend {
+    /// Connect to the current user's iTerm2.
+    pub fn new() -> Self {
+        Self::with_config(ApiConfig::default())
+    }
+
+    /// Connect to the API described by `config`.
+    pub fn with_config(config: ApiConfig) -> Self {
+        ITermApiBackend {
+            config,
+            runner: Arc::new(SystemOsascriptRunner::new()),
+            client: Mutex::new(None),
+        }
+    }
+
+    /// The open connection, (re)connecting when needed.
+    async fn client(&self) -> Result<Arc<ApiClient>> {
+        let mut client = self.client.lock().await;
+        if let Some(open) = client.as_ref().filter(|c| !c.is_closed()) {
+            return Ok(open.clone());
+        }
+        debug!("Connecting to the iTerm2 API at {:?}", self.config.endpoint);
+        let open = Arc::new(connect_with_default_credentials(&self.config, self.runner.clone()).await?);
+        *client = Some(open.clone());
+        Ok(open)
+    }
+
+    async fn list(&self, client: &ApiClient) -> Result<ListSessionsResponse> {
+        match client.request(Request::ListSessions(ListSessionsRequest {})).await? {
+            Response::ListSessions(response) => Ok(response),
+            response => Err(unexpected_response("list sessions", &response)),
+        }
+    }
+
+    async fn focus(&self, client: &ApiClient) -> Result<Vec<FocusChangedNotification>> {
+        match client.request(Request::Focus(FocusRequest {})).await? {
+            Response::Focus(response) => Ok(response.notifications),
+            response => Err(unexpected_response("focus", &response)),
+        }
+    }
+
+    /// Unique id of the session `target` selects.
+    async fn resolve(&self, client: &ApiClient, target: &TerminalTarget) -> Result<String> {
+        if let (Some(session), None) = (&target.session, &target.window) {
+            return Ok(session.clone());
+        }
+        let sessions = self.list(client).await?;
+        let focus = self.focus(client).await?;
+        resolve_session(&sessions, &focus, target)
+    }
+}
+
+#[async_trait]
+impl TerminalBackend for ITermApiBackend {
+    fn name(&self) -> &'static str {
+        "iterm2-api"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            select_session: true,
+            select_window: true,
+            ..BackendCapabilities::default()
+        }
+    }
+
+    async fn write_text(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()> {
+        let client = self.client().await?;
+        let session = self.resolve(&client, target).await?;
+        client.subscribe_screen_updates(&session).await?;
+        let updates = client.notifications();
+
+        let request = Request::SendText(SendTextRequest {
+            session: Some(session.clone()),
+            text: Some(format!("{}\r", text)),
+            suppress_broadcast: None,
+        });
+        let response = tokio::select! {
+            response = client.request(request) => response?,
+            _ = cancel.cancelled() => return Err(anyhow!("write_text cancelled")),
+        };
+        check_send_text(&session, &response)?;
+
+        // Give the screen a chance to show the text before the caller reads it back
+        if !wait_for_screen_update(updates, &session, SCREEN_UPDATE_WAIT).await {
+            debug!("No screen update from {} within {:?}", session, SCREEN_UPDATE_WAIT);
+        }
+        Ok(())
+    }
+
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String> {
+        let client = self.client().await?;
+        let session = self.resolve(&client, target).await?;
+        let request = Request::GetBuffer(GetBufferRequest {
+            session: Some(session.clone()),
+            line_range: Some(LineRange {
+                screen_contents_only: None,
+                trailing_lines: Some(i32::try_from(lines).unwrap_or(i32::MAX)),
+            }),
+        });
+        match client.request(request).await? {
+            Response::GetBuffer(response) => match response.status() {
+                GetBufferStatus::Ok => Ok(last_captured_lines(&join_lines(&response.contents), lines)),
+                status => Err(anyhow!("Failed to read session {}: {:?}", session, status)),
+            },
+            response => Err(unexpected_response("get buffer", &response)),
+        }
+    }
+
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()> {
+        let client = self.client().await?;
+        let session = self.resolve(&client, target).await?;
+        let request = Request::SendText(SendTextRequest {
+            session: Some(session.clone()),
+            text: Some(char::from(key.code).to_string()),
+            suppress_broadcast: None,
+        });
+        let response = client.request(request).await?;
+        check_send_text(&session, &response)
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>> {
+        let client = self.client().await?;
+        let sessions = self.list(&client).await?;
+        let focus = self.focus(&client).await?;
+        let active = resolve_session(&sessions, &focus, &TerminalTarget::default()).ok();
+
+        Ok(sessions
+            .windows
+            .iter()
+            .flat_map(|window| window.tabs.iter().map(move |tab| (window, tab)))
+            .filter_map(|(window, tab)| Some((window, tab, tab.root.as_ref()?)))
+            .flat_map(|(window, tab, root)| root.sessions().into_iter().map(move |summary| (window, tab, summary)))
+            .filter_map(|(window, tab, summary)| {
+                let id = summary.unique_identifier.clone()?;
+                let size = summary.grid_size.as_ref();
+                Some(TerminalSession {
+                    name: summary.title.clone().unwrap_or_default(),
+                    tty: None,
+                    active: active.as_ref() == Some(&id),
+                    id,
+                    window: window.window_id.clone(),
+                    tab: tab.tab_id.clone(),
+                    columns: size.and_then(|size| u16::try_from(size.width?).ok()),
+                    rows: size.and_then(|size| u16::try_from(size.height?).ok()),
+                })
+            })
+            .collect())
+    }
+
+    async fn query_process(&self, target: &TerminalTarget) -> Result<Option<ProcessInfo>> {
+        let client = self.client().await?;
+        let session = self.resolve(&client, target).await?;
+        let request = Request::Variable(VariableRequest {
+            session_id: Some(session.clone()),
+            get: vec!["tty".to_string()],
+        });
+        let tty = match client.request(request).await? {
+            Response::Variable(response) => match response.status() {
+                // Values are JSON encoded
+                VariableStatus::Ok => response
+                    .values
+                    .first()
+                    .and_then(|value| serde_json::from_str::<String>(value).ok())
+                    .unwrap_or_default(),
+                status => return Err(anyhow!("Failed to query the tty of {}: {:?}", session, status)),
+            },
+            response => return Err(unexpected_response("variable", &response)),
+        };
+        if tty.is_empty() {
+            return Ok(None);
+        }
+
+        debug!("Querying foreground process on {}", tty);
+        tokio::task::spawn_blocking(move || foreground_process(&tty)).await?
+    }
+}
+
+fn check_send_text(session: &str, response: &Response) -> Result<()> {
+    match response {
+        Response::SendText(response) => match response.status() {
+            SendTextStatus::Ok => Ok(()),
+            status => Err(anyhow!("Failed to send text to {}: {:?}", session, status)),
+        },
+        response => Err(unexpected_response("send text", response)),
+    }
+}
+
+/// Wait up to `timeout` for a screen update of `session`.
+async fn wait_for_screen_update(mut updates: broadcast::Receiver<Notification>, session: &str, timeout: Duration) -> bool {
+    let wait = async {
+        loop {
+            match updates.recv().await {
+                Ok(notification) => {
+                    let updated = notification.screen_update_notification.and_then(|update| update.session);
+                    if updated.as_deref() == Some(session) {
+                        return true;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return false,
+            }
+        }
+    };
+    tokio::time::timeout(timeout, wait).await.unwrap_or(false)
+}
+
+/// Join buffer lines, merging lines that iTerm2 wrapped at the margin.
+fn join_lines(contents: &[LineContents]) -> String {
+    let mut text = String::new();
+    for line in contents {
+        text.push_str(line.text());
+        if line.continuation() != Continuation::SoftEol {
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Pick the session `target` selects from a session listing and the current focus.
+///
+/// A window is matched by id or number; without one, the current window is
+/// used. Within a window, the selected tab and its active session win.
+fn resolve_session(
+    sessions: &ListSessionsResponse,
+    focus: &[FocusChangedNotification],
+    target: &TerminalTarget,
+) -> Result<String> {
+    let window = match &target.window {
+        Some(wanted) => sessions
+            .windows
+            .iter()
+            .find(|w| w.window_id.as_deref() == Some(wanted.as_str()) || w.number.map(|n| n.to_string()).as_deref() == Some(wanted.as_str()))
+            .ok_or_else(|| anyhow!("No iTerm2 window {}", wanted))?,
+        None => {
+            let current = focus
+                .iter()
+                .filter_map(|f| f.window.as_ref())
+                .filter(|w| w.window_status() != WindowStatus::ResignedKey)
+                .find_map(|w| w.window_id.as_deref());
+            sessions
+                .windows
+                .iter()
+                .find(|w| current.is_some() && w.window_id.as_deref() == current)
+                .or_else(|| sessions.windows.first())
+                .ok_or_else(|| anyhow!("iTerm2 has no open windows"))?
+        }
+    };
+
+    let in_window: Vec<String> = window
+        .tabs
+        .iter()
+        .filter_map(|tab| tab.root.as_ref())
+        .flat_map(|root| root.sessions())
+        .filter_map(|summary| summary.unique_identifier.clone())
+        .collect();
+
+    if let Some(session) = &target.session {
+        return if in_window.contains(session) {
+            Ok(session.clone())
+        } else {
+            Err(anyhow!("Session {} is not in iTerm2 window {}", session, window.window_id()))
+        };
+    }
+
+    let selected_tab = window
+        .tabs
+        .iter()
+        .find(|tab| focus.iter().any(|f| f.selected_tab.is_some() && f.selected_tab == tab.tab_id))
+        .or_else(|| window.tabs.first())
+        .ok_or_else(|| anyhow!("iTerm2 window {} has no tabs", window.window_id()))?;
+    let tab_sessions: Vec<String> = selected_tab
+        .root
+        .iter()
+        .flat_map(|root| root.sessions())
+        .filter_map(|summary| summary.unique_identifier.clone())
+        .collect();
+
+    tab_sessions
+        .iter()
+        .find(|id| focus.iter().any(|f| f.session.as_ref() == Some(*id)))
+        .or_else(|| tab_sessions.first())
+        .cloned()
+        .ok_or_else(|| anyhow!("iTerm2 tab {} has no sessions", selected_tab.tab_id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::iterm::api::proto::{
+        FocusWindow, ListSessionsTab, ListSessionsWindow, SessionSummary, SplitTreeChild, SplitTreeLink, SplitTreeNode,
+    };
+
+    fn tab(id: &str, sessions: &[&str]) -> ListSessionsTab {
+        ListSessionsTab {
+            tab_id: Some(id.to_string()),
+            root: Some(SplitTreeNode {
+                vertical: Some(true),
+                links: sessions
+                    .iter()
+                    .map(|s| SplitTreeLink {
+                        child: Some(SplitTreeChild::Session(SessionSummary {
+                            unique_identifier: Some(s.to_string()),
+                            grid_size: None,
+                            title: None,
+                        })),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn window(id: &str, number: i32, tabs: Vec<ListSessionsTab>) -> ListSessionsWindow {
+        ListSessionsWindow {
+            tabs,
+            window_id: Some(id.to_string()),
+            number: Some(number),
+        }
+    }
+
+    fn focus_window(id: &str, status: WindowStatus) -> FocusChangedNotification {
+        FocusChangedNotification {
+            window: Some(FocusWindow {
+                window_status: Some(status as i32),
+                window_id: Some(id.to_string()),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn target(session: Option<&str>, window: Option<&str>) -> TerminalTarget {
+        TerminalTarget {
+            session: session.map(str::to_string),
+            window: window.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn sessions_are_resolved_from_focus() {
+        let sessions = ListSessionsResponse {
+            windows: vec![
+                window("w1", 0, vec![tab("t1", &["a"])]),
+                window("w2", 1, vec![tab("t2", &["b"]), tab("t3", &["c", "d"])]),
+            ],
+            buried_sessions: vec![],
+        };
+        let focus = vec![
+            focus_window("w1", WindowStatus::ResignedKey),
+            focus_window("w2", WindowStatus::IsCurrent),
+            FocusChangedNotification {
+                selected_tab: Some("t3".to_string()),
+                ..Default::default()
+            },
+            FocusChangedNotification {
+                session: Some("d".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(resolve_session(&sessions, &focus, &target(None, None)).unwrap(), "d");
+        assert_eq!(resolve_session(&sessions, &focus, &target(None, Some("w1"))).unwrap(), "a");
+        assert_eq!(resolve_session(&sessions, &focus, &target(None, Some("0"))).unwrap(), "a");
+        assert_eq!(resolve_session(&sessions, &focus, &target(Some("b"), Some("w2"))).unwrap(), "b");
+        assert!(resolve_session(&sessions, &focus, &target(Some("a"), Some("w2"))).is_err());
+        assert!(resolve_session(&sessions, &focus, &target(None, Some("w9"))).is_err());
+
+        // Without focus information the first window, tab and session are used
+        assert_eq!(resolve_session(&sessions, &[], &target(None, None)).unwrap(), "a");
+    }
+
+    #[test]
+    fn wrapped_lines_are_joined() {
+        let line = |text: &str, continuation: Continuation| LineContents {
+            text: Some(text.to_string()),
+            continuation: Some(continuation as i32),
+        };
+        let contents = vec![
+            line("$ echo aaaa", Continuation::SoftEol),
+            line("bbbb", Continuation::HardEol),
+            line("aaaabbbb", Continuation::HardEol),
+        ];
+        assert_eq!(join_lines(&contents), "$ echo aaaabbbb\naaaabbbb\n");
+    }
+}
@@ -0,0 +1,366 @@
+//! kitty backend: drives a kitty window through remote control (`kitty @`).
+//!
+//! Text and control characters are typed with `send-text`, the screen and
+//! scrollback are read with `get-text --extent all`, and windows are listed
+//! with `ls`, whose JSON also carries the foreground processes of each
+//! window. Remote control must be enabled (`allow_remote_control`), and a
+//! server running outside kitty needs `listen_on` plus [`KittyConfig::to`].
+//!
+//! kitty calls panes "windows": the `session` of a [`TerminalTarget`] is a
+//! kitty window id, or any `--match` expression such as `title:vim`.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+
+use super::runner::{CommandRunner, SystemCommandRunner};
+use super::{last_captured_lines, BackendCapabilities, ControlKey, TerminalBackend, TerminalSession};
+use crate::mcp::types::{ProcessInfo, TerminalTarget};
+
+/// How to reach kitty and which window the backend acts on.
+#[derive(Debug, Clone, Default)]
+pub struct KittyConfig {
+    /// Remote control address (`kitty @ --to`, e.g. `unix:/tmp/kitty`);
+    /// the kitty instance the server runs in when unset
+    pub to: Option<String>,
+    /// Window id or match expression; the active window when unset
+    pub window: Option<String>,
+}
+
+/// An OS window in `kitty @ ls`.
+#[derive(Debug, Deserialize)]
+struct KittyOsWindow {
+    #[serde(default)]
+    id: u64,
+    #[serde(default)]
+    is_active: bool,
+    #[serde(default)]
+    tabs: Vec<KittyTab>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KittyTab {
+    id: u64,
+    #[serde(default)]
+    is_active: bool,
+    #[serde(default)]
+    windows: Vec<KittyWindow>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KittyWindow {
+    id: u64,
+    #[serde(default)]
+    is_active: bool,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    columns: Option<u16>,
+    #[serde(default)]
+    lines: Option<u16>,
+    #[serde(default)]
+    foreground_processes: Vec<KittyProcess>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KittyProcess {
+    pid: u32,
+    #[serde(default)]
+    cmdline: Vec<String>,
+}
+
+/// Backend acting on a kitty window.
+pub struct KittyBackend {
+    config: KittyConfig,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Default for KittyBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KittyBackend {
+    /// Create a backend for the active window of the enclosing kitty.
+    pub fn new() -> Self {
+        Self::with_config(KittyConfig::default())
+    }
+
+    /// Create a backend for a specific kitty instance and window.
+    pub fn with_config(config: KittyConfig) -> Self {
+        Self::with_runner(config, Arc::new(SystemCommandRunner::new()))
+    }
+
+    /// Create a backend with an injected runner (useful for tests).
+    pub fn with_runner(config: KittyConfig, runner: Arc<dyn CommandRunner>) -> Self {
+        KittyBackend { config, runner }
+    }
+
+    /// Run `kitty @ <args>` and return its stdout.
+    async fn remote(&self, args: &[&str], cancel: &CancellationToken) -> Result<String> {
+        let mut full = vec!["@".to_string()];
+        if let Some(to) = &self.config.to {
+            full.extend(["--to".to_string(), to.clone()]);
+        }
+        full.extend(args.iter().map(|arg| arg.to_string()));
+        let output = self.runner.run("kitty", &full, cancel).await?;
+        output.into_stdout(&format!("kitty @ {}", args[0]))
+    }
+
+    /// The `--match` expression selecting `target`, or the configured window.
+    fn window_match(&self, target: &TerminalTarget) -> Result<Option<String>> {
+        if target.window.is_some() {
+            return Err(anyhow!("The kitty backend selects windows with `session`; `window` is not supported"));
+        }
+        Ok(target
+            .session
+            .as_ref()
+            .or(self.config.window.as_ref())
+            .map(|window| match_expression(window)))
+    }
+
+    /// Run a window command (`<command> [--match m] <args>`).
+    async fn remote_window(
+        &self,
+        target: &TerminalTarget,
+        command: &str,
+        args: &[&str],
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        let window = self.window_match(target)?;
+        let mut full = vec![command];
+        if let Some(window) = &window {
+            full.extend(["--match", window.as_str()]);
+        }
+        full.extend_from_slice(args);
+        self.remote(&full, cancel).await
+    }
+
+    /// Windows of `kitty @ ls`, optionally restricted by a match expression.
+    async fn windows(&self, window: Option<&str>) -> Result<Vec<KittyOsWindow>> {
+        let mut args = vec!["ls"];
+        if let Some(window) = window {
+            args.extend(["--match", window]);
+        }
+        let output = self.remote(&args, &CancellationToken::new()).await?;
+        serde_json::from_str(&output).context("Unexpected output from kitty @ ls")
+    }
+}
+
+/// A bare id matches the window id; anything else is passed as is.
+fn match_expression(window: &str) -> String {
+    if window.contains(':') {
+        window.to_string()
+    } else {
+        format!("id:{}", window)
+    }
+}
+
+/// Escape text for `send-text`, which interprets Python escape sequences.
+fn escape_send_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+}
+
+/// The window kitty acts on without `--match`: the active window of the
+/// active tab of the active OS window.
+fn active_window(os_windows: &[KittyOsWindow]) -> Option<&KittyWindow> {
+    let os_window = os_windows.iter().find(|w| w.is_active).or(os_windows.first())?;
+    let tab = os_window.tabs.iter().find(|t| t.is_active).or(os_window.tabs.first())?;
+    tab.windows.iter().find(|w| w.is_active).or(tab.windows.first())
+}
+
+#[async_trait]
+impl TerminalBackend for KittyBackend {
+    fn name(&self) -> &'static str {
+        "kitty"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            select_session: true,
+            ..BackendCapabilities::default()
+        }
+    }
+
+    async fn write_text(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()> {
+        let text = format!("{}\\r", escape_send_text(text));
+        self.remote_window(target, "send-text", &["--", &text], cancel).await?;
+        Ok(())
+    }
+
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String> {
+        let output = self
+            .remote_window(target, "get-text", &["--extent", "all"], &CancellationToken::new())
+            .await?;
+        Ok(last_captured_lines(&output, lines))
+    }
+
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()> {
+        let code = format!("\\x{:02x}", key.code);
+        self.remote_window(target, "send-text", &["--", &code], &CancellationToken::new())
+            .await?;
+        Ok(())
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>> {
+        let os_windows = self.windows(None).await?;
+        let active = match &self.config.window {
+            Some(window) => {
+                let selected = self.windows(Some(&match_expression(window))).await?;
+                active_window(&selected).map(|w| w.id)
+            }
+            None => active_window(&os_windows).map(|w| w.id),
+        };
+
+        Ok(os_windows
+            .iter()
+            .flat_map(|os_window| os_window.tabs.iter().map(move |tab| (os_window.id, tab)))
+            .flat_map(|(os_window_id, tab)| tab.windows.iter().map(move |window| (os_window_id, tab.id, window)))
+            .map(|(os_window_id, tab_id, window)| TerminalSession {
+                id: window.id.to_string(),
+                name: format!("{} {}", tab_id, window.title).trim_end().to_string(),
+                tty: None,
+                active: Some(window.id) == active,
+                window: Some(os_window_id.to_string()),
+                tab: Some(tab_id.to_string()),
+                columns: window.columns,
+                rows: window.lines,
+            })
+            .collect())
+    }
+
+    async fn query_process(&self, target: &TerminalTarget) -> Result<Option<ProcessInfo>> {
+        let window = self.window_match(target)?;
+        let os_windows = self.windows(window.as_deref()).await?;
+        // The last foreground process is the leaf of the job (`sh -c` -> `sleep`)
+        let process = active_window(&os_windows).and_then(|window| window.foreground_processes.last());
+        Ok(process.map(|process| {
+            let program = process.cmdline.first().map(String::as_str).unwrap_or_default();
+            ProcessInfo {
+                pid: process.pid,
+                name: program.rsplit('/').next().unwrap_or(program).trim_start_matches('-').to_string(),
+                command: process.cmdline.join(" "),
+                ppid: None,
+                foreground: true,
+                metrics: None,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::backend::runner::{CommandOutput, MockCommandRunner};
+
+    const LS_OUTPUT: &str = r#"[
+        {"id": 1, "is_active": true, "is_focused": true, "tabs": [
+            {"id": 1, "is_active": false, "title": "zsh", "windows": [
+                {"id": 1, "is_active": true, "title": "zsh", "pid": 100,
+                 "foreground_processes": [{"pid": 100, "cwd": "/home/dev", "cmdline": ["-zsh"]}]}
+            ]},
+            {"id": 2, "is_active": true, "title": "server", "windows": [
+                {"id": 2, "is_active": false, "title": "logs", "pid": 200, "foreground_processes": []},
+                {"id": 5, "is_active": true, "title": "python3 server.py", "pid": 300, "columns": 120, "lines": 40,
+                 "foreground_processes": [
+                    {"pid": 300, "cwd": "/srv", "cmdline": ["/usr/bin/python3", "server.py", "--port", "8000"]}
+                 ]}
+            ]}
+        ]}
+    ]"#;
+
+    fn window(id: &str) -> TerminalTarget {
+        TerminalTarget {
+            session: Some(id.to_string()),
+            window: None,
+        }
+    }
+
+    #[test]
+    fn windows_are_matched_by_id_or_expression() {
+        assert_eq!(match_expression("5"), "id:5");
+        assert_eq!(match_expression("title:vim"), "title:vim");
+        assert_eq!(escape_send_text(r"printf 'a\n'"), r"printf 'a\\n'");
+    }
+
+    #[tokio::test]
+    async fn commands_target_windows() {
+        let runner = Arc::new(MockCommandRunner::new(vec![
+            CommandOutput::ok(""),
+            CommandOutput::ok("$ ls\nCargo.toml\n$\n\n"),
+            CommandOutput::ok(""),
+        ]));
+        let config = KittyConfig {
+            to: Some("unix:/tmp/kitty".to_string()),
+            window: None,
+        };
+        let backend = KittyBackend::with_runner(config, runner.clone());
+        let cancel = CancellationToken::new();
+
+        backend.write_text(&TerminalTarget::default(), r"echo a\tb", &cancel).await.unwrap();
+        let screen = backend.read_screen(&window("title:vim"), 2).await.unwrap();
+        assert_eq!(screen, "Cargo.toml\n$");
+        backend.send_control(&window("5"), ControlKey::parse("c").unwrap()).await.unwrap();
+
+        assert_eq!(
+            runner.calls(),
+            vec![
+                vec!["kitty", "@", "--to", "unix:/tmp/kitty", "send-text", "--", r"echo a\\tb\r"],
+                vec!["kitty", "@", "--to", "unix:/tmp/kitty", "get-text", "--match", "title:vim", "--extent", "all"],
+                vec!["kitty", "@", "--to", "unix:/tmp/kitty", "send-text", "--match", "id:5", "--", r"\x03"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn windows_and_processes_come_from_ls() {
+        let runner = Arc::new(MockCommandRunner::new(vec![
+            CommandOutput::ok(LS_OUTPUT),
+            CommandOutput::ok(LS_OUTPUT),
+        ]));
+        let backend = KittyBackend::with_runner(KittyConfig::default(), runner.clone());
+
+        let sessions = backend.list_sessions().await.unwrap();
+        let summary: Vec<(&str, &str, bool)> = sessions
+            .iter()
+            .map(|s| (s.id.as_str(), s.name.as_str(), s.active))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("1", "1 zsh", false), ("2", "2 logs", false), ("5", "2 python3 server.py", true)]
+        );
+        assert_eq!(sessions[2].window.as_deref(), Some("1"));
+        assert_eq!(sessions[2].tab.as_deref(), Some("2"));
+        assert_eq!((sessions[2].columns, sessions[2].rows), (Some(120), Some(40)));
+
+        let process = backend.query_process(&TerminalTarget::default()).await.unwrap().unwrap();
+        assert_eq!(process.pid, 300);
+        assert_eq!(process.name, "python3");
+        assert_eq!(process.command, "/usr/bin/python3 server.py --port 8000");
+        assert!(process.foreground);
+    }
+
+    #[tokio::test]
+    async fn selected_window_without_foreground_process() {
+        let runner = Arc::new(MockCommandRunner::new(vec![CommandOutput::ok(
+            r#"[{"id": 1, "tabs": [{"id": 2, "windows": [{"id": 2, "title": "logs", "foreground_processes": []}]}]}]"#,
+        )]));
+        let backend = KittyBackend::with_runner(KittyConfig::default(), runner.clone());
+
+        assert!(backend.query_process(&window("2")).await.unwrap().is_none());
+        assert_eq!(runner.calls()[0], vec!["kitty", "@", "ls", "--match", "id:2"]);
+
+        let error = backend
+            .query_process(&TerminalTarget {
+                session: None,
+                window: Some("1".to_string()),
+            })
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("not supported"), "{}", error);
+    }
+}
@@ -0,0 +1,481 @@
+/*!
+Terminal backends.
+
+The MCP tools in `tools.rs` only talk to a [`TerminalBackend`]: write text,
+read the screen/scrollback, send control keys, list sessions and query the
+foreground process. Each terminal (iTerm2 via AppleScript, and others) ships
+as one implementation of the trait, so the server can run against whatever
+terminal is available on the host. [`detect_backend`] picks one from the
+environment the server was started in, and each backend reports its
+[`BackendCapabilities`] so tools it cannot serve are not offered.
+*/
+
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::mcp::types::{Backend, ProcessInfo, TerminalTarget};
+
+pub mod iterm;
+pub mod iterm_api;
+pub mod kitty;
+pub mod pty;
+pub mod runner;
+pub mod screen;
+pub mod tmux;
+pub mod vt;
+pub mod wezterm;
+
+pub use iterm::ITermBackend;
+pub use iterm_api::ITermApiBackend;
+pub use kitty::KittyBackend;
+pub use pty::PtyBackend;
+pub use runner::{CommandOutput, CommandRunner, MockCommandRunner, SystemCommandRunner};
+pub use screen::ScreenBackend;
+pub use tmux::TmuxBackend;
+pub use vt::VtScreen;
+pub use wezterm::WeztermBackend;
+
+/// A control key such as Control-C, parsed from the letter sent by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlKey {
+    /// Upper-case letter or symbol (`C`, `[`, `]`, ...)
+    pub letter: char,
+    /// ASCII control code (`C` -> 3)
+    pub code: u8,
+}
+
+impl ControlKey {
+    /// Parse a single letter into a control key (`"c"` -> Control-C).
+    pub fn parse(letter: &str) -> Result<Self> {
+        let code = crate::mcp::utilities::letter_to_control_char(letter)?;
+        let letter = letter
+            .chars()
+            .next()
+            .map(|c| c.to_ascii_uppercase())
+            .ok_or_else(|| anyhow!("Control character must not be empty"))?;
+        Ok(ControlKey { letter, code })
+    }
+}
+
+/// A terminal session (iTerm2 session, tmux pane, PTY, ...).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerminalSession {
+    /// Backend-specific identifier
+    pub id: String,
+    /// Human readable name or title
+    pub name: String,
+    /// TTY device attached to the session, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tty: Option<String>,
+    /// Whether this is the session the tools act on
+    pub active: bool,
+    /// Window holding the session, for backends grouping sessions in windows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<String>,
+    /// Tab holding the session within its window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tab: Option<String>,
+    /// Width in character cells, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<u16>,
+    /// Height in character cells, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u16>,
+}
+
+/// What a backend can do, reported to clients in the `initialize` response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendCapabilities {
+    pub write_text: bool,
+    pub read_screen: bool,
+    pub send_control: bool,
+    pub list_sessions: bool,
+    pub query_process: bool,
+    /// Tool calls may select a session with `session`
+    pub select_session: bool,
+    /// Tool calls may select a window with `window`
+    pub select_window: bool,
+    /// New windows, tabs and split panes can be created
+    pub create_session: bool,
+    /// Sessions can be closed, renamed and given a badge
+    pub manage_sessions: bool,
+}
+
+impl Default for BackendCapabilities {
+    /// Every operation on the active session, without session or window
+    /// selection nor session creation and management.
+    fn default() -> Self {
+        BackendCapabilities {
+            write_text: true,
+            read_screen: true,
+            send_control: true,
+            list_sessions: true,
+            query_process: true,
+            select_session: false,
+            select_window: false,
+            create_session: false,
+            manage_sessions: false,
+        }
+    }
+}
+
+/// Where [`TerminalBackend::create_session`] puts a new session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionLayout {
+    /// A new window
+    Window,
+    /// A new tab in the window of the target session
+    Tab,
+    /// A split of the target session; `vertical` puts the panes side by side
+    Split { vertical: bool },
+}
+
+/// A session to create.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewSession {
+    pub layout: SessionLayout,
+    /// Terminal profile; the default profile when unset
+    pub profile: Option<String>,
+    /// Command typed into the new session once it starts
+    pub command: Option<String>,
+}
+
+/// Changes [`TerminalBackend::update_session`] applies; unset fields are kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionUpdate {
+    /// Session name
+    pub name: Option<String>,
+    /// Title of the tab holding the session
+    pub tab_title: Option<String>,
+    /// Badge text drawn over the session; empty removes the badge
+    pub badge: Option<String>,
+}
+
+/// Terminal operations the MCP tools depend on.
+///
+/// Methods taking a [`TerminalTarget`] act on the session (and window) it
+/// selects, or on the backend's active session when it is empty. Backends
+/// reject selections they cannot honour rather than falling back silently.
+#[async_trait]
+pub trait TerminalBackend: Send + Sync {
+    /// Short backend name (`iterm2`, ...)
+    fn name(&self) -> &'static str;
+
+    /// Operations and selections the backend supports.
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities::default()
+    }
+
+    /// Type `text` followed by Enter, like iTerm2's `write text`.
+    ///
+    /// Backends that spawn helper processes should abort them when `cancel` fires.
+    async fn write_text(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()>;
+
+    /// Read the last `lines` lines of the screen and scrollback.
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String>;
+
+    /// Send a control key.
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()>;
+
+    /// List the sessions known to the backend.
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>>;
+
+    /// Foreground process of the session, if any.
+    async fn query_process(&self, target: &TerminalTarget) -> Result<Option<ProcessInfo>>;
+
+    /// Create a window, tab or split next to the session `target` selects and
+    /// return the id of the new session.
+    ///
+    /// Only backends reporting `create_session` implement it.
+    async fn create_session(&self, _target: &TerminalTarget, _new: &NewSession) -> Result<String> {
+        Err(anyhow!("The {} backend cannot create sessions", self.name()))
+    }
+
+    /// Close the session `target` selects.
+    ///
+    /// Only backends reporting `manage_sessions` implement it.
+    async fn close_session(&self, _target: &TerminalTarget) -> Result<()> {
+        Err(anyhow!("The {} backend cannot close sessions", self.name()))
+    }
+
+    /// Rename the session `target` selects, retitle its tab or set its badge.
+    ///
+    /// Only backends reporting `manage_sessions` implement it.
+    async fn update_session(&self, _target: &TerminalTarget, _update: &SessionUpdate) -> Result<()> {
+        Err(anyhow!("The {} backend cannot update sessions", self.name()))
+    }
+}
+
+/// Pick the backend for the terminal the server was started in.
+///
+/// Multiplexers win over the terminal hosting them: `$TMUX`, then `$STY`
+/// (screen), then kitty and WezTerm, then iTerm2 when `iterm_running` says so.
+/// Anything else gets private PTY sessions. `env` reads environment variables.
+pub fn detect_backend(env: impl Fn(&str) -> Option<String>, iterm_running: impl FnOnce() -> bool) -> Backend {
+    let set = |name: &str| env(name).is_some_and(|value| !value.is_empty());
+    if set("TMUX") {
+        Backend::Tmux
+    } else if set("STY") {
+        Backend::Screen
+    } else if set("KITTY_WINDOW_ID") {
+        Backend::Kitty
+    } else if set("WEZTERM_PANE") {
+        Backend::Wezterm
+    } else if iterm_running() {
+        Backend::ITerm2
+    } else {
+        Backend::Pty
+    }
+}
+
+/// Find the one session `selector` names among `sessions`.
+///
+/// The selector is tried as a unique id, then as a TTY path (`/dev/ttys003`
+/// or `ttys003`), then as a name, where `*` and `?` act as wildcards. The
+/// first kind that matches anything wins and must match exactly one session.
+pub fn select_session<'a>(sessions: &'a [TerminalSession], selector: &str) -> Result<&'a TerminalSession> {
+    let device = selector.strip_prefix("/dev/").unwrap_or(selector);
+    let by_id = |session: &&TerminalSession| session.id == selector;
+    let by_tty = |session: &&TerminalSession| {
+        let tty = session.tty.as_deref().unwrap_or_default();
+        !tty.is_empty() && tty.strip_prefix("/dev/").unwrap_or(tty) == device
+    };
+    let by_name = |session: &&TerminalSession| wildcard_match(selector, &session.name);
+
+    let tiers: [&dyn Fn(&&TerminalSession) -> bool; 3] = [&by_id, &by_tty, &by_name];
+    for matches in tiers {
+        let found: Vec<&TerminalSession> = sessions.iter().filter(|session| matches(session)).collect();
+        match found.as_slice() {
+            [] => continue,
+            [session] => return Ok(session),
+            _ => {
+                let ids: Vec<&str> = found.iter().map(|session| session.id.as_str()).collect();
+                return Err(anyhow!(
+                    "Session selector '{}' matches {} sessions ({}); use a session id",
+                    selector,
+                    found.len(),
+                    ids.join(", ")
+                ));
+            }
+        }
+    }
+    Err(anyhow!("No session matches '{}' by id, tty or name", selector))
+}
+
+/// Glob-style match where `*` is any run of characters and `?` any one.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, tried)) => {
+                    p = star + 1;
+                    t = tried + 1;
+                    backtrack = Some((star, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The last `n` lines of a screen capture, ignoring the blank rows below the cursor.
+pub fn last_captured_lines(capture: &str, n: usize) -> String {
+    let lines: Vec<&str> = capture.lines().collect();
+    let used = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |last| last + 1);
+    lines[used.saturating_sub(n)..used].join("\n")
+}
+
+/// Shells a session sits in while it waits at the prompt.
+const SHELLS: &[&str] = &["sh", "bash", "zsh", "fish", "dash", "ksh", "tcsh", "csh", "nu", "login"];
+
+/// Whether `name` (as in [`ProcessInfo::name`]) is an interactive shell, i.e.
+/// a session whose foreground process has this name is idle.
+pub fn is_shell(name: &str) -> bool {
+    SHELLS.contains(&name.trim_start_matches('-'))
+}
+
+/// Find the foreground process attached to `tty` using `ps`.
+pub fn foreground_process(tty: &str) -> Result<Option<ProcessInfo>> {
+    let tty = tty.strip_prefix("/dev/").unwrap_or(tty);
+    let output = Command::new("ps")
+        .args(["-t", tty, "-o", "pid=,ppid=,stat=,command="])
+        .output()
+        .context("Failed to run ps")?;
+
+    // ps exits with 1 when no process matches
+    if !output.status.success() && !output.stdout.is_empty() {
+        return Err(anyhow!("ps failed with status {}", output.status));
+    }
+
+    Ok(parse_foreground_process(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Pick the foreground process from `ps -o pid=,ppid=,stat=,command=` output.
+///
+/// Foreground processes carry a `+` in their state; when a pipeline or a
+/// shell and its child are both in the foreground group, the leaf wins.
+fn parse_foreground_process(output: &str) -> Option<ProcessInfo> {
+    let processes: Vec<ProcessInfo> = output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok();
+            let stat = fields.next()?;
+            let command = fields.collect::<Vec<_>>().join(" ");
+            let name = command
+                .split_whitespace()
+                .next()
+                .map(|program| program.rsplit('/').next().unwrap_or(program))
+                .unwrap_or_default()
+                .trim_start_matches('-')
+                .to_string();
+
+            Some(ProcessInfo {
+                pid,
+                name,
+                command,
+                ppid,
+                foreground: stat.contains('+'),
+                metrics: None,
+            })
+        })
+        .filter(|process| process.foreground)
+        .collect();
+
+    let is_parent = |pid: u32| processes.iter().any(|p| p.ppid == Some(pid));
+    processes
+        .iter()
+        .rev()
+        .find(|process| !is_parent(process.pid))
+        .or(processes.last())
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_key_parsing() {
+        assert_eq!(ControlKey::parse("c").unwrap(), ControlKey { letter: 'C', code: 3 });
+        assert_eq!(ControlKey::parse("]").unwrap(), ControlKey { letter: ']', code: 29 });
+        assert!(ControlKey::parse("").is_err());
+        assert!(ControlKey::parse("?").is_err());
+    }
+
+    #[test]
+    fn backends_are_detected_from_the_environment() {
+        let detect = |vars: &[(&str, &str)], iterm: bool| {
+            detect_backend(
+                |name| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string()),
+                || iterm,
+            )
+        };
+        assert_eq!(detect(&[("TMUX", "/tmp/tmux-501/default,42,0"), ("STY", "1.s")], true), Backend::Tmux);
+        assert_eq!(detect(&[("STY", "4242.pts-0.host")], true), Backend::Screen);
+        assert_eq!(detect(&[("KITTY_WINDOW_ID", "3"), ("WEZTERM_PANE", "0")], true), Backend::Kitty);
+        assert_eq!(detect(&[("WEZTERM_PANE", "0")], true), Backend::Wezterm);
+        assert_eq!(detect(&[("TMUX", "")], true), Backend::ITerm2);
+        assert_eq!(detect(&[], false), Backend::Pty);
+    }
+
+    #[test]
+    fn sessions_are_selected_by_id_tty_or_name() {
+        let session = |id: &str, name: &str, tty: &str| TerminalSession {
+            id: id.to_string(),
+            name: name.to_string(),
+            tty: Some(tty.to_string()),
+            ..TerminalSession::default()
+        };
+        let sessions = vec![
+            session("A1", "zsh", "/dev/ttys001"),
+            session("B2", "vim notes.txt", "/dev/ttys002"),
+            session("C3", "zsh", "/dev/ttys003"),
+            session("ttys001", "build", "/dev/ttys004"),
+        ];
+        let select = |selector: &str| select_session(&sessions, selector).map(|session| session.id.as_str());
+
+        assert_eq!(select("B2").unwrap(), "B2");
+        assert_eq!(select("/dev/ttys003").unwrap(), "C3");
+        assert_eq!(select("ttys002").unwrap(), "B2");
+        // Ids win over TTY names
+        assert_eq!(select("ttys001").unwrap(), "ttys001");
+        assert_eq!(select("vim*").unwrap(), "B2");
+        assert_eq!(select("b?ild").unwrap(), "ttys001");
+
+        let error = select("zsh").unwrap_err().to_string();
+        assert_eq!(error, "Session selector 'zsh' matches 2 sessions (A1, C3); use a session id");
+        let error = select("emacs*").unwrap_err().to_string();
+        assert_eq!(error, "No session matches 'emacs*' by id, tty or name");
+    }
+
+    #[test]
+    fn wildcards_match_runs_and_single_characters() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a*c", "abbbc"));
+        assert!(wildcard_match("*.txt", "vim notes.txt"));
+        assert!(wildcard_match("?sh", "zsh"));
+        assert!(!wildcard_match("?sh", "bash"));
+        assert!(!wildcard_match("a*c", "abcd"));
+    }
+
+    #[test]
+    fn captures_are_trimmed_to_the_last_lines() {
+        let capture = "$ echo one\none\n$ echo two\ntwo\n$\n\n\n";
+        assert_eq!(last_captured_lines(capture, 2), "two\n$");
+        assert_eq!(last_captured_lines(capture, 100), "$ echo one\none\n$ echo two\ntwo\n$");
+        assert_eq!(last_captured_lines("\n\n", 5), "");
+    }
+
+    #[test]
+    fn foreground_leaf_process_is_selected() {
+        let output = "\
+  100     1 Ss   -zsh
+  200   100 S+   /usr/bin/python3 server.py --port 8000
+  201   200 S+   /bin/sleep 10
+  300   100 S    vim notes.txt
+";
+        let process = parse_foreground_process(output).unwrap();
+        assert_eq!(process.pid, 201);
+        assert_eq!(process.ppid, Some(200));
+        assert_eq!(process.name, "sleep");
+        assert_eq!(process.command, "/bin/sleep 10");
+
+        // Only the idle shell: nothing in the foreground
+        assert!(parse_foreground_process("  100     1 Ss   -zsh\n").is_none());
+
+        let login_shell = parse_foreground_process("  100     1 Ss+  -zsh\n").unwrap();
+        assert_eq!(login_shell.name, "zsh");
+    }
+
+    #[test]
+    fn shells_are_told_apart_from_programs() {
+        assert!(is_shell("zsh"));
+        assert!(is_shell("-bash"));
+        assert!(!is_shell("vim"));
+        assert!(!is_shell("zsh-newuser-install"));
+    }
+}
@@ -0,0 +1,896 @@
+//! PTY backend: shells spawned and owned by the server.
+//!
+//! Each session is a shell running on the slave side of a pseudo-terminal
+//! (`openpty` + fork/exec). A reader thread drains the master side into an
+//! in-memory [`OutputSink`], which `read_screen` serves: a plain-text
+//! [`Scrollback`], or a [`VtScreen`] emulator when full-screen programs must
+//! render correctly. This backend works on any Unix, so agents can run
+//! headless and every tool can be tested without macOS.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use super::vt::VtScreen;
+use super::{foreground_process, BackendCapabilities, ControlKey, TerminalBackend, TerminalSession};
+use crate::mcp::types::{ProcessInfo, TerminalTarget};
+
+/// Lines kept in the scrollback of each session by default
+pub const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
+
+/// How often a write blocked on a full PTY buffer checks for cancellation
+const WRITE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Terminal size reported to the shell
+const PTY_ROWS: u16 = 24;
+const PTY_COLS: u16 = 80;
+
+/// Terminal output model fed by the reader thread of a session.
+pub trait OutputSink: Send {
+    /// Append raw output.
+    fn feed(&mut self, bytes: &[u8]);
+
+    /// The last `n` lines of output.
+    fn last_lines(&self, n: usize) -> String;
+}
+
+/// How the output of a session is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Emulation {
+    /// Escape sequences are stripped; suited to `TERM=dumb`
+    #[default]
+    Plain,
+    /// A VT100/xterm screen is emulated ([`VtScreen`])
+    Vt,
+}
+
+/// How to start the shell of a new session.
+#[derive(Debug, Clone)]
+pub struct PtyConfig {
+    /// Program to run (defaults to `$SHELL`, then `/bin/sh`)
+    pub program: String,
+    /// Arguments passed to the program
+    pub args: Vec<String>,
+    /// Extra environment variables
+    pub env: Vec<(String, String)>,
+    /// Maximum number of lines kept per session
+    pub scrollback_lines: usize,
+    /// How output is interpreted
+    pub emulation: Emulation,
+}
+
+impl PtyConfig {
+    /// The user's shell on an emulated xterm screen.
+    pub fn vt() -> Self {
+        PtyConfig {
+            env: vec![("TERM".to_string(), "xterm".to_string())],
+            emulation: Emulation::Vt,
+            ..PtyConfig::default()
+        }
+    }
+}
+
+impl Default for PtyConfig {
+    fn default() -> Self {
+        PtyConfig {
+            program: std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string()),
+            args: Vec::new(),
+            // Output is kept as plain text, so ask programs not to draw
+            env: vec![("TERM".to_string(), "dumb".to_string())],
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            emulation: Emulation::Plain,
+        }
+    }
+}
+
+/// Backend that spawns shells in pseudo-terminals.
+///
+/// The first session is spawned lazily on first use. A session whose shell
+/// has exited (`exit`, `^D`, a crash) is respawned under the same id the next
+/// time a tool selects it.
+pub struct PtyBackend {
+    config: PtyConfig,
+    sessions: Mutex<Sessions>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    list: Vec<Arc<PtySession>>,
+    active: usize,
+    next_id: usize,
+}
+
+impl Default for PtyBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PtyBackend {
+    /// Create a backend running the user's shell.
+    pub fn new() -> Self {
+        Self::with_config(PtyConfig::default())
+    }
+
+    /// Create a backend with a custom shell configuration.
+    pub fn with_config(config: PtyConfig) -> Self {
+        PtyBackend {
+            config,
+            sessions: Mutex::new(Sessions::default()),
+        }
+    }
+
+    /// Spawn a new session and make it the active one.
+    pub fn spawn_session(&self) -> Result<Arc<PtySession>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.next_id += 1;
+        let session = Arc::new(PtySession::spawn(
+            format!("pty-{}", sessions.next_id),
+            &self.config,
+        )?);
+        sessions.list.push(session.clone());
+        sessions.active = sessions.list.len() - 1;
+        Ok(session)
+    }
+
+    /// The active session, spawning the first one if needed.
+    pub fn active_session(&self) -> Result<Arc<PtySession>> {
+        {
+            let mut sessions = self.sessions.lock().unwrap();
+            let active = sessions.active;
+            if active < sessions.list.len() {
+                return self.revive(&mut sessions, active);
+            }
+        }
+        self.spawn_session()
+    }
+
+    /// The session selected by `target` (by id, e.g. `pty-2`), or the active one.
+    pub fn target_session(&self, target: &TerminalTarget) -> Result<Arc<PtySession>> {
+        if target.window.is_some() {
+            return Err(anyhow!("The pty backend has no windows"));
+        }
+        let Some(id) = &target.session else {
+            return self.active_session();
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        let index = sessions
+            .list
+            .iter()
+            .position(|session| &session.id == id)
+            .ok_or_else(|| anyhow!("Unknown pty session: {}", id))?;
+        self.revive(&mut sessions, index)
+    }
+
+    /// The session at `index`, replaced by a new shell with the same id if
+    /// its shell has exited.
+    fn revive(&self, sessions: &mut Sessions, index: usize) -> Result<Arc<PtySession>> {
+        let session = &sessions.list[index];
+        if session.is_running() {
+            return Ok(session.clone());
+        }
+        info!("The shell in {} has exited; starting a new one", session.id);
+        let fresh = Arc::new(PtySession::spawn(session.id.clone(), &self.config)?);
+        sessions.list[index] = fresh.clone();
+        Ok(fresh)
+    }
+}
+
+#[async_trait]
+impl TerminalBackend for PtyBackend {
+    fn name(&self) -> &'static str {
+        match self.config.emulation {
+            Emulation::Plain => "pty",
+            Emulation::Vt => "vt",
+        }
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            select_session: true,
+            ..BackendCapabilities::default()
+        }
+    }
+
+    async fn write_text(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()> {
+        let session = self.target_session(target)?;
+        // Like iTerm2's `write text`: the text followed by Enter
+        let mut input = text.as_bytes().to_vec();
+        input.push(b'\r');
+        session.write(input, cancel).await
+    }
+
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String> {
+        Ok(self.target_session(target)?.read_lines(lines))
+    }
+
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()> {
+        let session = self.target_session(target)?;
+        session.write(vec![key.code], &CancellationToken::new()).await
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>> {
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions
+            .list
+            .iter()
+            .enumerate()
+            .map(|(index, session)| TerminalSession {
+                id: session.id.clone(),
+                name: session.name.clone(),
+                tty: Some(session.tty.clone()),
+                active: index == sessions.active,
+                ..TerminalSession::default()
+            })
+            .collect())
+    }
+
+    async fn query_process(&self, target: &TerminalTarget) -> Result<Option<ProcessInfo>> {
+        let tty = self.target_session(target)?.tty.clone();
+        tokio::task::spawn_blocking(move || foreground_process(&tty)).await?
+    }
+}
+
+/// A shell running on a pseudo-terminal.
+pub struct PtySession {
+    id: String,
+    name: String,
+    tty: String,
+    master: Mutex<File>,
+    child: Mutex<Child>,
+    output: Arc<Mutex<Box<dyn OutputSink>>>,
+}
+
+impl PtySession {
+    /// Open a pseudo-terminal and start `config.program` on it.
+    fn spawn(id: String, config: &PtyConfig) -> Result<Self> {
+        let (master, slave) = open_pty()?;
+        let tty = tty_name(&slave)?;
+
+        let mut command = Command::new(&config.program);
+        command
+            .args(&config.args)
+            .envs(config.env.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .stdin(slave.try_clone()?)
+            .stdout(slave.try_clone()?)
+            .stderr(slave);
+        // SAFETY: only async-signal-safe calls between fork and exec
+        unsafe {
+            command.pre_exec(|| {
+                // New session with the slave (now stdin) as controlling terminal
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command
+            .spawn()
+            .with_context(|| format!("Failed to spawn {}", config.program))?;
+        // `command` still holds the slave descriptors; close them so the
+        // reader sees EOF once the shell exits
+        drop(command);
+
+        let sink: Box<dyn OutputSink> = match config.emulation {
+            Emulation::Plain => Box::new(Scrollback::new(config.scrollback_lines)),
+            Emulation::Vt => Box::new(VtScreen::new(
+                PTY_ROWS as usize,
+                PTY_COLS as usize,
+                config.scrollback_lines,
+            )),
+        };
+        let output = Arc::new(Mutex::new(sink));
+        let mut reader = master.try_clone()?;
+        let sink = output.clone();
+        let thread_id = id.clone();
+        thread::Builder::new()
+            .name(format!("{}-reader", id))
+            .spawn(move || {
+                let mut chunk = [0u8; 8192];
+                loop {
+                    match reader.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(n) => sink.lock().unwrap().feed(&chunk[..n]),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        // The master is non-blocking so writes can be cancelled
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            let _ = wait_ready(reader.as_raw_fd(), false, None);
+                        }
+                        // Linux reports EIO once the slave side is closed
+                        Err(e) => {
+                            debug!("{} reader stopped: {}", thread_id, e);
+                            break;
+                        }
+                    }
+                }
+            })?;
+
+        let name = config
+            .program
+            .rsplit('/')
+            .next()
+            .unwrap_or(&config.program)
+            .to_string();
+        info!("Spawned {} (pid {}) on {}", name, child.id(), tty);
+
+        Ok(PtySession {
+            id,
+            name,
+            tty,
+            master: Mutex::new(master),
+            child: Mutex::new(child),
+            output,
+        })
+    }
+
+    /// Session identifier (`pty-1`, ...)
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Path of the slave device (`/dev/pts/3`)
+    pub fn tty(&self) -> &str {
+        &self.tty
+    }
+
+    /// Process id of the shell
+    pub fn pid(&self) -> u32 {
+        self.child.lock().unwrap().id()
+    }
+
+    /// Whether the shell is still running
+    pub fn is_running(&self) -> bool {
+        matches!(self.child.lock().unwrap().try_wait(), Ok(None))
+    }
+
+    /// Write raw bytes to the terminal, as if typed.
+    ///
+    /// A shell that stops reading fills the PTY buffer and stalls the write,
+    /// so it runs on a blocking thread. When `cancel` fires the call returns
+    /// at once and the thread gives up within [`WRITE_POLL_INTERVAL`].
+    pub async fn write(self: &Arc<Self>, bytes: Vec<u8>, cancel: &CancellationToken) -> Result<()> {
+        if !self.is_running() {
+            return Err(anyhow!("The shell in {} has exited", self.id));
+        }
+        let session = self.clone();
+        let stop = cancel.clone();
+        let write = tokio::task::spawn_blocking(move || session.write_blocking(&bytes, &stop));
+        tokio::select! {
+            result = write => result.map_err(|e| anyhow!("failed to join the PTY writer: {}", e))?,
+            _ = cancel.cancelled() => Err(anyhow!("Write to {} cancelled by the client", self.id)),
+        }
+    }
+
+    fn write_blocking(&self, mut bytes: &[u8], cancel: &CancellationToken) -> Result<()> {
+        let mut master = self.master.lock().unwrap();
+        while !bytes.is_empty() {
+            match master.write(bytes) {
+                Ok(n) => bytes = &bytes[n..],
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if cancel.is_cancelled() {
+                        return Err(anyhow!("Write to {} cancelled by the client", self.id));
+                    }
+                    if !self.is_running() {
+                        return Err(anyhow!("The shell in {} has exited", self.id));
+                    }
+                    wait_ready(master.as_raw_fd(), true, Some(WRITE_POLL_INTERVAL))
+                        .context("Failed to wait for the pseudo-terminal")?;
+                }
+                Err(e) => return Err(e).context("Failed to write to the pseudo-terminal"),
+            }
+        }
+        Ok(())
+    }
+
+    /// The last `lines` lines of output.
+    pub fn read_lines(&self, lines: usize) -> String {
+        self.output.lock().unwrap().last_lines(lines)
+    }
+}
+
+impl Drop for PtySession {
+    /// Kill the shell and everything it started.
+    ///
+    /// The shell leads its own session and process group; background jobs
+    /// may sit in other groups of that session and would keep the slave (and
+    /// the reader thread) alive, so the processes on the terminal are listed
+    /// before the shell dies and takes the controlling terminal with it.
+    fn drop(&mut self) {
+        let child = self.child.get_mut().unwrap();
+        let jobs = terminal_processes(&self.tty);
+        let pid = child.id() as libc::pid_t;
+        // SAFETY: plain signal delivery; the pids come from our child and `ps`
+        unsafe {
+            if libc::killpg(pid, libc::SIGKILL) == -1 {
+                debug!("Failed to kill {}: {}", self.id, io::Error::last_os_error());
+            }
+            for job in jobs {
+                libc::kill(job as libc::pid_t, libc::SIGKILL);
+            }
+        }
+        let _ = child.wait();
+    }
+}
+
+/// Pids of the processes whose controlling terminal is `tty`.
+fn terminal_processes(tty: &str) -> Vec<u32> {
+    let tty = tty.strip_prefix("/dev/").unwrap_or(tty);
+    Command::new("ps")
+        .args(["-t", tty, "-o", "pid="])
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .filter_map(|pid| pid.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Open a master/slave pseudo-terminal pair, both close-on-exec, the master non-blocking.
+fn open_pty() -> Result<(File, File)> {
+    let mut master: RawFd = -1;
+    let mut slave: RawFd = -1;
+    let mut size = libc::winsize {
+        ws_row: PTY_ROWS,
+        ws_col: PTY_COLS,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    // SAFETY: the out-pointers are valid for the duration of the call.
+    // `winp` is `*const` on Linux but `*mut` on macOS.
+    #[allow(clippy::unnecessary_mut_passed)]
+    let rc = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut size,
+        )
+    };
+    if rc != 0 {
+        return Err(io::Error::last_os_error()).context("openpty failed");
+    }
+
+    // SAFETY: openpty returned two fresh descriptors that we now own
+    let (master, slave) = unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) };
+    for file in [&master, &slave] {
+        set_cloexec(file)?;
+    }
+    set_nonblocking(&master)?;
+    Ok((master, slave))
+}
+
+fn set_nonblocking(file: &File) -> Result<()> {
+    // SAFETY: fcntl on a descriptor we own
+    let rc = unsafe {
+        let flags = libc::fcntl(file.as_raw_fd(), libc::F_GETFL);
+        if flags == -1 {
+            -1
+        } else {
+            libc::fcntl(file.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK)
+        }
+    };
+    if rc == -1 {
+        return Err(io::Error::last_os_error()).context("fcntl(O_NONBLOCK) failed");
+    }
+    Ok(())
+}
+
+/// Wait until `fd` is readable (or writable when `write`), or `timeout` passes.
+///
+/// Uses `select` because macOS's `poll` does not support devices.
+fn wait_ready(fd: RawFd, write: bool, timeout: Option<Duration>) -> io::Result<()> {
+    // SAFETY: the fd_set is initialized by FD_ZERO and `fd` is an open
+    // descriptor below FD_SETSIZE (the server opens few files)
+    unsafe {
+        let mut set: libc::fd_set = std::mem::zeroed();
+        libc::FD_ZERO(&mut set);
+        libc::FD_SET(fd, &mut set);
+        let (read_set, write_set) = if write {
+            (std::ptr::null_mut(), &mut set as *mut libc::fd_set)
+        } else {
+            (&mut set as *mut libc::fd_set, std::ptr::null_mut())
+        };
+        let mut tv = timeout.map(|timeout| libc::timeval {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+        });
+        let tv_ptr = tv.as_mut().map_or(std::ptr::null_mut(), |tv| tv as *mut libc::timeval);
+        match libc::select(fd + 1, read_set, write_set, std::ptr::null_mut(), tv_ptr) {
+            -1 => {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    Ok(())
+                } else {
+                    Err(error)
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn set_cloexec(file: &File) -> Result<()> {
+    // SAFETY: fcntl on a descriptor we own
+    let rc = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+    if rc == -1 {
+        return Err(io::Error::last_os_error()).context("fcntl(FD_CLOEXEC) failed");
+    }
+    Ok(())
+}
+
+/// Device path of a terminal descriptor.
+fn tty_name(file: &File) -> Result<String> {
+    let mut buffer = [0 as libc::c_char; 256];
+    // SAFETY: the buffer is valid and its length is passed along
+    let rc = unsafe { libc::ttyname_r(file.as_raw_fd(), buffer.as_mut_ptr(), buffer.len()) };
+    if rc != 0 {
+        return Err(io::Error::from_raw_os_error(rc)).context("ttyname_r failed");
+    }
+    // SAFETY: ttyname_r wrote a NUL-terminated string into the buffer
+    let name = unsafe { std::ffi::CStr::from_ptr(buffer.as_ptr()) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+/// Escape sequence parser state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    Normal,
+    Escape,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+/// Plain-text scrollback fed with raw terminal output.
+///
+/// ANSI escape sequences are dropped, `\r` returns to the start of the line
+/// (so progress bars overwrite themselves) and backspace erases; only the
+/// last `limit` lines are kept.
+#[derive(Debug)]
+pub struct Scrollback {
+    lines: VecDeque<String>,
+    current: String,
+    limit: usize,
+    state: EscapeState,
+    carriage_return: bool,
+    pending_utf8: Vec<u8>,
+}
+
+impl Scrollback {
+    /// Create an empty scrollback keeping up to `limit` lines.
+    pub fn new(limit: usize) -> Self {
+        Scrollback {
+            lines: VecDeque::new(),
+            current: String::new(),
+            limit: limit.max(1),
+            state: EscapeState::Normal,
+            carriage_return: false,
+            pending_utf8: Vec::new(),
+        }
+    }
+
+    /// Append raw output.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let mut data = std::mem::take(&mut self.pending_utf8);
+        data.extend_from_slice(bytes);
+
+        let text = match std::str::from_utf8(&data) {
+            Ok(text) => text.to_string(),
+            Err(e) if e.error_len().is_none() => {
+                // Incomplete character at the end of the chunk; keep it for later
+                let (valid, rest) = data.split_at(e.valid_up_to());
+                self.pending_utf8 = rest.to_vec();
+                String::from_utf8_lossy(valid).into_owned()
+            }
+            Err(_) => String::from_utf8_lossy(&data).into_owned(),
+        };
+
+        for c in text.chars() {
+            self.push_char(c);
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        match self.state {
+            EscapeState::Normal => match c {
+                '\x1b' => self.state = EscapeState::Escape,
+                '\n' => self.new_line(),
+                '\r' => self.carriage_return = true,
+                '\x08' => {
+                    self.current.pop();
+                }
+                '\t' => self.print('\t'),
+                c if c.is_control() => {}
+                c => self.print(c),
+            },
+            EscapeState::Escape => {
+                self.state = match c {
+                    '[' => EscapeState::Csi,
+                    ']' => EscapeState::Osc,
+                    _ => EscapeState::Normal,
+                }
+            }
+            EscapeState::Csi => {
+                if ('\x40'..='\x7e').contains(&c) {
+                    self.state = EscapeState::Normal;
+                }
+            }
+            EscapeState::Osc => match c {
+                '\x07' => self.state = EscapeState::Normal,
+                '\x1b' => self.state = EscapeState::OscEscape,
+                _ => {}
+            },
+            EscapeState::OscEscape => {
+                self.state = if c == '\\' {
+                    EscapeState::Normal
+                } else {
+                    EscapeState::Osc
+                }
+            }
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        if self.carriage_return {
+            self.current.clear();
+            self.carriage_return = false;
+        }
+        self.current.push(c);
+    }
+
+    fn new_line(&mut self) {
+        self.carriage_return = false;
+        self.lines.push_back(std::mem::take(&mut self.current));
+        while self.lines.len() > self.limit {
+            self.lines.pop_front();
+        }
+    }
+
+    /// The last `n` lines, including the unfinished one (e.g. the prompt).
+    pub fn last_lines(&self, n: usize) -> String {
+        let mut all: Vec<&str> = self.lines.iter().map(String::as_str).collect();
+        if !self.current.is_empty() {
+            all.push(&self.current);
+        }
+        let start = all.len().saturating_sub(n);
+        all[start..].join("\n")
+    }
+}
+
+impl OutputSink for Scrollback {
+    fn feed(&mut self, bytes: &[u8]) {
+        Scrollback::feed(self, bytes)
+    }
+
+    fn last_lines(&self, n: usize) -> String {
+        Scrollback::last_lines(self, n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn sh_backend() -> PtyBackend {
+        PtyBackend::with_config(PtyConfig {
+            program: "/bin/sh".to_string(),
+            args: Vec::new(),
+            env: vec![
+                ("TERM".to_string(), "dumb".to_string()),
+                ("PS1".to_string(), "$ ".to_string()),
+            ],
+            scrollback_lines: 100,
+            emulation: Emulation::Plain,
+        })
+    }
+
+    /// Poll the screen until `predicate` holds or five seconds pass.
+    ///
+    /// Lines are matched by suffix: the first command may be echoed before
+    /// the shell prints its prompt.
+    async fn wait_for(backend: &PtyBackend, predicate: impl Fn(&str) -> bool) -> String {
+        let started = Instant::now();
+        loop {
+            let screen = backend.read_screen(&TerminalTarget::default(), 50).await.unwrap();
+            if predicate(&screen) {
+                return screen;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "timed out; screen:\n{}",
+                screen
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[test]
+    fn scrollback_strips_escapes_and_handles_carriage_returns() {
+        let mut scrollback = Scrollback::new(3);
+        scrollback.feed(b"\x1b[1;32mgreen\x1b[0m\r\n");
+        scrollback.feed(b"\x1b]0;title\x07progress 10%\rprogress 100%\r\n");
+        scrollback.feed(b"typo\x08\x08po\r\n");
+        // Multi-byte character split across two reads
+        scrollback.feed(&"ação".as_bytes()[..2]);
+        scrollback.feed(&"ação".as_bytes()[2..]);
+
+        assert_eq!(scrollback.last_lines(10), "green\nprogress 100%\ntypo\nação");
+
+        scrollback.feed(b"\nmore\n");
+        // Only the last three complete lines are kept
+        assert_eq!(scrollback.last_lines(10), "typo\nação\nmore");
+        assert_eq!(scrollback.last_lines(1), "more");
+    }
+
+    #[tokio::test]
+    async fn commands_run_in_the_shell() {
+        let backend = sh_backend();
+        let cancel = CancellationToken::new();
+        let active = TerminalTarget::default();
+
+        backend.write_text(&active, "echo hello-$((6 * 7))", &cancel).await.unwrap();
+        wait_for(&backend, |screen| screen.lines().any(|line| line.ends_with("hello-42"))).await;
+
+        let sessions = backend.list_sessions().await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, "pty-1");
+        assert_eq!(sessions[0].name, "sh");
+        assert!(sessions[0].active);
+        assert!(sessions[0].tty.as_deref().unwrap().starts_with("/dev/"));
+    }
+
+    #[tokio::test]
+    async fn control_c_interrupts_the_foreground_process() {
+        let backend = sh_backend();
+        let cancel = CancellationToken::new();
+        let active = TerminalTarget::default();
+
+        backend.write_text(&active, "sleep 30", &cancel).await.unwrap();
+
+        // The foreground process becomes `sleep`
+        let started = Instant::now();
+        loop {
+            let process = backend.query_process(&active).await.unwrap();
+            if process.as_ref().map(|p| p.name.as_str()) == Some("sleep") {
+                break;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5), "sleep never started: {:?}", process);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        backend.send_control(&active, ControlKey::parse("c").unwrap()).await.unwrap();
+        backend.write_text(&active, "echo after-$((1 + 1))", &cancel).await.unwrap();
+        wait_for(&backend, |screen| screen.lines().any(|line| line.ends_with("after-2"))).await;
+    }
+
+    #[tokio::test]
+    async fn exited_shells_are_respawned() {
+        let backend = sh_backend();
+        let cancel = CancellationToken::new();
+        let active = TerminalTarget::default();
+        let session = backend.active_session().unwrap();
+
+        backend.write_text(&active, "exit", &cancel).await.unwrap();
+        let started = Instant::now();
+        while session.is_running() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        // The old handle stays dead; the next call gets a fresh shell with the same id
+        let error = session.write(b"echo late\r".to_vec(), &cancel).await.unwrap_err();
+        assert!(error.to_string().contains("has exited"), "{}", error);
+
+        backend.write_text(&active, "echo back-$((1 + 1))", &cancel).await.unwrap();
+        wait_for(&backend, |screen| screen.lines().any(|line| line.ends_with("back-2"))).await;
+        let revived = backend.active_session().unwrap();
+        assert_eq!(revived.id(), "pty-1");
+        assert_ne!(revived.pid(), session.pid());
+        assert_eq!(backend.list_sessions().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn dropping_a_session_kills_its_background_jobs() {
+        let backend = sh_backend();
+        let cancel = CancellationToken::new();
+        let active = TerminalTarget::default();
+
+        backend.write_text(&active, "sleep 300 & echo job-$!", &cancel).await.unwrap();
+        // The echo may share a line with the prompt
+        let job_pid = |screen: &str| {
+            screen
+                .lines()
+                .filter_map(|line| line.split_once("job-"))
+                .find_map(|(_, pid)| pid.trim().parse::<u32>().ok())
+        };
+        let screen = wait_for(&backend, |screen| job_pid(screen).is_some()).await;
+        let job = job_pid(&screen).unwrap();
+
+        drop(backend);
+        // Dead, or a zombie waiting for a reaper that is not us
+        let started = Instant::now();
+        loop {
+            let output = Command::new("ps").args(["-o", "stat=", "-p", &job.to_string()]).output().unwrap();
+            let stat = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if stat.is_empty() || stat.starts_with('Z') {
+                break;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5), "job {} survived: {}", job, stat);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn blocked_writes_are_cancelled() {
+        // `sleep` never reads, so the PTY input buffer fills up; in canonical
+        // mode the line discipline would drop the excess instead of blocking
+        let backend = PtyBackend::with_config(PtyConfig {
+            program: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), "stty raw -echo; echo ready; exec sleep 30".to_string()],
+            ..PtyConfig::default()
+        });
+        wait_for(&backend, |screen| screen.contains("ready")).await;
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            trigger.cancel();
+        });
+
+        // On the single-threaded test runtime the timer above only fires if
+        // the write leaves the executor free
+        let started = Instant::now();
+        let text = "x".repeat(1 << 20);
+        let error = backend.write_text(&TerminalTarget::default(), &text, &cancel).await.unwrap_err();
+        assert!(error.to_string().contains("cancelled"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn sessions_are_selected_by_id() {
+        let backend = sh_backend();
+        let cancel = CancellationToken::new();
+        let first = backend.active_session().unwrap();
+        backend.spawn_session().unwrap();
+
+        // pty-2 is now active; pty-1 can still be targeted explicitly
+        let pty1 = TerminalTarget {
+            session: Some("pty-1".to_string()),
+            window: None,
+        };
+        backend.write_text(&pty1, "echo first-$((2 + 2))", &cancel).await.unwrap();
+        let started = Instant::now();
+        while !first.read_lines(50).lines().any(|line| line.ends_with("first-4")) {
+            assert!(started.elapsed() < Duration::from_secs(5), "{}", first.read_lines(50));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!backend.read_screen(&TerminalTarget::default(), 50).await.unwrap().contains("first-4"));
+
+        let missing = TerminalTarget {
+            session: Some("pty-9".to_string()),
+            window: None,
+        };
+        let error = backend.read_screen(&missing, 10).await.unwrap_err();
+        assert!(error.to_string().contains("pty-9"), "{}", error);
+    }
+}
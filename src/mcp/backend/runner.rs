@@ -0,0 +1,189 @@
+//! Command runner for backends that drive a terminal through its CLI
+//! (`tmux`, `screen`, `wezterm cli`, `kitty @`, ...).
+//!
+//! Backends take an `Arc<dyn CommandRunner>` so tests can inject a mock and
+//! check the exact command lines without the terminal being installed.
+
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// Default timeout for a single invocation
+pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Captured result of a finished command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Whether the command exited with status 0
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    /// A successful run printing `stdout`.
+    pub fn ok(stdout: impl Into<String>) -> Self {
+        CommandOutput {
+            success: true,
+            stdout: stdout.into(),
+            stderr: String::new(),
+        }
+    }
+
+    /// The stdout of a successful run, or an error carrying the command's message.
+    pub fn into_stdout(self, what: &str) -> Result<String> {
+        if self.success {
+            return Ok(self.stdout);
+        }
+        // Some tools (screen) report errors on stdout
+        let message = if self.stderr.trim().is_empty() {
+            self.stdout.trim()
+        } else {
+            self.stderr.trim()
+        };
+        Err(anyhow!("{} failed: {}", what, message))
+    }
+}
+
+/// Runs an external program to completion.
+#[async_trait]
+pub trait CommandRunner: Send + Sync {
+    /// Run `program` with `args`, aborting when `cancel` fires.
+    async fn run(&self, program: &str, args: &[String], cancel: &CancellationToken) -> Result<CommandOutput>;
+}
+
+/// Runs programs with `tokio::process`, killing them on timeout or cancellation.
+pub struct SystemCommandRunner {
+    timeout: Duration,
+}
+
+impl Default for SystemCommandRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemCommandRunner {
+    pub fn new() -> Self {
+        Self::with_timeout(DEFAULT_COMMAND_TIMEOUT)
+    }
+
+    pub fn with_timeout(timeout: Duration) -> Self {
+        SystemCommandRunner { timeout }
+    }
+}
+
+#[async_trait]
+impl CommandRunner for SystemCommandRunner {
+    async fn run(&self, program: &str, args: &[String], cancel: &CancellationToken) -> Result<CommandOutput> {
+        debug!("Running {} {:?}", program, args);
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run {}", program))?;
+
+        // Dropping the wait future kills the child
+        let output = tokio::select! {
+            output = tokio::time::timeout(self.timeout, child.wait_with_output()) => output
+                .map_err(|_| anyhow!("{} timed out after {:?}", program, self.timeout))??,
+            _ = cancel.cancelled() => return Err(anyhow!("{} was cancelled", program)),
+        };
+
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// In-memory runner replaying queued outputs and recording every command line.
+#[derive(Debug, Default)]
+pub struct MockCommandRunner {
+    responses: Mutex<VecDeque<CommandOutput>>,
+    calls: Mutex<Vec<Vec<String>>>,
+}
+
+impl MockCommandRunner {
+    /// Create a runner answering with `responses`, in order.
+    pub fn new(responses: Vec<CommandOutput>) -> Self {
+        MockCommandRunner {
+            responses: Mutex::new(responses.into()),
+            calls: Mutex::default(),
+        }
+    }
+
+    /// Command lines received so far, program first.
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl CommandRunner for MockCommandRunner {
+    async fn run(&self, program: &str, args: &[String], _cancel: &CancellationToken) -> Result<CommandOutput> {
+        let mut call = vec![program.to_string()];
+        call.extend_from_slice(args);
+        self.calls.lock().unwrap().push(call.clone());
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| anyhow!("MockCommandRunner: no more responses (called with {:?})", call))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn output_and_failures_are_captured() {
+        let runner = SystemCommandRunner::new();
+        let cancel = CancellationToken::new();
+
+        let output = runner.run("sh", &args(&["-c", "echo out"]), &cancel).await.unwrap();
+        assert_eq!(output, CommandOutput::ok("out\n"));
+
+        let output = runner
+            .run("sh", &args(&["-c", "echo oops; exit 3"]), &cancel)
+            .await
+            .unwrap();
+        assert!(!output.success);
+        let error = output.into_stdout("sh").unwrap_err();
+        assert_eq!(error.to_string(), "sh failed: oops");
+    }
+
+    #[tokio::test]
+    async fn slow_commands_time_out_or_are_cancelled() {
+        let runner = SystemCommandRunner::with_timeout(Duration::from_millis(100));
+        let error = runner
+            .run("sleep", &args(&["5"]), &CancellationToken::new())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{}", error);
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let error = SystemCommandRunner::new()
+            .run("sleep", &args(&["5"]), &cancel)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("cancelled"), "{}", error);
+    }
+}
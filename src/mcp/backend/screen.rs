@@ -0,0 +1,496 @@
+//! GNU screen backend: drives a window of a running screen session.
+//!
+//! Text and control characters are typed with `screen -X stuff` and the
+//! screen is read with `hardcopy -h` into a private temporary file. Like the
+//! tmux backend, every operation is a short-lived `screen` client invocation.
+//!
+//! The session (`-S`) and window (`-p`) come from the tool call's
+//! [`TerminalTarget`], falling back to [`ScreenConfig`].
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+
+use super::runner::{CommandRunner, SystemCommandRunner};
+use super::{foreground_process, last_captured_lines, BackendCapabilities, ControlKey, TerminalBackend, TerminalSession};
+use crate::mcp::types::{ProcessInfo, TerminalTarget};
+
+/// How long to wait for screen to write a hardcopy
+const HARDCOPY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Characters typed per `stuff` command; screen limits the size of a command message
+const STUFF_CHUNK_CHARS: usize = 256;
+
+/// Which screen session and window the backend acts on.
+#[derive(Debug, Clone, Default)]
+pub struct ScreenConfig {
+    /// Session name or `pid.name` (`screen -S`); screen picks the only
+    /// session when unset
+    pub session: Option<String>,
+    /// Window number or title (`screen -p`); the session's current window
+    /// when unset
+    pub window: Option<String>,
+}
+
+/// Backend acting on a window of a screen session.
+pub struct ScreenBackend {
+    config: ScreenConfig,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Default for ScreenBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScreenBackend {
+    /// Create a backend for the only screen session of the user.
+    pub fn new() -> Self {
+        Self::with_config(ScreenConfig::default())
+    }
+
+    /// Create a backend for a specific session and window.
+    pub fn with_config(config: ScreenConfig) -> Self {
+        Self::with_runner(config, Arc::new(SystemCommandRunner::new()))
+    }
+
+    /// Create a backend with an injected runner (useful for tests).
+    pub fn with_runner(config: ScreenConfig, runner: Arc<dyn CommandRunner>) -> Self {
+        ScreenBackend { config, runner }
+    }
+
+    /// Session and window selected by `target`.
+    ///
+    /// The configured window only applies to the configured session.
+    fn selection(&self, target: &TerminalTarget) -> (Option<String>, Option<String>) {
+        match &target.session {
+            Some(session) => (Some(session.clone()), target.window.clone()),
+            None => (
+                self.config.session.clone(),
+                target.window.clone().or_else(|| self.config.window.clone()),
+            ),
+        }
+    }
+
+    /// Run `screen [-S session] [-p window] -X <command>` against `target`.
+    async fn command(&self, target: &TerminalTarget, command: &[&str], cancel: &CancellationToken) -> Result<()> {
+        let (session, window) = self.selection(target);
+        let mut args = Vec::new();
+        if let Some(session) = session {
+            args.extend(["-S".to_string(), session]);
+        }
+        if let Some(window) = window {
+            args.extend(["-p".to_string(), window]);
+        }
+        args.push("-X".to_string());
+        args.extend(command.iter().map(|arg| arg.to_string()));
+
+        let output = self.runner.run("screen", &args, cancel).await?;
+        output.into_stdout(&format!("screen {}", command[0]))?;
+        Ok(())
+    }
+
+    /// Type `input`, already escaped for `stuff`.
+    async fn stuff(&self, target: &TerminalTarget, input: &str, cancel: &CancellationToken) -> Result<()> {
+        self.command(target, &["stuff", input], cancel).await
+    }
+
+    /// The sessions listed by `screen -ls`.
+    async fn screen_sessions(&self) -> Result<Vec<ScreenSession>> {
+        // Older releases exit with 1 even when sessions exist, so the status is ignored
+        let output = self
+            .runner
+            .run("screen", &["-ls".to_string()], &CancellationToken::new())
+            .await?;
+        Ok(parse_screen_ls(&output.stdout))
+    }
+
+    /// The session `target` selects, resolved against `screen -ls`.
+    async fn find_session(&self, target: &TerminalTarget) -> Result<ScreenSession> {
+        let (selected, _) = self.selection(target);
+        let sessions = self.screen_sessions().await?;
+        match selected {
+            Some(selected) => sessions
+                .into_iter()
+                .find(|session| session.matches(&selected))
+                .ok_or_else(|| anyhow!("No screen session named {}", selected)),
+            None if sessions.len() == 1 => Ok(sessions.into_iter().next().unwrap()),
+            None => Err(anyhow!("{} screen sessions are running; select one", sessions.len())),
+        }
+    }
+}
+
+#[async_trait]
+impl TerminalBackend for ScreenBackend {
+    fn name(&self) -> &'static str {
+        "screen"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            select_session: true,
+            select_window: true,
+            ..BackendCapabilities::default()
+        }
+    }
+
+    async fn write_text(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()> {
+        let chars: Vec<char> = text.chars().collect();
+        for chunk in chars.chunks(STUFF_CHUNK_CHARS) {
+            let chunk: String = chunk.iter().collect();
+            self.stuff(target, &escape_stuff(&chunk), cancel).await?;
+        }
+        self.stuff(target, "^M", cancel).await
+    }
+
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String> {
+        let dir = HardcopyDir::create()?;
+        let path = dir.path().join("hardcopy.txt");
+        let path_arg = path.to_string_lossy().into_owned();
+        self.command(target, &["hardcopy", "-h", &path_arg], &CancellationToken::new())
+            .await?;
+
+        let capture = wait_for_hardcopy(&path).await?;
+        Ok(last_captured_lines(&capture, lines))
+    }
+
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()> {
+        // Octal escapes cover `^[`, `^]` and friends as well as letters
+        let input = format!("\\{:03o}", key.code);
+        self.stuff(target, &input, &CancellationToken::new()).await
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>> {
+        let sessions = self.screen_sessions().await?;
+        let only = sessions.len() == 1;
+        Ok(sessions
+            .into_iter()
+            .map(|session| {
+                let active = match &self.config.session {
+                    Some(selected) => session.matches(selected),
+                    None => only,
+                };
+                TerminalSession {
+                    id: session.id,
+                    name: session.name,
+                    tty: None,
+                    active,
+                    ..TerminalSession::default()
+                }
+            })
+            .collect())
+    }
+
+    async fn query_process(&self, target: &TerminalTarget) -> Result<Option<ProcessInfo>> {
+        let session = self.find_session(target).await?;
+        let (_, window) = self.selection(target);
+
+        let args = ["-A", "-o", "pid=,ppid=,tty="].map(str::to_string);
+        let output = self
+            .runner
+            .run("ps", &args, &CancellationToken::new())
+            .await?
+            .into_stdout("ps")?;
+        let windows = window_processes(&output, session.pid);
+
+        let tty = match (window, windows.as_slice()) {
+            (_, []) => return Ok(None),
+            (None, [only]) => only.1.clone(),
+            (None, _) => return Err(anyhow!("Session {} has several windows; select one", session.id)),
+            (Some(window), _) => windows
+                .iter()
+                .find(|(pid, _)| window_number(*pid).as_deref() == Some(window.as_str()))
+                .map(|(_, tty)| tty.clone())
+                .ok_or_else(|| anyhow!("No window {} in screen session {}", window, session.id))?,
+        };
+        tokio::task::spawn_blocking(move || foreground_process(&tty)).await?
+    }
+}
+
+/// A session listed by `screen -ls`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ScreenSession {
+    /// `pid.name`, as accepted by `screen -S`
+    id: String,
+    pid: u32,
+    name: String,
+}
+
+impl ScreenSession {
+    /// Whether `selector` (a name, `pid.name` or pid) designates this session.
+    fn matches(&self, selector: &str) -> bool {
+        selector == self.id || selector == self.name || selector == self.pid.to_string()
+    }
+}
+
+/// Parse the `<TAB>pid.name<TAB>(...)` lines of `screen -ls`.
+fn parse_screen_ls(output: &str) -> Vec<ScreenSession> {
+    output
+        .lines()
+        .filter(|line| line.starts_with('\t'))
+        .filter_map(|line| {
+            let id = line.split_whitespace().next()?;
+            let (pid, name) = id.split_once('.')?;
+            Some(ScreenSession {
+                id: id.to_string(),
+                pid: pid.parse().ok()?,
+                name: name.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Escape text for `stuff`, which interprets `\`, `^` and `$` sequences.
+fn escape_stuff(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '^' | '$') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Children of the screen process (one per window) as `(pid, tty)`, from
+/// `ps -A -o pid=,ppid=,tty=` output.
+fn window_processes(output: &str, screen_pid: u32) -> Vec<(u32, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid: u32 = fields.next()?.parse().ok()?;
+            let tty = fields.next().filter(|tty| *tty != "?" && *tty != "??")?;
+            (ppid == screen_pid).then(|| (pid, format!("/dev/{}", tty)))
+        })
+        .collect()
+}
+
+/// Window number of a process started by screen, from its `WINDOW` variable.
+///
+/// Only available where `/proc` exposes process environments (Linux).
+fn window_number(pid: u32) -> Option<String> {
+    let environ = std::fs::read(format!("/proc/{}/environ", pid)).ok()?;
+    environ
+        .split(|byte| *byte == 0)
+        .find_map(|entry| entry.strip_prefix(b"WINDOW="))
+        .map(|number| String::from_utf8_lossy(number).into_owned())
+}
+
+/// Owner-only temporary directory for one hardcopy, removed when dropped.
+struct HardcopyDir {
+    path: PathBuf,
+}
+
+impl HardcopyDir {
+    fn create() -> Result<Self> {
+        use std::os::unix::fs::DirBuilderExt;
+
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "rs_iterm-screen-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(HardcopyDir { path })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for HardcopyDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Wait for the screen session to finish writing `path` and read it.
+///
+/// `screen -X` returns once the command is delivered, before the session has
+/// written the file, so the size is polled until it stops changing.
+async fn wait_for_hardcopy(path: &Path) -> Result<String> {
+    let started = Instant::now();
+    let mut last_size = None;
+    loop {
+        let size = std::fs::metadata(path).map(|meta| meta.len()).ok();
+        if size.is_some_and(|size| size > 0) && size == last_size {
+            let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            return Ok(String::from_utf8_lossy(&bytes).into_owned());
+        }
+        if started.elapsed() > HARDCOPY_TIMEOUT {
+            return Err(anyhow!("screen did not write a hardcopy within {:?}", HARDCOPY_TIMEOUT));
+        }
+        last_size = size;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::backend::runner::{CommandOutput, MockCommandRunner};
+    use std::sync::Mutex;
+
+    const SCREEN_LS: &str = "There are screens on:
+\t4242.deploy\t(10/16/2026 09:12:01 AM)\t(Detached)
+\t777.pts-0.build\t(Attached)
+2 Sockets in /run/screen/S-agent.
+";
+
+    /// Runner standing in for a screen session: `hardcopy` writes `screen`
+    /// to the requested file, everything else succeeds silently.
+    struct HardcopyRunner {
+        screen: String,
+        calls: Mutex<Vec<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl CommandRunner for HardcopyRunner {
+        async fn run(&self, _program: &str, args: &[String], _cancel: &CancellationToken) -> Result<CommandOutput> {
+            self.calls.lock().unwrap().push(args.to_vec());
+            if let Some(index) = args.iter().position(|arg| arg == "hardcopy") {
+                std::fs::write(&args[index + 2], &self.screen)?;
+            }
+            Ok(CommandOutput::ok(""))
+        }
+    }
+
+    fn target(session: Option<&str>, window: Option<&str>) -> TerminalTarget {
+        TerminalTarget {
+            session: session.map(str::to_string),
+            window: window.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn stuff_input_is_escaped() {
+        assert_eq!(escape_stuff("echo $HOME ^C \\n"), "echo \\$HOME \\^C \\\\n");
+        assert_eq!(escape_stuff("ls -la"), "ls -la");
+    }
+
+    #[test]
+    fn sessions_are_parsed_from_screen_ls() {
+        let sessions = parse_screen_ls(SCREEN_LS);
+        assert_eq!(
+            sessions,
+            vec![
+                ScreenSession {
+                    id: "4242.deploy".to_string(),
+                    pid: 4242,
+                    name: "deploy".to_string(),
+                },
+                ScreenSession {
+                    id: "777.pts-0.build".to_string(),
+                    pid: 777,
+                    name: "pts-0.build".to_string(),
+                },
+            ]
+        );
+        assert!(sessions[0].matches("deploy") && sessions[0].matches("4242") && sessions[0].matches("4242.deploy"));
+        assert!(parse_screen_ls("No Sockets found in /run/screen/S-agent.\n").is_empty());
+    }
+
+    #[test]
+    fn window_processes_are_children_of_the_session() {
+        let output = "    1     0 ?\n 4242     1 ?\n 4243  4242 pts/3\n 4250  4242 pts/4\n 4251  4243 pts/3\n";
+        assert_eq!(
+            window_processes(output, 4242),
+            vec![(4243, "/dev/pts/3".to_string()), (4250, "/dev/pts/4".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn text_and_control_keys_are_stuffed_into_the_selected_window() {
+        let runner = Arc::new(MockCommandRunner::new(vec![CommandOutput::ok(""); 5]));
+        let config = ScreenConfig {
+            session: Some("deploy".to_string()),
+            window: Some("0".to_string()),
+        };
+        let backend = ScreenBackend::with_runner(config, runner.clone());
+        let cancel = CancellationToken::new();
+
+        backend.write_text(&target(None, None), "echo $USER", &cancel).await.unwrap();
+        backend.write_text(&target(Some("build"), None), "make", &cancel).await.unwrap();
+        backend
+            .send_control(&target(None, Some("logs")), ControlKey::parse("c").unwrap())
+            .await
+            .unwrap();
+
+        let calls: Vec<String> = runner.calls().iter().map(|call| call.join(" ")).collect();
+        assert_eq!(
+            calls,
+            vec![
+                "screen -S deploy -p 0 -X stuff echo \\$USER",
+                "screen -S deploy -p 0 -X stuff ^M",
+                "screen -S build -X stuff make",
+                "screen -S build -X stuff ^M",
+                "screen -S deploy -p logs -X stuff \\003",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn screen_errors_are_reported() {
+        let runner = Arc::new(MockCommandRunner::new(vec![CommandOutput {
+            success: false,
+            stdout: "No screen session found.\n".to_string(),
+            stderr: String::new(),
+        }]));
+        let backend = ScreenBackend::with_runner(ScreenConfig::default(), runner);
+
+        let error = backend
+            .write_text(&target(Some("missing"), None), "ls", &CancellationToken::new())
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "screen stuff failed: No screen session found.");
+    }
+
+    #[tokio::test]
+    async fn the_screen_is_read_from_a_hardcopy() {
+        let runner = Arc::new(HardcopyRunner {
+            screen: "$ make\nbuilding\ndone\n$\n\n\n\n".to_string(),
+            calls: Mutex::default(),
+        });
+        let backend = ScreenBackend::with_runner(ScreenConfig::default(), runner.clone());
+
+        let screen = backend.read_screen(&target(Some("deploy"), Some("2")), 2).await.unwrap();
+        assert_eq!(screen, "done\n$");
+
+        let calls = runner.calls.lock().unwrap();
+        assert_eq!(calls[0][..6], ["-S", "deploy", "-p", "2", "-X", "hardcopy"]);
+        assert_eq!(calls[0][6], "-h");
+        // The private directory is removed after reading
+        assert!(!Path::new(&calls[0][7]).exists());
+    }
+
+    #[tokio::test]
+    async fn sessions_are_listed_with_the_configured_one_active() {
+        let runner = Arc::new(MockCommandRunner::new(vec![CommandOutput {
+            success: false,
+            stdout: SCREEN_LS.to_string(),
+            stderr: String::new(),
+        }]));
+        let config = ScreenConfig {
+            session: Some("777".to_string()),
+            window: None,
+        };
+        let backend = ScreenBackend::with_runner(config, runner);
+
+        let sessions = backend.list_sessions().await.unwrap();
+        let active: Vec<(&str, bool)> = sessions.iter().map(|s| (s.id.as_str(), s.active)).collect();
+        assert_eq!(active, vec![("4242.deploy", false), ("777.pts-0.build", true)]);
+    }
+}
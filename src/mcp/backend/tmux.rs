@@ -0,0 +1,417 @@
+//! tmux backend: drives a pane of a running tmux server.
+//!
+//! Text is typed with `send-keys -l`, the screen is read with
+//! `capture-pane -p -S -N` and control characters are sent as `send-keys C-x`.
+//! Every operation is a short-lived `tmux` client invocation, so the backend
+//! holds no connection state and works with any server the user can reach.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio_util::sync::CancellationToken;
+
+use super::runner::{CommandRunner, SystemCommandRunner};
+use super::{foreground_process, last_captured_lines, BackendCapabilities, ControlKey, TerminalBackend, TerminalSession};
+use crate::mcp::types::{ProcessInfo, TerminalTarget};
+
+/// Format of `list-panes`:
+/// `id<TAB>target<TAB>title<TAB>tty<TAB>session<TAB>window<TAB>width<TAB>height`
+const LIST_PANES_FORMAT: &str = "#{pane_id}\t#{session_name}:#{window_index}.#{pane_index}\t#{pane_title}\t#{pane_tty}\t#{session_name}\t#{window_index}\t#{pane_width}\t#{pane_height}";
+
+/// Which tmux server and pane the backend acts on.
+#[derive(Debug, Clone, Default)]
+pub struct TmuxConfig {
+    /// Socket name (`tmux -L`); the default server when unset
+    pub socket_name: Option<String>,
+    /// Target pane (`-t`, e.g. `work:1.0` or `%3`); tmux picks the current
+    /// pane when unset
+    pub target: Option<String>,
+}
+
+/// Backend acting on a tmux pane.
+///
+/// A [`TerminalTarget`] maps onto a tmux target: `session` and `window`
+/// become `session:window`, selecting the active pane of that window.
+pub struct TmuxBackend {
+    config: TmuxConfig,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Default for TmuxBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TmuxBackend {
+    /// Create a backend for the current pane of the default server.
+    pub fn new() -> Self {
+        Self::with_config(TmuxConfig::default())
+    }
+
+    /// Create a backend for a specific server and pane.
+    pub fn with_config(config: TmuxConfig) -> Self {
+        Self::with_runner(config, Arc::new(SystemCommandRunner::new()))
+    }
+
+    /// Create a backend with an injected runner (useful for tests).
+    pub fn with_runner(config: TmuxConfig, runner: Arc<dyn CommandRunner>) -> Self {
+        TmuxBackend { config, runner }
+    }
+
+    /// Run a tmux command and return its stdout.
+    async fn tmux(&self, args: &[&str], cancel: &CancellationToken) -> Result<String> {
+        let mut full = Vec::new();
+        if let Some(socket) = &self.config.socket_name {
+            full.extend(["-L".to_string(), socket.clone()]);
+        }
+        full.extend(args.iter().map(|arg| arg.to_string()));
+        let output = self.runner.run("tmux", &full, cancel).await?;
+        output.into_stdout(&format!("tmux {}", args[0]))
+    }
+
+    /// The `-t` argument selecting `target`, or the configured pane.
+    fn pane_target(&self, target: &TerminalTarget) -> Option<String> {
+        match (&target.session, &target.window) {
+            (None, None) => self.config.target.clone(),
+            (Some(session), None) => Some(session.clone()),
+            (session, Some(window)) => Some(format!("{}:{}", session.as_deref().unwrap_or_default(), window)),
+        }
+    }
+
+    /// Run a tmux command against a pane (`<command> -t <target> <args>`).
+    async fn tmux_pane(
+        &self,
+        target: &TerminalTarget,
+        command: &str,
+        args: &[&str],
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        let pane = self.pane_target(target);
+        let mut full = vec![command];
+        if let Some(pane) = &pane {
+            full.extend(["-t", pane.as_str()]);
+        }
+        full.extend_from_slice(args);
+        self.tmux(&full, cancel).await
+    }
+
+    /// Query a format variable (`#{pane_id}`, ...) of a pane.
+    async fn pane_format(&self, target: &TerminalTarget, format: &str) -> Result<String> {
+        let value = self
+            .tmux_pane(target, "display-message", &["-p", format], &CancellationToken::new())
+            .await?;
+        Ok(value.trim_end_matches('\n').to_string())
+    }
+}
+
+#[async_trait]
+impl TerminalBackend for TmuxBackend {
+    fn name(&self) -> &'static str {
+        "tmux"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            select_session: true,
+            select_window: true,
+            ..BackendCapabilities::default()
+        }
+    }
+
+    async fn write_text(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()> {
+        // tmux treats an argument ending in `;` as a command separator, so
+        // trailing semicolons are sent as hex keys instead
+        let literal = text.trim_end_matches(';');
+        self.tmux_pane(target, "send-keys", &["-l", "--", literal], cancel).await?;
+        let semicolons = text.len() - literal.len();
+        if semicolons > 0 {
+            let mut args = vec!["-H"];
+            args.extend(std::iter::repeat_n("3b", semicolons));
+            self.tmux_pane(target, "send-keys", &args, cancel).await?;
+        }
+        self.tmux_pane(target, "send-keys", &["Enter"], cancel).await?;
+        Ok(())
+    }
+
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String> {
+        let start = format!("-{}", lines);
+        let output = self
+            .tmux_pane(target, "capture-pane", &["-p", "-J", "-S", &start], &CancellationToken::new())
+            .await?;
+        Ok(last_captured_lines(&output, lines))
+    }
+
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()> {
+        let cancel = CancellationToken::new();
+        if key.letter.is_ascii_alphabetic() {
+            let name = format!("C-{}", key.letter.to_ascii_lowercase());
+            self.tmux_pane(target, "send-keys", &[&name], &cancel).await?;
+        } else {
+            // `C-[`, `C-]`, ... have no portable key names; send the raw byte
+            let code = format!("{:02x}", key.code);
+            self.tmux_pane(target, "send-keys", &["-H", &code], &cancel).await?;
+        }
+        Ok(())
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>> {
+        let active = self.pane_format(&TerminalTarget::default(), "#{pane_id}").await?;
+        let output = self
+            .tmux(&["list-panes", "-a", "-F", LIST_PANES_FORMAT], &CancellationToken::new())
+            .await?;
+        Ok(parse_panes(&output, &active))
+    }
+
+    async fn query_process(&self, target: &TerminalTarget) -> Result<Option<ProcessInfo>> {
+        let tty = self.pane_format(target, "#{pane_tty}").await?;
+        if tty.is_empty() {
+            return Ok(None);
+        }
+        tokio::task::spawn_blocking(move || foreground_process(&tty)).await?
+    }
+}
+
+/// Parse `list-panes` output in [`LIST_PANES_FORMAT`].
+///
+/// The pane the backend targets is reported as active, whatever tmux
+/// considers the active pane of each window. tmux sessions and windows are
+/// reported as the windows and tabs holding the panes.
+fn parse_panes(output: &str, active_id: &str) -> Vec<TerminalSession> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let id = fields.next()?.to_string();
+            let target = fields.next()?;
+            let title = fields.next().unwrap_or_default();
+            let tty = fields.next().filter(|tty| !tty.is_empty()).map(str::to_string);
+            let name = if title.is_empty() {
+                target.to_string()
+            } else {
+                format!("{} {}", target, title)
+            };
+            Some(TerminalSession {
+                active: id == active_id,
+                id,
+                name,
+                tty,
+                window: fields.next().map(str::to_string),
+                tab: fields.next().map(str::to_string),
+                columns: fields.next().and_then(|width| width.parse().ok()),
+                rows: fields.next().and_then(|height| height.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::backend::runner::{CommandOutput, MockCommandRunner};
+    use std::time::{Duration, Instant};
+
+    /// A private tmux server (`tmux -L`) killed when dropped.
+    struct TestServer {
+        socket: String,
+        socket_path: String,
+    }
+
+    impl TestServer {
+        /// Start a server running `/bin/sh`, or `None` when tmux is missing.
+        fn start() -> Option<Self> {
+            // Unique per test, so no other server's panes show up in `list-panes -a`
+            let socket = format!("rs_iterm_test_{}", uuid::Uuid::new_v4().simple());
+            let status = std::process::Command::new("tmux")
+                .args(["-L", &socket, "-f", "/dev/null", "new-session", "-d", "-s", "test"])
+                .args(["-x", "80", "-y", "24", "env PS1='$ ' /bin/sh"])
+                .status();
+            if !matches!(status, Ok(status) if status.success()) {
+                eprintln!("tmux is not available; skipping");
+                return None;
+            }
+            // kill-server leaves the socket file behind
+            let output = std::process::Command::new("tmux")
+                .args(["-L", &socket, "display-message", "-p", "#{socket_path}"])
+                .output()
+                .ok()?;
+            let socket_path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            Some(TestServer { socket, socket_path })
+        }
+
+        fn backend(&self) -> TmuxBackend {
+            TmuxBackend::with_config(TmuxConfig {
+                socket_name: Some(self.socket.clone()),
+                target: Some("test:0.0".to_string()),
+            })
+        }
+    }
+
+    impl Drop for TestServer {
+        fn drop(&mut self) {
+            let _ = std::process::Command::new("tmux")
+                .args(["-L", &self.socket, "kill-server"])
+                .status();
+            let _ = std::fs::remove_file(&self.socket_path);
+        }
+    }
+
+    /// Poll the pane until a line ends with `expected` or five seconds pass.
+    async fn wait_for_line(backend: &TmuxBackend, expected: &str) -> String {
+        let started = Instant::now();
+        loop {
+            let screen = backend.read_screen(&TerminalTarget::default(), 50).await.unwrap();
+            if screen.lines().any(|line| line.ends_with(expected)) {
+                return screen;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "timed out waiting for {:?}; screen:\n{}",
+                expected,
+                screen
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn targets_select_session_and_window() {
+        let runner = Arc::new(MockCommandRunner::new(vec![
+            CommandOutput::ok(""),
+            CommandOutput::ok(""),
+            CommandOutput::ok(""),
+            CommandOutput::ok(""),
+        ]));
+        let config = TmuxConfig {
+            socket_name: Some("agents".to_string()),
+            target: Some("main:0.0".to_string()),
+        };
+        let backend = TmuxBackend::with_runner(config, runner.clone());
+        let key = ControlKey::parse("c").unwrap();
+        let target = |session: Option<&str>, window: Option<&str>| TerminalTarget {
+            session: session.map(str::to_string),
+            window: window.map(str::to_string),
+        };
+
+        backend.send_control(&target(None, None), key).await.unwrap();
+        backend.send_control(&target(Some("work"), None), key).await.unwrap();
+        backend.send_control(&target(Some("work"), Some("2")), key).await.unwrap();
+        backend.send_control(&target(None, Some("logs")), key).await.unwrap();
+
+        let targets: Vec<String> = runner.calls().into_iter().map(|call| call[5].clone()).collect();
+        assert_eq!(targets, vec!["main:0.0", "work", "work:2", ":logs"]);
+        assert_eq!(runner.calls()[0], vec!["tmux", "-L", "agents", "send-keys", "-t", "main:0.0", "C-c"]);
+    }
+
+    #[test]
+    fn panes_are_parsed_from_list_panes_output() {
+        let output = "%0\twork:0.0\tvim\t/dev/pts/3\twork\t0\t80\t24\n%4\twork:1.0\t\t/dev/pts/7\twork\t1\t120\t40\n";
+        let panes = parse_panes(output, "%4");
+        assert_eq!(
+            panes,
+            vec![
+                TerminalSession {
+                    id: "%0".to_string(),
+                    name: "work:0.0 vim".to_string(),
+                    tty: Some("/dev/pts/3".to_string()),
+                    active: false,
+                    window: Some("work".to_string()),
+                    tab: Some("0".to_string()),
+                    columns: Some(80),
+                    rows: Some(24),
+                },
+                TerminalSession {
+                    id: "%4".to_string(),
+                    name: "work:1.0".to_string(),
+                    tty: Some("/dev/pts/7".to_string()),
+                    active: true,
+                    window: Some("work".to_string()),
+                    tab: Some("1".to_string()),
+                    columns: Some(120),
+                    rows: Some(40),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn commands_run_in_the_pane() {
+        let Some(server) = TestServer::start() else { return };
+        let backend = server.backend();
+        let cancel = CancellationToken::new();
+        let active = TerminalTarget::default();
+
+        backend.write_text(&active, "echo tmux-$((6 * 7))", &cancel).await.unwrap();
+        wait_for_line(&backend, "tmux-42").await;
+
+        // A trailing semicolon is typed, not swallowed as a separator
+        backend.write_text(&active, "echo semi;", &cancel).await.unwrap();
+        wait_for_line(&backend, "$ echo semi;").await;
+
+        // Only the panes of the test session, once tmux has settled on them
+        let started = Instant::now();
+        let sessions = loop {
+            let sessions: Vec<TerminalSession> = backend
+                .list_sessions()
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|session| session.window.as_deref() == Some("test"))
+                .collect();
+            if sessions.len() == 1 || started.elapsed() > Duration::from_secs(5) {
+                break sessions;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        };
+        assert_eq!(sessions.len(), 1, "panes: {:?}", sessions);
+        assert!(sessions[0].id.starts_with('%'));
+        assert!(sessions[0].name.starts_with("test:0.0"));
+        assert!(sessions[0].active);
+        assert!(sessions[0].tty.as_deref().unwrap().starts_with("/dev/"));
+        assert_eq!(sessions[0].window.as_deref(), Some("test"));
+        assert_eq!((sessions[0].columns, sessions[0].rows), (Some(80), Some(24)));
+    }
+
+    #[tokio::test]
+    async fn control_c_interrupts_the_foreground_process() {
+        let Some(server) = TestServer::start() else { return };
+        let backend = server.backend();
+        let cancel = CancellationToken::new();
+        let active = TerminalTarget::default();
+
+        backend.write_text(&active, "sleep 30", &cancel).await.unwrap();
+        let started = Instant::now();
+        loop {
+            let process = backend.query_process(&active).await.unwrap();
+            if process.as_ref().map(|p| p.name.as_str()) == Some("sleep") {
+                break;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5), "sleep never started: {:?}", process);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        backend.send_control(&active, ControlKey::parse("c").unwrap()).await.unwrap();
+        backend.write_text(&active, "echo after-$((1 + 1))", &cancel).await.unwrap();
+        wait_for_line(&backend, "after-2").await;
+    }
+
+    #[tokio::test]
+    async fn unknown_targets_are_reported() {
+        let Some(server) = TestServer::start() else { return };
+        let backend = TmuxBackend::with_config(TmuxConfig {
+            socket_name: Some(server.socket.clone()),
+            target: Some("missing:9".to_string()),
+        });
+
+        let error = backend.read_screen(&TerminalTarget::default(), 10).await.unwrap_err();
+        assert!(error.to_string().contains("capture-pane"), "{}", error);
+
+        let missing = TerminalTarget {
+            session: Some("test".to_string()),
+            window: Some("9".to_string()),
+        };
+        let error = server.backend().read_screen(&missing, 10).await.unwrap_err();
+        assert!(error.to_string().contains("capture-pane"), "{}", error);
+    }
+}
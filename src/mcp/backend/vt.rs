@@ -0,0 +1,565 @@
+//! Headless VT100/xterm emulator.
+//!
+//! [`VtScreen`] interprets terminal output the way a terminal would: cursor
+//! movement, erase, insert/delete, scroll regions and the alternate screen.
+//! Full-screen programs (vim, top, progress bars) therefore read back as the
+//! rendered grid instead of a stream of redraws. Lines scrolled off the top of
+//! the primary screen are kept in a scrollback.
+//!
+//! Colors and other attributes are parsed and dropped, and every character
+//! occupies a single cell.
+
+use std::collections::VecDeque;
+
+use super::pty::OutputSink;
+
+/// Parser state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    /// `ESC (`, `ESC #`, ...: one more character to skip
+    EscapeIntermediate,
+    Csi,
+    Osc,
+    OscEscape,
+    /// DCS, SOS, PM and APC strings, skipped up to ST
+    Passthrough,
+    PassthroughEscape,
+}
+
+/// A terminal screen driven by raw output.
+#[derive(Debug)]
+pub struct VtScreen {
+    rows: usize,
+    cols: usize,
+    grid: Vec<Vec<char>>,
+    /// The primary grid while the alternate screen is shown
+    primary: Option<Vec<Vec<char>>>,
+    row: usize,
+    col: usize,
+    saved_cursor: (usize, usize),
+    /// The last column was written; the next character wraps first
+    wrap_pending: bool,
+    autowrap: bool,
+    /// Scroll region, inclusive
+    top: usize,
+    bottom: usize,
+    scrollback: VecDeque<String>,
+    limit: usize,
+    state: State,
+    sequence: String,
+    pending_utf8: Vec<u8>,
+}
+
+impl VtScreen {
+    /// Create a blank `rows` x `cols` screen keeping up to `limit` scrollback lines.
+    pub fn new(rows: usize, cols: usize, limit: usize) -> Self {
+        let rows = rows.max(1);
+        let cols = cols.max(1);
+        VtScreen {
+            rows,
+            cols,
+            grid: blank_grid(rows, cols),
+            primary: None,
+            row: 0,
+            col: 0,
+            saved_cursor: (0, 0),
+            wrap_pending: false,
+            autowrap: true,
+            top: 0,
+            bottom: rows - 1,
+            scrollback: VecDeque::new(),
+            limit,
+            state: State::Ground,
+            sequence: String::new(),
+            pending_utf8: Vec::new(),
+        }
+    }
+
+    /// Cursor position as `(row, column)`, zero-based.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Whether the alternate screen is shown.
+    pub fn is_alternate(&self) -> bool {
+        self.primary.is_some()
+    }
+
+    /// Every row of the visible grid, without trailing spaces.
+    pub fn rows(&self) -> Vec<String> {
+        self.grid.iter().map(|row| render_row(row)).collect()
+    }
+
+    /// Append raw output.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let mut data = std::mem::take(&mut self.pending_utf8);
+        data.extend_from_slice(bytes);
+
+        let text = match std::str::from_utf8(&data) {
+            Ok(text) => text.to_string(),
+            Err(e) if e.error_len().is_none() => {
+                // Incomplete character at the end of the chunk; keep it for later
+                let (valid, rest) = data.split_at(e.valid_up_to());
+                self.pending_utf8 = rest.to_vec();
+                String::from_utf8_lossy(valid).into_owned()
+            }
+            Err(_) => String::from_utf8_lossy(&data).into_owned(),
+        };
+
+        for c in text.chars() {
+            self.push_char(c);
+        }
+    }
+
+    /// The last `n` lines of scrollback and screen, down to the cursor or the
+    /// last non-blank row. The alternate screen has no scrollback.
+    pub fn last_lines(&self, n: usize) -> String {
+        let rows = self.rows();
+        let used = rows
+            .iter()
+            .rposition(|row| !row.is_empty())
+            .map_or(0, |last| last + 1)
+            .max(self.row + 1);
+
+        let mut all: Vec<&str> = Vec::new();
+        if !self.is_alternate() {
+            all.extend(self.scrollback.iter().map(String::as_str));
+        }
+        all.extend(rows[..used].iter().map(String::as_str));
+        let start = all.len().saturating_sub(n);
+        all[start..].join("\n")
+    }
+
+    fn push_char(&mut self, c: char) {
+        match self.state {
+            State::Ground => self.ground(c),
+            State::Escape => self.escape(c),
+            State::EscapeIntermediate => self.state = State::Ground,
+            State::Csi => match c {
+                '\x1b' => self.state = State::Escape,
+                '\x20'..='\x3f' => self.sequence.push(c),
+                '\x40'..='\x7e' => {
+                    self.state = State::Ground;
+                    let sequence = std::mem::take(&mut self.sequence);
+                    self.csi(&sequence, c);
+                }
+                c if c.is_control() => self.control(c),
+                _ => self.state = State::Ground,
+            },
+            State::Osc => match c {
+                '\x07' => self.state = State::Ground,
+                '\x1b' => self.state = State::OscEscape,
+                _ => {}
+            },
+            State::OscEscape => self.state = if c == '\\' { State::Ground } else { State::Osc },
+            State::Passthrough => {
+                if c == '\x1b' {
+                    self.state = State::PassthroughEscape;
+                }
+            }
+            State::PassthroughEscape => {
+                self.state = if c == '\\' {
+                    State::Ground
+                } else {
+                    State::Passthrough
+                }
+            }
+        }
+    }
+
+    fn ground(&mut self, c: char) {
+        if c == '\x1b' {
+            self.state = State::Escape;
+        } else if c.is_control() {
+            self.control(c);
+        } else {
+            self.print(c);
+        }
+    }
+
+    fn control(&mut self, c: char) {
+        match c {
+            '\x08' => {
+                self.wrap_pending = false;
+                self.col = self.col.saturating_sub(1);
+            }
+            '\t' => {
+                self.wrap_pending = false;
+                self.col = ((self.col / 8 + 1) * 8).min(self.cols - 1);
+            }
+            '\n' | '\x0b' | '\x0c' => self.line_feed(),
+            '\r' => {
+                self.wrap_pending = false;
+                self.col = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn escape(&mut self, c: char) {
+        self.state = State::Ground;
+        match c {
+            '[' => {
+                self.sequence.clear();
+                self.state = State::Csi;
+            }
+            ']' => self.state = State::Osc,
+            'P' | 'X' | '^' | '_' => self.state = State::Passthrough,
+            '(' | ')' | '*' | '+' | '#' | '%' => self.state = State::EscapeIntermediate,
+            '7' => self.save_cursor(),
+            '8' => self.restore_cursor(),
+            'D' => self.line_feed(),
+            'E' => {
+                self.col = 0;
+                self.line_feed();
+            }
+            'M' => self.reverse_index(),
+            'c' => *self = VtScreen::new(self.rows, self.cols, self.limit),
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, sequence: &str, action: char) {
+        let private = sequence.starts_with(['?', '>', '<', '=']);
+        let params: Vec<usize> = sequence
+            .trim_start_matches(['?', '>', '<', '='])
+            .split(';')
+            .map(|param| param.trim_end_matches(|c: char| !c.is_ascii_digit()).parse().unwrap_or(0))
+            .collect();
+        let param = |index: usize| params.get(index).copied().unwrap_or(0);
+        // Movement and repeat counts treat 0 as 1
+        let count = param(0).max(1);
+
+        if private {
+            match action {
+                'h' => params.iter().for_each(|&mode| self.set_mode(mode, true)),
+                'l' => params.iter().for_each(|&mode| self.set_mode(mode, false)),
+                _ => {}
+            }
+            return;
+        }
+
+        self.wrap_pending = false;
+        match action {
+            'A' => self.row = self.row.saturating_sub(count).max(self.upper_limit()),
+            'B' | 'e' => self.row = (self.row + count).min(self.lower_limit()),
+            'C' | 'a' => self.col = (self.col + count).min(self.cols - 1),
+            'D' => self.col = self.col.saturating_sub(count),
+            'E' => {
+                self.row = (self.row + count).min(self.lower_limit());
+                self.col = 0;
+            }
+            'F' => {
+                self.row = self.row.saturating_sub(count).max(self.upper_limit());
+                self.col = 0;
+            }
+            'G' | '`' => self.col = (count - 1).min(self.cols - 1),
+            'd' => self.row = (count - 1).min(self.rows - 1),
+            'H' | 'f' => {
+                self.row = (param(0).max(1) - 1).min(self.rows - 1);
+                self.col = (param(1).max(1) - 1).min(self.cols - 1);
+            }
+            'J' => self.erase_display(param(0)),
+            'K' => self.erase_line(param(0)),
+            '@' => {
+                let row = &mut self.grid[self.row];
+                for _ in 0..count.min(self.cols - self.col) {
+                    row.pop();
+                    row.insert(self.col, ' ');
+                }
+            }
+            'P' => {
+                let row = &mut self.grid[self.row];
+                for _ in 0..count.min(self.cols - self.col) {
+                    row.remove(self.col);
+                    row.push(' ');
+                }
+            }
+            'X' => {
+                let end = (self.col + count).min(self.cols);
+                self.grid[self.row][self.col..end].fill(' ');
+            }
+            'L' if (self.top..=self.bottom).contains(&self.row) => {
+                for _ in 0..count.min(self.bottom - self.row + 1) {
+                    self.grid.remove(self.bottom);
+                    self.grid.insert(self.row, vec![' '; self.cols]);
+                }
+                self.col = 0;
+            }
+            'M' if (self.top..=self.bottom).contains(&self.row) => {
+                for _ in 0..count.min(self.bottom - self.row + 1) {
+                    self.grid.remove(self.row);
+                    self.grid.insert(self.bottom, vec![' '; self.cols]);
+                }
+                self.col = 0;
+            }
+            'S' => (0..count.min(self.bottom - self.top + 1)).for_each(|_| self.scroll_up()),
+            'T' => (0..count.min(self.bottom - self.top + 1)).for_each(|_| self.scroll_down()),
+            'r' => {
+                let top = param(0).max(1) - 1;
+                let bottom = if param(1) == 0 { self.rows } else { param(1).min(self.rows) } - 1;
+                if top < bottom {
+                    self.top = top;
+                    self.bottom = bottom;
+                    self.row = 0;
+                    self.col = 0;
+                }
+            }
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            _ => {}
+        }
+    }
+
+    fn set_mode(&mut self, mode: usize, enabled: bool) {
+        match mode {
+            7 => self.autowrap = enabled,
+            47 | 1047 | 1049 => {
+                let save_cursor = mode == 1049;
+                if enabled && !self.is_alternate() {
+                    if save_cursor {
+                        self.save_cursor();
+                    }
+                    let primary = std::mem::replace(&mut self.grid, blank_grid(self.rows, self.cols));
+                    self.primary = Some(primary);
+                } else if !enabled {
+                    if let Some(primary) = self.primary.take() {
+                        self.grid = primary;
+                        if save_cursor {
+                            self.restore_cursor();
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            self.col = 0;
+            self.line_feed();
+        }
+        self.grid[self.row][self.col] = c;
+        if self.col + 1 < self.cols {
+            self.col += 1;
+        } else if self.autowrap {
+            self.wrap_pending = true;
+        }
+    }
+
+    fn line_feed(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.bottom {
+            self.scroll_up();
+        } else if self.row + 1 < self.rows {
+            self.row += 1;
+        }
+    }
+
+    fn reverse_index(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.top {
+            self.scroll_down();
+        } else {
+            self.row = self.row.saturating_sub(1);
+        }
+    }
+
+    /// Scroll the region up one line; the top line of the primary screen
+    /// goes to the scrollback.
+    fn scroll_up(&mut self) {
+        let line = self.grid.remove(self.top);
+        self.grid.insert(self.bottom, vec![' '; self.cols]);
+        if self.top == 0 && !self.is_alternate() && self.limit > 0 {
+            self.scrollback.push_back(render_row(&line));
+            while self.scrollback.len() > self.limit {
+                self.scrollback.pop_front();
+            }
+        }
+    }
+
+    fn scroll_down(&mut self) {
+        self.grid.remove(self.bottom);
+        self.grid.insert(self.top, vec![' '; self.cols]);
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        match mode {
+            0 => {
+                self.erase_line(0);
+                self.grid[self.row + 1..].iter_mut().for_each(|row| row.fill(' '));
+            }
+            1 => {
+                self.erase_line(1);
+                self.grid[..self.row].iter_mut().for_each(|row| row.fill(' '));
+            }
+            2 => self.grid.iter_mut().for_each(|row| row.fill(' ')),
+            3 => self.scrollback.clear(),
+            _ => {}
+        }
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        let row = &mut self.grid[self.row];
+        match mode {
+            0 => row[self.col..].fill(' '),
+            1 => row[..=self.col].fill(' '),
+            2 => row.fill(' '),
+            _ => {}
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved_cursor = (self.row, self.col);
+    }
+
+    fn restore_cursor(&mut self) {
+        self.wrap_pending = false;
+        (self.row, self.col) = self.saved_cursor;
+    }
+
+    /// Upmost row reachable by relative moves: the region top when inside it.
+    fn upper_limit(&self) -> usize {
+        if self.row >= self.top {
+            self.top
+        } else {
+            0
+        }
+    }
+
+    /// Lowest row reachable by relative moves: the region bottom when inside it.
+    fn lower_limit(&self) -> usize {
+        if self.row <= self.bottom {
+            self.bottom
+        } else {
+            self.rows - 1
+        }
+    }
+}
+
+impl OutputSink for VtScreen {
+    fn feed(&mut self, bytes: &[u8]) {
+        VtScreen::feed(self, bytes)
+    }
+
+    fn last_lines(&self, n: usize) -> String {
+        VtScreen::last_lines(self, n)
+    }
+}
+
+fn blank_grid(rows: usize, cols: usize) -> Vec<Vec<char>> {
+    vec![vec![' '; cols]; rows]
+}
+
+fn render_row(row: &[char]) -> String {
+    row.iter().collect::<String>().trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(rows: usize, cols: usize, output: &str) -> VtScreen {
+        let mut screen = VtScreen::new(rows, cols, 100);
+        screen.feed(output.as_bytes());
+        screen
+    }
+
+    #[test]
+    fn text_wraps_and_scrolls_into_the_scrollback() {
+        let vt = screen(3, 5, "one\r\ntwo\r\nthree\r\nfour+more");
+        assert_eq!(vt.rows(), vec!["three", "four+", "more"]);
+        assert_eq!(vt.last_lines(10), "one\ntwo\nthree\nfour+\nmore");
+        assert_eq!(vt.last_lines(2), "four+\nmore");
+
+        // Writing the last column does not wrap until the next character
+        let vt = screen(2, 3, "abc");
+        assert_eq!(vt.cursor(), (0, 2));
+        assert_eq!(vt.rows(), vec!["abc", ""]);
+    }
+
+    #[test]
+    fn redraws_overwrite_in_place() {
+        // A progress bar redrawn with \r and erase-line
+        let vt = screen(4, 20, "10% [#    ]\r\x1b[K100% [#####]\r\n$ ");
+        assert_eq!(vt.last_lines(10), "100% [#####]\n$");
+
+        // Cursor addressing, erase display and a color that is dropped
+        let vt = screen(3, 10, "garbage\x1b[2J\x1b[2;3H\x1b[1;31mhi\x1b[0m\x1b[1;1Htop");
+        assert_eq!(vt.rows(), vec!["top", "  hi", ""]);
+    }
+
+    #[test]
+    fn insert_delete_and_erase_characters() {
+        let vt = screen(1, 10, "abcdef\x1b[1;3H\x1b[2@");
+        assert_eq!(vt.rows(), vec!["ab  cdef"]);
+        let vt = screen(1, 10, "abcdef\x1b[1;2H\x1b[2P");
+        assert_eq!(vt.rows(), vec!["adef"]);
+        let vt = screen(1, 10, "abcdef\x1b[1;2H\x1b[3X");
+        assert_eq!(vt.rows(), vec!["a   ef"]);
+        let vt = screen(1, 10, "abcdef\x1b[1;4H\x1b[1K");
+        assert_eq!(vt.rows(), vec!["    ef"]);
+    }
+
+    #[test]
+    fn scroll_regions_keep_the_status_lines_fixed() {
+        // Like a pager: title on row 1, status on row 4, rows 2-3 scroll
+        let mut vt = screen(4, 10, "title\x1b[4;1Hstatus\x1b[2;3r\x1b[2;1Ha\r\nb\r\nc\r\nd");
+        assert_eq!(vt.rows(), vec!["title", "c", "d", "status"]);
+        // Lines scrolled inside a region do not reach the scrollback
+        assert_eq!(vt.last_lines(10), "title\nc\nd\nstatus");
+
+        // Reverse index at the region top scrolls down; insert/delete line
+        vt.feed(b"\x1b[2;1H\x1bMz");
+        assert_eq!(vt.rows(), vec!["title", "z", "c", "status"]);
+        vt.feed(b"\x1b[2;1H\x1b[M");
+        assert_eq!(vt.rows(), vec!["title", "c", "", "status"]);
+        vt.feed(b"\x1b[2;1H\x1b[L");
+        assert_eq!(vt.rows(), vec!["title", "", "c", "status"]);
+    }
+
+    #[test]
+    fn huge_scroll_counts_are_clamped_to_the_region() {
+        let mut vt = screen(3, 10, "one\r\ntwo\r\nthree");
+        vt.feed(b"\x1b[18446744073709551615T");
+        assert_eq!(vt.rows(), vec!["", "", ""]);
+
+        let mut vt = screen(3, 10, "one\r\ntwo\r\nthree");
+        vt.feed(b"\x1b[18446744073709551615S");
+        assert_eq!(vt.rows(), vec!["", "", ""]);
+        // Only the lines on screen reach the scrollback
+        assert!(vt.last_lines(10).starts_with("one\ntwo\nthree\n"));
+        assert_eq!(vt.last_lines(10).matches("one").count(), 1);
+    }
+
+    #[test]
+    fn alternate_screen_restores_the_primary_screen() {
+        let mut vt = screen(3, 20, "$ vim notes\r\n");
+        vt.feed(b"\x1b[?1049h\x1b[H\x1b[2Jline 1\r\n~\r\n\"notes\" 1L");
+        assert!(vt.is_alternate());
+        assert_eq!(vt.last_lines(10), "line 1\n~\n\"notes\" 1L");
+
+        vt.feed(b"\x1b[?1049l$ ");
+        assert!(!vt.is_alternate());
+        assert_eq!(vt.last_lines(10), "$ vim notes\n$");
+    }
+
+    #[test]
+    fn strings_and_split_sequences_are_parsed() {
+        let mut vt = VtScreen::new(2, 20, 10);
+        // Title (OSC), DCS and a CSI split across reads
+        vt.feed(b"\x1b]0;title\x07\x1bPq#0\x1b\\a\x1b[");
+        vt.feed(b"5Gb");
+        vt.feed(&"ç".as_bytes()[..1]);
+        vt.feed(&"ç".as_bytes()[1..]);
+        assert_eq!(vt.rows(), vec!["a   bç", ""]);
+
+        // Full reset
+        vt.feed(b"\x1bc");
+        assert_eq!(vt.last_lines(10), "");
+    }
+}
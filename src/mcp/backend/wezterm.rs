@@ -0,0 +1,320 @@
+//! WezTerm backend: drives a pane through `wezterm cli`.
+//!
+//! Text and control characters are typed with `send-text --no-paste`, the
+//! screen and scrollback are read with `get-text --start-line -N`, and panes
+//! are listed with `list --format json`. Like the tmux backend, every
+//! operation is a short-lived CLI invocation.
+//!
+//! Panes are selected by id (`session` of a [`TerminalTarget`]); without
+//! one, `wezterm cli` acts on the pane it runs in or the focused pane.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+
+use super::runner::{CommandRunner, SystemCommandRunner};
+use super::{foreground_process, last_captured_lines, BackendCapabilities, ControlKey, TerminalBackend, TerminalSession};
+use crate::mcp::types::{ProcessInfo, TerminalTarget};
+
+/// Which WezTerm pane the backend acts on.
+#[derive(Debug, Clone, Default)]
+pub struct WeztermConfig {
+    /// Pane id (`--pane-id`); `wezterm cli` picks `$WEZTERM_PANE` or the
+    /// focused pane when unset
+    pub pane_id: Option<String>,
+}
+
+/// A pane in `wezterm cli list --format json`.
+#[derive(Debug, Deserialize)]
+struct WeztermPane {
+    window_id: u64,
+    tab_id: u64,
+    pane_id: u64,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    tty_name: Option<String>,
+    #[serde(default)]
+    size: Option<WeztermSize>,
+}
+
+/// Pane size in `wezterm cli list --format json`.
+#[derive(Debug, Deserialize)]
+struct WeztermSize {
+    rows: u16,
+    cols: u16,
+}
+
+/// A client in `wezterm cli list-clients --format json`.
+#[derive(Debug, Deserialize)]
+struct WeztermClient {
+    #[serde(default)]
+    focused_pane_id: Option<u64>,
+}
+
+/// Backend acting on a WezTerm pane.
+pub struct WeztermBackend {
+    config: WeztermConfig,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Default for WeztermBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WeztermBackend {
+    /// Create a backend for the pane `wezterm cli` picks.
+    pub fn new() -> Self {
+        Self::with_config(WeztermConfig::default())
+    }
+
+    /// Create a backend for a specific pane.
+    pub fn with_config(config: WeztermConfig) -> Self {
+        Self::with_runner(config, Arc::new(SystemCommandRunner::new()))
+    }
+
+    /// Create a backend with an injected runner (useful for tests).
+    pub fn with_runner(config: WeztermConfig, runner: Arc<dyn CommandRunner>) -> Self {
+        WeztermBackend { config, runner }
+    }
+
+    /// Run `wezterm cli <args>` and return its stdout.
+    async fn cli(&self, args: &[&str], cancel: &CancellationToken) -> Result<String> {
+        let mut full = vec!["cli".to_string()];
+        full.extend(args.iter().map(|arg| arg.to_string()));
+        let output = self.runner.run("wezterm", &full, cancel).await?;
+        output.into_stdout(&format!("wezterm cli {}", args[0]))
+    }
+
+    /// The pane `target` selects, or the configured one.
+    fn pane_id(&self, target: &TerminalTarget) -> Result<Option<String>> {
+        if target.window.is_some() {
+            return Err(anyhow!("The wezterm backend selects panes by id; window selection is not supported"));
+        }
+        Ok(target.session.clone().or_else(|| self.config.pane_id.clone()))
+    }
+
+    /// Run a pane command (`<command> [--pane-id N] <args>`).
+    async fn cli_pane(
+        &self,
+        target: &TerminalTarget,
+        command: &str,
+        args: &[&str],
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        let pane = self.pane_id(target)?;
+        let mut full = vec![command];
+        if let Some(pane) = &pane {
+            full.extend(["--pane-id", pane.as_str()]);
+        }
+        full.extend_from_slice(args);
+        self.cli(&full, cancel).await
+    }
+
+    /// Type `text` without bracketed paste, so `\r` submits the line.
+    async fn send(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()> {
+        self.cli_pane(target, "send-text", &["--no-paste", "--", text], cancel).await?;
+        Ok(())
+    }
+
+    async fn panes(&self) -> Result<Vec<WeztermPane>> {
+        let output = self.cli(&["list", "--format", "json"], &CancellationToken::new()).await?;
+        serde_json::from_str(&output).context("Unexpected output from wezterm cli list")
+    }
+
+    /// Id of the pane the backend acts on by default.
+    async fn default_pane(&self) -> Result<Option<u64>> {
+        if let Some(pane) = &self.config.pane_id {
+            return Ok(pane.parse().ok());
+        }
+        let output = self
+            .cli(&["list-clients", "--format", "json"], &CancellationToken::new())
+            .await?;
+        let clients: Vec<WeztermClient> =
+            serde_json::from_str(&output).context("Unexpected output from wezterm cli list-clients")?;
+        Ok(clients.iter().find_map(|client| client.focused_pane_id))
+    }
+}
+
+#[async_trait]
+impl TerminalBackend for WeztermBackend {
+    fn name(&self) -> &'static str {
+        "wezterm"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            select_session: true,
+            ..BackendCapabilities::default()
+        }
+    }
+
+    async fn write_text(&self, target: &TerminalTarget, text: &str, cancel: &CancellationToken) -> Result<()> {
+        self.send(target, &format!("{}\r", text), cancel).await
+    }
+
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String> {
+        let start = format!("--start-line=-{}", lines);
+        let output = self
+            .cli_pane(target, "get-text", &[&start], &CancellationToken::new())
+            .await?;
+        Ok(last_captured_lines(&output, lines))
+    }
+
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()> {
+        self.send(target, &char::from(key.code).to_string(), &CancellationToken::new())
+            .await
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>> {
+        let active = self.default_pane().await?;
+        Ok(self
+            .panes()
+            .await?
+            .into_iter()
+            .map(|pane| TerminalSession {
+                id: pane.pane_id.to_string(),
+                name: format!("{}:{} {}", pane.window_id, pane.tab_id, pane.title)
+                    .trim_end()
+                    .to_string(),
+                tty: pane.tty_name.filter(|tty| !tty.is_empty()),
+                active: Some(pane.pane_id) == active,
+                window: Some(pane.window_id.to_string()),
+                tab: Some(pane.tab_id.to_string()),
+                columns: pane.size.as_ref().map(|size| size.cols),
+                rows: pane.size.as_ref().map(|size| size.rows),
+            })
+            .collect())
+    }
+
+    async fn query_process(&self, target: &TerminalTarget) -> Result<Option<ProcessInfo>> {
+        let pane = match self.pane_id(target)? {
+            Some(pane) => pane.parse().ok(),
+            None => self.default_pane().await?,
+        };
+        let tty = self
+            .panes()
+            .await?
+            .into_iter()
+            .find(|p| Some(p.pane_id) == pane)
+            .and_then(|p| p.tty_name)
+            .filter(|tty| !tty.is_empty());
+        match tty {
+            Some(tty) => tokio::task::spawn_blocking(move || foreground_process(&tty)).await?,
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::backend::runner::{CommandOutput, MockCommandRunner};
+
+    const LIST_OUTPUT: &str = r#"[
+        {"window_id": 0, "tab_id": 0, "pane_id": 0, "workspace": "default", "title": "zsh",
+         "cwd": "file:///home/dev", "is_active": true, "is_zoomed": false, "tty_name": "/dev/pts/4"},
+        {"window_id": 0, "tab_id": 1, "pane_id": 3, "workspace": "default", "title": "vim notes.txt",
+         "cwd": "file:///home/dev", "is_active": true, "is_zoomed": false, "tty_name": "/dev/pts/9",
+         "size": {"rows": 40, "cols": 120, "pixel_width": 1200, "pixel_height": 800, "dpi": 96}}
+    ]"#;
+
+    fn pane(id: &str) -> TerminalTarget {
+        TerminalTarget {
+            session: Some(id.to_string()),
+            window: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn commands_target_panes() {
+        let runner = Arc::new(MockCommandRunner::new(vec![
+            CommandOutput::ok(""),
+            CommandOutput::ok("$ ls\nCargo.toml\n$\n\n\n"),
+            CommandOutput::ok(""),
+        ]));
+        let config = WeztermConfig {
+            pane_id: Some("7".to_string()),
+        };
+        let backend = WeztermBackend::with_runner(config, runner.clone());
+        let cancel = CancellationToken::new();
+
+        backend.write_text(&TerminalTarget::default(), "-n ls", &cancel).await.unwrap();
+        let screen = backend.read_screen(&pane("3"), 2).await.unwrap();
+        assert_eq!(screen, "Cargo.toml\n$");
+        backend
+            .send_control(&TerminalTarget::default(), ControlKey::parse("c").unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            runner.calls(),
+            vec![
+                vec!["wezterm", "cli", "send-text", "--pane-id", "7", "--no-paste", "--", "-n ls\r"],
+                vec!["wezterm", "cli", "get-text", "--pane-id", "3", "--start-line=-2"],
+                vec!["wezterm", "cli", "send-text", "--pane-id", "7", "--no-paste", "--", "\u{3}"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn panes_are_listed_with_the_focused_one_active() {
+        let runner = Arc::new(MockCommandRunner::new(vec![
+            CommandOutput::ok(r#"[{"username": "dev", "pid": 42, "focused_pane_id": 3}]"#),
+            CommandOutput::ok(LIST_OUTPUT),
+        ]));
+        let backend = WeztermBackend::with_runner(WeztermConfig::default(), runner.clone());
+
+        let sessions = backend.list_sessions().await.unwrap();
+        assert_eq!(
+            sessions,
+            vec![
+                TerminalSession {
+                    id: "0".to_string(),
+                    name: "0:0 zsh".to_string(),
+                    tty: Some("/dev/pts/4".to_string()),
+                    active: false,
+                    window: Some("0".to_string()),
+                    tab: Some("0".to_string()),
+                    ..TerminalSession::default()
+                },
+                TerminalSession {
+                    id: "3".to_string(),
+                    name: "0:1 vim notes.txt".to_string(),
+                    tty: Some("/dev/pts/9".to_string()),
+                    active: true,
+                    window: Some("0".to_string()),
+                    tab: Some("1".to_string()),
+                    columns: Some(120),
+                    rows: Some(40),
+                },
+            ]
+        );
+        assert_eq!(runner.calls()[0], vec!["wezterm", "cli", "list-clients", "--format", "json"]);
+    }
+
+    #[tokio::test]
+    async fn errors_are_reported() {
+        let runner = Arc::new(MockCommandRunner::new(vec![CommandOutput {
+            success: false,
+            stdout: String::new(),
+            stderr: "pane 12 not found\n".to_string(),
+        }]));
+        let backend = WeztermBackend::with_runner(WeztermConfig::default(), runner.clone());
+
+        let error = backend.read_screen(&pane("12"), 5).await.unwrap_err();
+        assert_eq!(error.to_string(), "wezterm cli get-text failed: pane 12 not found");
+
+        let window = TerminalTarget {
+            session: None,
+            window: Some("1".to_string()),
+        };
+        assert!(backend.read_screen(&window, 5).await.is_err());
+        assert_eq!(runner.calls().len(), 1);
+    }
+}
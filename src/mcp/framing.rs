@@ -0,0 +1,455 @@
+//! Enquadramento (framing) das mensagens nos transportes de stream
+//!
+//! Dois formatos são suportados:
+//! - newline: um JSON por linha, terminado por `\n` (o formato original);
+//! - Content-Length: cabeçalhos no estilo LSP (`Content-Length: N\r\n\r\n`)
+//!   seguidos de exatamente `N` bytes, o que permite JSON formatado em várias linhas.
+//!
+//! No modo `Auto` o formato é detectado pelo primeiro byte da conexão e as
+//! respostas usam o mesmo formato. Frames maiores que o limite, cabeçalhos
+//! malformados e UTF-8 inválido viram `Frame::Invalid` em vez de derrubar a
+//! conexão; o leitor descarta os bytes do frame inválido e continua no próximo.
+
+use std::io;
+
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::{debug, warn};
+
+use crate::mcp::types::Framing;
+
+/// Tamanho máximo padrão de uma mensagem (4 MiB)
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Tamanho máximo do bloco de cabeçalhos no modo Content-Length
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// Tamanho das leituras do stream
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Um frame lido do stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// Mensagem completa em UTF-8
+    Message(String),
+    /// Frame descartado; o cliente deve receber um erro de parse
+    Invalid(FrameError),
+}
+
+/// Motivo pelo qual um frame foi descartado
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FrameError {
+    /// A mensagem excede o tamanho máximo permitido
+    #[error("Mensagem de {size} bytes excede o limite de {limit} bytes")]
+    Oversized { size: usize, limit: usize },
+
+    /// A mensagem não é UTF-8 válido
+    #[error("Mensagem não é UTF-8 válido")]
+    InvalidUtf8,
+
+    /// Cabeçalhos Content-Length ausentes ou inválidos
+    #[error("Cabeçalho de frame inválido: {0}")]
+    MalformedHeader(String),
+}
+
+/// Bytes que ainda precisam ser descartados após um frame inválido
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Discard {
+    /// Nada a descartar
+    Nothing,
+    /// Descarta até o próximo `\n` (modo newline)
+    UntilNewline,
+    /// Descarta uma quantidade fixa de bytes (corpo Content-Length)
+    Bytes(usize),
+}
+
+/// Leitor de frames sobre um stream assíncrono
+pub struct FrameReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    framing: Framing,
+    max_message_size: usize,
+    discard: Discard,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    /// Cria um leitor com o formato e o limite informados
+    pub fn new(reader: R, framing: Framing, max_message_size: usize) -> Self {
+        FrameReader {
+            reader,
+            buffer: Vec::with_capacity(READ_CHUNK_SIZE),
+            framing,
+            max_message_size,
+            discard: Discard::Nothing,
+        }
+    }
+
+    /// Formato em uso (permanece `Auto` até o primeiro byte ser recebido)
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Lê o próximo frame, retornando `None` no EOF
+    pub async fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+
+        loop {
+            if let Some(frame) = self.parse_frame() {
+                return Ok(Some(frame));
+            }
+
+            let n = self.reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(self.finish());
+            }
+
+            debug!("Lidos {} bytes, total de {} bytes no buffer", n, self.buffer.len() + n);
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// Trata os bytes restantes no EOF
+    fn finish(&mut self) -> Option<Frame> {
+        // Uma última linha sem `\n` ainda é uma mensagem no modo newline
+        if self.framing == Framing::Newline && self.discard == Discard::Nothing {
+            let rest = std::mem::take(&mut self.buffer);
+            let line = trim_line(&rest);
+            if !line.iter().all(u8::is_ascii_whitespace) {
+                return Some(self.decode(line.to_vec()));
+            }
+        }
+
+        if !self.buffer.is_empty() {
+            warn!("Descartando {} bytes incompletos no EOF", self.buffer.len());
+            self.buffer.clear();
+        }
+        None
+    }
+
+    /// Tenta extrair um frame completo do buffer
+    fn parse_frame(&mut self) -> Option<Frame> {
+        loop {
+            if !self.skip_discarded() {
+                return None;
+            }
+
+            if self.framing == Framing::Auto {
+                self.framing = detect_framing(&self.buffer)?;
+                debug!("Formato de frame detectado: {:?}", self.framing);
+            }
+
+            let frame = match self.framing {
+                Framing::ContentLength => self.parse_content_length(),
+                _ => self.parse_newline(),
+            };
+
+            match frame {
+                // Linha em branco entre mensagens; continua procurando
+                Some(None) => continue,
+                Some(Some(frame)) => return Some(frame),
+                None => return None,
+            }
+        }
+    }
+
+    /// Descarta bytes pendentes de um frame inválido; retorna `false` se o buffer acabou
+    fn skip_discarded(&mut self) -> bool {
+        match self.discard {
+            Discard::Nothing => true,
+            Discard::UntilNewline => match self.buffer.iter().position(|&b| b == b'\n') {
+                Some(pos) => {
+                    self.buffer.drain(..=pos);
+                    self.discard = Discard::Nothing;
+                    true
+                }
+                None => {
+                    self.buffer.clear();
+                    false
+                }
+            },
+            Discard::Bytes(remaining) => {
+                let n = remaining.min(self.buffer.len());
+                self.buffer.drain(..n);
+                if n == remaining {
+                    self.discard = Discard::Nothing;
+                    true
+                } else {
+                    self.discard = Discard::Bytes(remaining - n);
+                    false
+                }
+            }
+        }
+    }
+
+    /// Modo newline: `Some(None)` indica uma linha em branco
+    fn parse_newline(&mut self) -> Option<Option<Frame>> {
+        match self.buffer.iter().position(|&b| b == b'\n') {
+            Some(pos) => {
+                let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+                let line = trim_line(&line[..pos]);
+
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    return Some(None);
+                }
+
+                if line.len() > self.max_message_size {
+                    return Some(Some(self.oversized(line.len())));
+                }
+
+                Some(Some(self.decode(line.to_vec())))
+            }
+            None if self.buffer.len() > self.max_message_size => {
+                // Não há como saber o tamanho final; descarta até o fim da linha
+                let size = self.buffer.len();
+                self.buffer.clear();
+                self.discard = Discard::UntilNewline;
+                Some(Some(self.oversized(size)))
+            }
+            None => None,
+        }
+    }
+
+    /// Modo Content-Length: `Some(None)` indica bytes em branco entre mensagens
+    fn parse_content_length(&mut self) -> Option<Option<Frame>> {
+        // Tolera `\r\n` extras entre mensagens
+        let leading = self
+            .buffer
+            .iter()
+            .take_while(|b| b.is_ascii_whitespace())
+            .count();
+        if leading > 0 {
+            self.buffer.drain(..leading);
+            return Some(None);
+        }
+
+        let (header_len, body_start) = match find_header_end(&self.buffer) {
+            Some(found) => found,
+            None if self.buffer.len() > MAX_HEADER_SIZE => {
+                self.buffer.clear();
+                return Some(Some(Frame::Invalid(FrameError::MalformedHeader(
+                    "bloco de cabeçalhos muito grande".to_string(),
+                ))));
+            }
+            None => return None,
+        };
+
+        let length = match parse_content_length(&self.buffer[..header_len]) {
+            Ok(length) => length,
+            Err(error) => {
+                self.buffer.drain(..body_start);
+                return Some(Some(Frame::Invalid(error)));
+            }
+        };
+
+        if length > self.max_message_size {
+            self.buffer.drain(..body_start);
+            self.discard = Discard::Bytes(length);
+            return Some(Some(self.oversized(length)));
+        }
+
+        if self.buffer.len() < body_start + length {
+            return None;
+        }
+
+        let body: Vec<u8> = self.buffer.drain(..body_start + length).skip(body_start).collect();
+        Some(Some(self.decode(body)))
+    }
+
+    fn oversized(&self, size: usize) -> Frame {
+        warn!("Mensagem de {} bytes excede o limite de {} bytes", size, self.max_message_size);
+        Frame::Invalid(FrameError::Oversized {
+            size,
+            limit: self.max_message_size,
+        })
+    }
+
+    fn decode(&self, bytes: Vec<u8>) -> Frame {
+        match String::from_utf8(bytes) {
+            Ok(message) => Frame::Message(message),
+            Err(_) => {
+                warn!("Mensagem com UTF-8 inválido descartada");
+                Frame::Invalid(FrameError::InvalidUtf8)
+            }
+        }
+    }
+}
+
+/// Detecta o formato pelo primeiro byte relevante do stream
+fn detect_framing(buffer: &[u8]) -> Option<Framing> {
+    let first = buffer.iter().find(|b| !b.is_ascii_whitespace())?;
+
+    // Cabeçalhos começam com uma letra (`Content-Length`, `Content-Type`)
+    if first.is_ascii_alphabetic() {
+        Some(Framing::ContentLength)
+    } else {
+        Some(Framing::Newline)
+    }
+}
+
+/// Localiza o fim dos cabeçalhos, retornando (fim dos cabeçalhos, início do corpo)
+fn find_header_end(buffer: &[u8]) -> Option<(usize, usize)> {
+    let crlf = buffer.windows(4).position(|w| w == b"\r\n\r\n").map(|pos| (pos, pos + 4));
+    let lf = buffer.windows(2).position(|w| w == b"\n\n").map(|pos| (pos, pos + 2));
+
+    match (crlf, lf) {
+        (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+        (found, None) | (None, found) => found,
+    }
+}
+
+/// Extrai o valor de `Content-Length` do bloco de cabeçalhos
+fn parse_content_length(headers: &[u8]) -> Result<usize, FrameError> {
+    let headers = std::str::from_utf8(headers)
+        .map_err(|_| FrameError::MalformedHeader("cabeçalhos não são UTF-8".to_string()))?;
+
+    for line in headers.lines() {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| FrameError::MalformedHeader(format!("linha sem ':': {}", line.trim())))?;
+
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            return value.trim().parse().map_err(|_| {
+                FrameError::MalformedHeader(format!("Content-Length inválido: {}", value.trim()))
+            });
+        }
+    }
+
+    Err(FrameError::MalformedHeader("Content-Length ausente".to_string()))
+}
+
+/// Remove o `\r` final de uma linha
+fn trim_line(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Escreve uma mensagem usando o formato da conexão
+pub async fn write_frame<W>(writer: &mut W, message: &str, framing: Framing) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    match framing {
+        Framing::ContentLength => {
+            let header = format!("Content-Length: {}\r\n\r\n", message.len());
+            writer.write_all(header.as_bytes()).await?;
+            writer.write_all(message.as_bytes()).await?;
+        }
+        Framing::Newline | Framing::Auto => {
+            writer.write_all(message.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+    }
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_all(input: &[u8], framing: Framing, max: usize) -> (Vec<Frame>, Framing) {
+        let mut reader = FrameReader::new(input, framing, max);
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.next_frame().await.unwrap() {
+            frames.push(frame);
+        }
+        (frames, reader.framing())
+    }
+
+    fn message(text: &str) -> Frame {
+        Frame::Message(text.to_string())
+    }
+
+    #[tokio::test]
+    async fn newline_framing_is_detected() {
+        let input = b"{\"a\":1}\r\n\n{\"b\":2}\n{\"c\":3}";
+        let (frames, framing) = read_all(input, Framing::Auto, 1024).await;
+
+        assert_eq!(framing, Framing::Newline);
+        assert_eq!(frames, vec![message("{\"a\":1}"), message("{\"b\":2}"), message("{\"c\":3}")]);
+    }
+
+    #[tokio::test]
+    async fn content_length_framing_is_detected() {
+        let first = "{\n  \"jsonrpc\": \"2.0\",\n  \"id\": 1\n}";
+        let second = "{\"id\":2}";
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{}\r\ncontent-length: {}\n\n{}",
+            first.len(),
+            first,
+            second.len(),
+            second
+        );
+        let (frames, framing) = read_all(input.as_bytes(), Framing::Auto, 1024).await;
+
+        assert_eq!(framing, Framing::ContentLength);
+        assert_eq!(frames, vec![message(first), message(second)]);
+    }
+
+    #[tokio::test]
+    async fn oversized_newline_message_is_skipped() {
+        let big = format!("{{\"x\":\"{}\"}}", "a".repeat(100));
+        let input = format!("{}\n{{\"ok\":true}}\n", big);
+        let (frames, _) = read_all(input.as_bytes(), Framing::Newline, 32).await;
+
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(frames[0], Frame::Invalid(FrameError::Oversized { limit: 32, .. })));
+        assert_eq!(frames[1], message("{\"ok\":true}"));
+    }
+
+    #[tokio::test]
+    async fn oversized_newline_message_split_across_reads_is_skipped() {
+        // Maior que o chunk de leitura, sem `\n` até o final
+        let big = "a".repeat(READ_CHUNK_SIZE * 3);
+        let input = format!("{}\n{{\"ok\":true}}\n", big);
+        let (frames, _) = read_all(input.as_bytes(), Framing::Newline, 1024).await;
+
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(frames[0], Frame::Invalid(FrameError::Oversized { .. })));
+        assert_eq!(frames[1], message("{\"ok\":true}"));
+    }
+
+    #[tokio::test]
+    async fn oversized_content_length_body_is_skipped() {
+        let big = "b".repeat(100);
+        let small = "{\"ok\":true}";
+        let input = format!(
+            "Content-Length: {}\r\n\r\n{}Content-Length: {}\r\n\r\n{}",
+            big.len(),
+            big,
+            small.len(),
+            small
+        );
+        let (frames, _) = read_all(input.as_bytes(), Framing::ContentLength, 32).await;
+
+        assert_eq!(
+            frames,
+            vec![
+                Frame::Invalid(FrameError::Oversized { size: 100, limit: 32 }),
+                message(small)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_utf8_and_malformed_headers_are_reported() {
+        let mut input = b"{\"bad\":\"\xff\xfe\"}\n{\"ok\":1}\n".to_vec();
+        let (frames, _) = read_all(&input, Framing::Auto, 1024).await;
+        assert_eq!(frames, vec![Frame::Invalid(FrameError::InvalidUtf8), message("{\"ok\":1}")]);
+
+        input = b"Content-Type: application/json\r\n\r\nContent-Length: 2\r\n\r\n{}".to_vec();
+        let (frames, _) = read_all(&input, Framing::Auto, 1024).await;
+        assert!(matches!(frames[0], Frame::Invalid(FrameError::MalformedHeader(_))));
+        assert_eq!(frames[1], message("{}"));
+    }
+
+    #[tokio::test]
+    async fn write_frame_uses_connection_framing() {
+        let mut out = Vec::new();
+        write_frame(&mut out, "{\"id\":1}", Framing::ContentLength).await.unwrap();
+        write_frame(&mut out, "{\"id\":2}", Framing::Newline).await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Content-Length: 8\r\n\r\n{\"id\":1}{\"id\":2}\n"
+        );
+    }
+}
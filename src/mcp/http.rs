@@ -0,0 +1,489 @@
+//! Transporte MCP "Streamable HTTP"
+//!
+//! Um único endpoint (`/mcp`) atende:
+//! - `POST`: uma mensagem JSON-RPC do cliente; requisições recebem a resposta
+//!   em `application/json`, notificações recebem `202 Accepted`.
+//! - `GET`: abre um stream SSE por onde o servidor envia mensagens e
+//!   notificações para o cliente.
+//! - `DELETE`: encerra a sessão.
+//!
+//! A sessão é criada quando o `initialize` é bem-sucedido e devolvida no
+//! cabeçalho `Mcp-Session-Id`, que o cliente deve repetir em todas as
+//! requisições seguintes. Sessões ociosas expiram, o número de sessões abertas
+//! e o tamanho do corpo dos `POST` são limitados (veja [`HttpLimits`]).
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::rejection::StringRejection;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use futures::stream::{self, Stream};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::mcp::framing::DEFAULT_MAX_MESSAGE_SIZE;
+use crate::mcp::jsonrpc::{self, JsonRpcError, JsonRpcResponse, RequestId};
+use crate::mcp::router::Router;
+use crate::mcp::session::Session;
+
+/// Caminho do endpoint MCP
+pub const MCP_ENDPOINT: &str = "/mcp";
+
+/// Cabeçalho que associa requisições HTTP a uma sessão MCP
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Limites de sessões e mensagens do transporte HTTP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpLimits {
+    /// Tempo sem requisições após o qual uma sessão expira; sessões com
+    /// requisições em andamento ou stream SSE aberto não expiram
+    pub idle_timeout: Duration,
+    /// Número máximo de sessões abertas ao mesmo tempo
+    pub max_sessions: usize,
+    /// Tamanho máximo do corpo de um `POST`, em bytes
+    pub max_message_size: usize,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        HttpLimits {
+            idle_timeout: Duration::from_secs(30 * 60),
+            max_sessions: 64,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+/// Sessão MCP criada por um `initialize` via HTTP
+pub struct HttpSession {
+    /// Identificador enviado no cabeçalho `Mcp-Session-Id`
+    id: String,
+    /// Requisições em andamento e canal do stream SSE aberto pelo cliente
+    ///
+    /// Notificações emitidas durante uma requisição (ex.: progresso) seguem
+    /// pelo stream SSE, enquanto a resposta volta no corpo do `POST`.
+    requests: Session,
+    /// Momento da última requisição do cliente
+    last_seen: Mutex<Instant>,
+}
+
+impl HttpSession {
+    /// Identificador da sessão
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Número de requisições em andamento
+    pub fn in_flight(&self) -> usize {
+        self.requests.in_flight()
+    }
+
+    /// Registra atividade do cliente, adiando a expiração
+    fn touch(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    /// Se a sessão está ociosa há mais de `timeout`
+    fn is_idle(&self, timeout: Duration) -> bool {
+        let streaming = self.requests.outgoing().is_some_and(|sender| !sender.is_closed());
+        !streaming && self.in_flight() == 0 && self.last_seen.lock().unwrap().elapsed() > timeout
+    }
+
+    /// Envia uma mensagem ao cliente pelo stream SSE
+    ///
+    /// Retorna `false` se o cliente não tem um stream aberto.
+    pub fn send(&self, message: String) -> bool {
+        self.requests.send(message)
+    }
+
+    /// Abre um novo stream SSE, substituindo o anterior
+    fn open_stream(&self) -> mpsc::UnboundedReceiver<String> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.requests.set_outgoing(Some(sender));
+        receiver
+    }
+
+    /// Fecha o stream SSE, se houver
+    fn close_stream(&self) {
+        self.requests.set_outgoing(None);
+    }
+}
+
+/// Estado compartilhado do transporte HTTP
+pub struct HttpTransport {
+    /// Router que processa as mensagens
+    router: Arc<Router>,
+    /// Expiração e número máximo de sessões
+    limits: HttpLimits,
+    /// Sessões ativas indexadas pelo ID
+    sessions: Mutex<HashMap<String, Arc<HttpSession>>>,
+    /// Contador de sessões ativas
+    active_connections: Arc<AtomicUsize>,
+    /// Contador total de sessões
+    total_connections: Arc<AtomicUsize>,
+    /// Contador de mensagens processadas
+    total_messages: Arc<AtomicUsize>,
+    /// Contador de erros
+    total_errors: Arc<AtomicUsize>,
+}
+
+impl HttpTransport {
+    /// Cria o transporte HTTP para o router informado
+    pub fn new(router: Arc<Router>) -> Arc<Self> {
+        Self::with_limits(router, HttpLimits::default())
+    }
+
+    /// Cria o transporte HTTP com limites de sessões próprios
+    pub fn with_limits(router: Arc<Router>, limits: HttpLimits) -> Arc<Self> {
+        Self::with_counters(
+            router,
+            limits,
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(AtomicUsize::new(0)),
+        )
+    }
+
+    /// Cria o transporte HTTP atualizando os contadores do servidor
+    pub(crate) fn with_counters(
+        router: Arc<Router>,
+        limits: HttpLimits,
+        active_connections: Arc<AtomicUsize>,
+        total_connections: Arc<AtomicUsize>,
+        total_messages: Arc<AtomicUsize>,
+        total_errors: Arc<AtomicUsize>,
+    ) -> Arc<Self> {
+        Arc::new(HttpTransport {
+            router,
+            limits,
+            sessions: Mutex::new(HashMap::new()),
+            active_connections,
+            total_connections,
+            total_messages,
+            total_errors,
+        })
+    }
+
+    /// Cria o `axum::Router` com o endpoint MCP
+    pub fn app(self: &Arc<Self>) -> axum::Router {
+        axum::Router::new()
+            .route(
+                MCP_ENDPOINT,
+                get(handle_get).post(handle_post).delete(handle_delete),
+            )
+            .layer(DefaultBodyLimit::max(self.limits.max_message_size))
+            .with_state(self.clone())
+    }
+
+    /// Obtém uma sessão pelo ID
+    pub fn session(&self, id: &str) -> Option<Arc<HttpSession>> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    /// Número de sessões ativas
+    pub fn session_count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Envia uma mensagem para todas as sessões com stream SSE aberto
+    ///
+    /// Retorna quantas sessões receberam a mensagem.
+    pub fn notify_all(&self, message: &str) -> usize {
+        let sessions: Vec<Arc<HttpSession>> =
+            self.sessions.lock().unwrap().values().cloned().collect();
+        sessions
+            .iter()
+            .filter(|session| session.send(message.to_string()))
+            .count()
+    }
+
+    /// Fecha todos os streams SSE para permitir o shutdown ordenado
+    pub fn close_streams(&self) {
+        for session in self.sessions.lock().unwrap().values() {
+            session.close_stream();
+        }
+    }
+
+    /// Cria uma sessão ainda não registrada, para processar o `initialize`
+    fn new_session(&self) -> Arc<HttpSession> {
+        Arc::new(HttpSession {
+            id: uuid::Uuid::new_v4().to_string(),
+            requests: Session::new(),
+            last_seen: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Registra uma sessão inicializada, recusando além do limite de sessões
+    fn register_session(&self, session: &Arc<HttpSession>) -> Result<(), HttpError> {
+        self.expire_idle_sessions();
+
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() >= self.limits.max_sessions {
+            warn!("Limite de {} sessões HTTP atingido", self.limits.max_sessions);
+            return Err(HttpError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Limite de sessões atingido; encerre uma sessão com DELETE",
+            ));
+        }
+        sessions.insert(session.id.clone(), session.clone());
+        drop(sessions);
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+
+        info!("Nova sessão HTTP: {}", session.id);
+        Ok(())
+    }
+
+    /// Remove as sessões ociosas há mais que o tempo limite
+    fn expire_idle_sessions(&self) {
+        let idle: Vec<String> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|session| session.is_idle(self.limits.idle_timeout))
+            .map(|session| session.id.clone())
+            .collect();
+        for id in idle {
+            info!("Sessão HTTP expirada por inatividade: {}", id);
+            self.remove_session(&id);
+        }
+    }
+
+    /// Remove uma sessão, retornando se ela existia
+    fn remove_session(&self, id: &str) -> bool {
+        let removed = self.sessions.lock().unwrap().remove(id);
+        match removed {
+            Some(session) => {
+                session.close_stream();
+                self.active_connections.fetch_sub(1, Ordering::Relaxed);
+                info!("Sessão HTTP encerrada: {}", id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Resolve a sessão indicada no cabeçalho `Mcp-Session-Id`
+    fn session_from_headers(&self, headers: &HeaderMap) -> Result<Arc<HttpSession>, HttpError> {
+        let id = headers
+            .get(SESSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| HttpError::new(StatusCode::BAD_REQUEST, "Cabeçalho Mcp-Session-Id ausente"))?;
+
+        self.expire_idle_sessions();
+        let session = self
+            .session(id)
+            .ok_or_else(|| HttpError::new(StatusCode::NOT_FOUND, "Sessão desconhecida ou encerrada"))?;
+        session.touch();
+        Ok(session)
+    }
+}
+
+/// Erro HTTP com um erro JSON-RPC no corpo
+struct HttpError {
+    status: StatusCode,
+    error: JsonRpcError,
+}
+
+impl HttpError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        HttpError {
+            status,
+            error: JsonRpcError::new(jsonrpc::INVALID_REQUEST, message),
+        }
+    }
+}
+
+impl IntoResponse for HttpError {
+    fn into_response(self) -> Response {
+        let body =
+            serde_json::to_string(&JsonRpcResponse::failure(RequestId::Null, self.error))
+                .unwrap_or_default();
+        (self.status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+    }
+}
+
+/// Recusa requisições de páginas web de outras origens (proteção contra DNS rebinding)
+fn check_origin(headers: &HeaderMap) -> Result<(), HttpError> {
+    let origin = match headers.get(header::ORIGIN).and_then(|value| value.to_str().ok()) {
+        Some(origin) => origin,
+        None => return Ok(()),
+    };
+
+    let host = origin
+        .split("://")
+        .nth(1)
+        .unwrap_or(origin)
+        .split('/')
+        .next()
+        .unwrap_or("");
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or(""),
+        None => host.split(':').next().unwrap_or(""),
+    };
+
+    if matches!(host, "localhost" | "127.0.0.1" | "::1") {
+        Ok(())
+    } else {
+        warn!("Requisição HTTP recusada da origem {}", origin);
+        Err(HttpError::new(
+            StatusCode::FORBIDDEN,
+            format!("Origem não permitida: {}", origin),
+        ))
+    }
+}
+
+/// `POST /mcp`: processa uma mensagem JSON-RPC do cliente
+async fn handle_post(
+    State(transport): State<Arc<HttpTransport>>,
+    headers: HeaderMap,
+    body: Result<String, StringRejection>,
+) -> Response {
+    if let Err(error) = check_origin(&headers) {
+        return error.into_response();
+    }
+
+    transport.total_messages.fetch_add(1, Ordering::Relaxed);
+
+    // Corpos acima de `max_message_size` são recusados antes de serem lidos por inteiro
+    let body = match body {
+        Ok(body) => body,
+        Err(rejection) => {
+            transport.total_errors.fetch_add(1, Ordering::Relaxed);
+            warn!("Corpo do POST recusado: {}", rejection.body_text());
+            return HttpError::new(rejection.status(), rejection.body_text()).into_response();
+        }
+    };
+
+    let is_initialize = serde_json::from_str::<serde_json::Value>(&body)
+        .map(|value| value.get("method").and_then(|method| method.as_str()) == Some("initialize"))
+        .unwrap_or(false);
+
+    // O initialize cria a sessão; as demais mensagens precisam de uma sessão válida
+    let session = if !is_initialize {
+        transport.session_from_headers(&headers)
+    } else if headers.contains_key(SESSION_HEADER) {
+        Err(HttpError::new(
+            StatusCode::BAD_REQUEST,
+            "initialize não pode ser enviado em uma sessão existente",
+        ))
+    } else {
+        Ok(transport.new_session())
+    };
+    let session = match session {
+        Ok(session) => session,
+        Err(error) => {
+            transport.total_errors.fetch_add(1, Ordering::Relaxed);
+            return error.into_response();
+        }
+    };
+
+    debug!("POST na sessão {}: {}", session.id, body);
+
+    let reply = transport
+        .router
+        .process_session_message(&body, &session.requests)
+        .await;
+
+    // A sessão só passa a existir depois de um initialize bem-sucedido
+    if is_initialize {
+        let succeeded = reply
+            .as_deref()
+            .and_then(|reply| serde_json::from_str::<serde_json::Value>(reply).ok())
+            .is_some_and(|reply| reply.get("result").is_some());
+        if !succeeded {
+            transport.total_errors.fetch_add(1, Ordering::Relaxed);
+            return match reply {
+                Some(reply) => (
+                    StatusCode::BAD_REQUEST,
+                    [(header::CONTENT_TYPE, "application/json")],
+                    reply,
+                )
+                    .into_response(),
+                None => StatusCode::BAD_REQUEST.into_response(),
+            };
+        }
+        if let Err(error) = transport.register_session(&session) {
+            transport.total_errors.fetch_add(1, Ordering::Relaxed);
+            return error.into_response();
+        }
+    }
+
+    let mut response = match reply {
+        Some(reply) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/json")],
+            reply,
+        )
+            .into_response(),
+        // Notificações, respostas do cliente e requisições canceladas não têm corpo de retorno
+        None => StatusCode::ACCEPTED.into_response(),
+    };
+    // Requisições longas não contam como ociosidade
+    session.touch();
+
+    if let Ok(value) = HeaderValue::from_str(&session.id) {
+        response.headers_mut().insert(SESSION_HEADER, value);
+    }
+
+    response
+}
+
+/// `GET /mcp`: abre o stream SSE de mensagens do servidor para o cliente
+async fn handle_get(
+    State(transport): State<Arc<HttpTransport>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(error) = check_origin(&headers) {
+        return error.into_response();
+    }
+
+    let session = match transport.session_from_headers(&headers) {
+        Ok(session) => session,
+        Err(error) => return error.into_response(),
+    };
+
+    debug!("Stream SSE aberto na sessão {}", session.id);
+    Sse::new(event_stream(session.open_stream()))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// `DELETE /mcp`: encerra a sessão
+async fn handle_delete(
+    State(transport): State<Arc<HttpTransport>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(error) = check_origin(&headers) {
+        return error.into_response();
+    }
+
+    let session = match transport.session_from_headers(&headers) {
+        Ok(session) => session,
+        Err(error) => return error.into_response(),
+    };
+
+    transport.remove_session(&session.id);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Converte o canal da sessão em eventos SSE `message`
+fn event_stream(
+    receiver: mpsc::UnboundedReceiver<String>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream::unfold(receiver, |mut receiver| async move {
+        receiver
+            .recv()
+            .await
+            .map(|message| (Ok(Event::default().event("message").data(message)), receiver))
+    })
+}
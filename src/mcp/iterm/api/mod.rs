@@ -0,0 +1,379 @@
+/*!
+Client for iTerm2's websocket API (the protocol behind its Python API).
+
+iTerm2 serves the API on a Unix socket (`~/Library/Application
+Support/iTerm2/private/socket`) or, in older releases, on `localhost:1912`.
+Each websocket frame carries one protobuf message ([`proto`]): requests carry
+an id echoed in the response, and notifications for subscriptions (screen
+updates, ...) arrive unsolicited.
+
+The connection layer only depends on an [`ApiEndpoint`], so tests can point
+the client at a stand-in websocket server on Linux.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use futures::{SinkExt, StreamExt};
+use prost::Message as _;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, warn};
+
+use crate::mcp::iterm::OsascriptRunner;
+
+pub mod proto;
+
+use proto::{
+    ClientOriginatedMessage, Notification, NotificationRequest, NotificationStatus, NotificationType, Request, Response,
+    ServerOriginatedMessage,
+};
+
+/// Websocket subprotocol spoken by iTerm2
+pub const API_SUBPROTOCOL: &str = "api.iterm2.com";
+
+/// TCP address of the API in iTerm2 releases without the Unix socket
+pub const LEGACY_TCP_ADDRESS: &str = "localhost:1912";
+
+/// Default timeout for a single request
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the API is served.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiEndpoint {
+    /// `host:port`
+    Tcp(String),
+    /// Path of a Unix socket
+    Unix(PathBuf),
+}
+
+impl ApiEndpoint {
+    /// The Unix socket of the current user's iTerm2 when it exists, the legacy TCP port otherwise.
+    pub fn user_default() -> Self {
+        let socket = std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join("Library/Application Support/iTerm2/private/socket"));
+        match socket {
+            Some(socket) if socket.exists() => ApiEndpoint::Unix(socket),
+            _ => ApiEndpoint::Tcp(LEGACY_TCP_ADDRESS.to_string()),
+        }
+    }
+
+    /// Parse `host:port` or a socket path (anything containing `/`).
+    pub fn parse(value: &str) -> Self {
+        if value.contains('/') {
+            ApiEndpoint::Unix(PathBuf::from(value))
+        } else {
+            ApiEndpoint::Tcp(value.to_string())
+        }
+    }
+}
+
+impl Default for ApiEndpoint {
+    fn default() -> Self {
+        Self::user_default()
+    }
+}
+
+/// Cookie and key authorizing a client that iTerm2 did not launch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApiCredentials {
+    pub cookie: Option<String>,
+    pub key: Option<String>,
+}
+
+impl ApiCredentials {
+    /// Credentials from `ITERM2_COOKIE`/`ITERM2_KEY`, set by iTerm2 for scripts it launches.
+    pub fn from_env() -> Self {
+        ApiCredentials {
+            cookie: std::env::var("ITERM2_COOKIE").ok(),
+            key: std::env::var("ITERM2_KEY").ok(),
+        }
+    }
+
+    /// Ask iTerm2 for a cookie through AppleScript (the user may be prompted).
+    pub fn request_via_applescript(runner: &dyn OsascriptRunner, app_name: &str, timeout_secs: u64) -> Result<Self> {
+        let script = format!(
+            r#"tell application "iTerm2" to request cookie and key for app named {}"#,
+            crate::mcp::iterm::escape_applescript(app_name)
+        );
+        let output = runner.run(&[script.as_str()], timeout_secs)?;
+        let mut parts = output.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some(cookie), Some(key)) => Ok(ApiCredentials {
+                cookie: Some(cookie.to_string()),
+                key: Some(key.to_string()),
+            }),
+            _ => Err(anyhow!("iTerm2 did not return a cookie and key: {:?}", output.trim())),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.cookie.is_none() && self.key.is_none()
+    }
+}
+
+/// How to reach and authenticate with the API.
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    pub endpoint: ApiEndpoint,
+    /// Defaults to [`ApiCredentials::from_env`]; requested through AppleScript
+    /// on macOS when empty
+    pub credentials: ApiCredentials,
+    /// Name shown by iTerm2 when it asks the user to allow the connection
+    pub advisory_name: String,
+    pub request_timeout: Duration,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            endpoint: ApiEndpoint::default(),
+            credentials: ApiCredentials::from_env(),
+            advisory_name: "rs_iterm".to_string(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+}
+
+type Pending = Arc<Mutex<HashMap<i64, oneshot::Sender<Response>>>>;
+
+/// An open API connection.
+///
+/// Requests may be issued concurrently; a reader task routes each response
+/// to its caller by id and broadcasts notifications.
+pub struct ApiClient {
+    outgoing: mpsc::UnboundedSender<Vec<u8>>,
+    pending: Pending,
+    notifications: broadcast::Sender<Notification>,
+    closed: Arc<AtomicBool>,
+    next_id: AtomicI64,
+    timeout: Duration,
+    /// Sessions whose screen updates this connection is subscribed to
+    screen_updates: Mutex<HashSet<String>>,
+}
+
+impl ApiClient {
+    /// Connect and perform the websocket handshake.
+    pub async fn connect(config: &ApiConfig) -> Result<Self> {
+        match &config.endpoint {
+            ApiEndpoint::Tcp(address) => {
+                let stream = TcpStream::connect(address)
+                    .await
+                    .with_context(|| format!("Failed to connect to the iTerm2 API at {}", address))?;
+                Self::handshake(config, &format!("ws://{}/", address), stream).await
+            }
+            ApiEndpoint::Unix(path) => {
+                let stream = UnixStream::connect(path)
+                    .await
+                    .with_context(|| format!("Failed to connect to the iTerm2 API at {}", path.display()))?;
+                Self::handshake(config, "ws://localhost/", stream).await
+            }
+        }
+    }
+
+    async fn handshake<S>(config: &ApiConfig, url: &str, stream: S) -> Result<Self>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let mut request = url.into_client_request()?;
+        let headers = request.headers_mut();
+        headers.insert("Sec-WebSocket-Protocol", HeaderValue::from_static(API_SUBPROTOCOL));
+        headers.insert("Origin", HeaderValue::from_static("ws://localhost/"));
+        headers.insert(
+            "x-iterm2-library-version",
+            HeaderValue::from_str(&format!("rs_iterm {}", env!("CARGO_PKG_VERSION")))?,
+        );
+        headers.insert("x-iterm2-advisory-name", HeaderValue::from_str(&config.advisory_name)?);
+        if let Some(cookie) = &config.credentials.cookie {
+            headers.insert("x-iterm2-cookie", HeaderValue::from_str(cookie)?);
+        }
+        if let Some(key) = &config.credentials.key {
+            headers.insert("x-iterm2-key", HeaderValue::from_str(key)?);
+        }
+
+        let (socket, _) = tokio_tungstenite::client_async(request, stream)
+            .await
+            .context("iTerm2 API handshake failed")?;
+        let (mut sink, mut stream) = socket.split();
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+        let pending: Pending = Arc::default();
+        let (notifications, _) = broadcast::channel(64);
+        let closed = Arc::new(AtomicBool::new(false));
+
+        tokio::spawn(async move {
+            while let Some(bytes) = outgoing_rx.recv().await {
+                if let Err(e) = sink.send(Message::Binary(bytes.into())).await {
+                    warn!("Failed to send to the iTerm2 API: {}", e);
+                    break;
+                }
+            }
+        });
+
+        let reader_pending = pending.clone();
+        let reader_notifications = notifications.clone();
+        let reader_closed = closed.clone();
+        tokio::spawn(async move {
+            while let Some(frame) = stream.next().await {
+                let bytes = match frame {
+                    Ok(Message::Binary(bytes)) => bytes,
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        debug!("iTerm2 API connection closed: {}", e);
+                        break;
+                    }
+                };
+                let message = match ServerOriginatedMessage::decode(&bytes[..]) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        warn!("Undecodable message from the iTerm2 API: {}", e);
+                        continue;
+                    }
+                };
+                match (message.id, message.submessage) {
+                    (_, Some(Response::Event(notification))) => {
+                        let _ = reader_notifications.send(notification);
+                    }
+                    (Some(id), Some(response)) => {
+                        if let Some(waiter) = reader_pending.lock().unwrap().remove(&id) {
+                            let _ = waiter.send(response);
+                        }
+                    }
+                    _ => debug!("Ignoring iTerm2 API message without a known payload"),
+                }
+            }
+            reader_closed.store(true, Ordering::SeqCst);
+            // Dropping the waiters fails their requests
+            reader_pending.lock().unwrap().clear();
+        });
+
+        Ok(ApiClient {
+            outgoing,
+            pending,
+            notifications,
+            closed,
+            next_id: AtomicI64::new(1),
+            timeout: config.request_timeout,
+            screen_updates: Mutex::default(),
+        })
+    }
+
+    /// Whether the connection has been closed by either side.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Send a request and wait for its response.
+    ///
+    /// An `error` reply from iTerm2 is returned as an `Err`.
+    pub async fn request(&self, request: Request) -> Result<Response> {
+        if self.is_closed() {
+            return Err(anyhow!("The iTerm2 API connection is closed"));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (waiter, response) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, waiter);
+
+        let message = ClientOriginatedMessage {
+            id: Some(id),
+            submessage: Some(request),
+        };
+        if self.outgoing.send(message.encode_to_vec()).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(anyhow!("The iTerm2 API connection is closed"));
+        }
+
+        let response = match tokio::time::timeout(self.timeout, response).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(anyhow!("The iTerm2 API connection closed before responding")),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                return Err(anyhow!("The iTerm2 API did not respond within {:?}", self.timeout));
+            }
+        };
+        match response {
+            Response::Error(error) => Err(anyhow!("iTerm2 API error: {}", error)),
+            response => Ok(response),
+        }
+    }
+
+    /// Receive the notifications of every subscription made on this connection.
+    pub fn notifications(&self) -> broadcast::Receiver<Notification> {
+        self.notifications.subscribe()
+    }
+
+    /// Subscribe to the screen updates of `session`, once per connection.
+    pub async fn subscribe_screen_updates(&self, session: &str) -> Result<()> {
+        if self.screen_updates.lock().unwrap().contains(session) {
+            return Ok(());
+        }
+        let request = Request::Notification(NotificationRequest {
+            session: Some(session.to_string()),
+            subscribe: Some(true),
+            notification_type: Some(NotificationType::NotifyOnScreenUpdate as i32),
+        });
+        match self.request(request).await? {
+            Response::Notification(response) => match response.status() {
+                NotificationStatus::Ok | NotificationStatus::AlreadySubscribed => {
+                    self.screen_updates.lock().unwrap().insert(session.to_string());
+                    Ok(())
+                }
+                status => Err(anyhow!("Failed to subscribe to screen updates of {}: {:?}", session, status)),
+            },
+            response => Err(unexpected_response("notification", &response)),
+        }
+    }
+}
+
+/// Error for a response of the wrong kind.
+pub fn unexpected_response(expected: &str, response: &Response) -> anyhow::Error {
+    anyhow!("Expected a {} response from the iTerm2 API, got {:?}", expected, response)
+}
+
+/// Connect with `config`, asking iTerm2 for credentials through AppleScript
+/// when none are configured on macOS.
+pub async fn connect_with_default_credentials(config: &ApiConfig, runner: Arc<dyn OsascriptRunner>) -> Result<ApiClient> {
+    let mut config = config.clone();
+    if config.credentials.is_empty() && cfg!(target_os = "macos") {
+        let name = config.advisory_name.clone();
+        let timeout = config.request_timeout.as_secs().max(1);
+        config.credentials =
+            tokio::task::spawn_blocking(move || ApiCredentials::request_via_applescript(runner.as_ref(), &name, timeout))
+                .await??;
+    }
+    ApiClient::connect(&config).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::iterm::MockOsascriptRunner;
+
+    #[test]
+    fn endpoints_are_parsed() {
+        assert_eq!(ApiEndpoint::parse("localhost:1912"), ApiEndpoint::Tcp("localhost:1912".to_string()));
+        assert_eq!(
+            ApiEndpoint::parse("/tmp/iterm2.sock"),
+            ApiEndpoint::Unix(PathBuf::from("/tmp/iterm2.sock"))
+        );
+    }
+
+    #[test]
+    fn credentials_are_requested_via_applescript() {
+        let runner = MockOsascriptRunner::new(vec!["c00kie k3y\n".to_string(), "oops".to_string()]);
+        let credentials = ApiCredentials::request_via_applescript(&runner, "rs_iterm", 1).unwrap();
+        assert_eq!(credentials.cookie.as_deref(), Some("c00kie"));
+        assert_eq!(credentials.key.as_deref(), Some("k3y"));
+
+        assert!(ApiCredentials::request_via_applescript(&runner, "rs_iterm", 1).is_err());
+    }
+}
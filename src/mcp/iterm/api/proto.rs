@@ -0,0 +1,399 @@
+//! Subset of iTerm2's `api.proto` used by the websocket API client.
+//!
+//! The messages are written by hand with `prost` derives instead of being
+//! generated, so no `protoc` is needed at build time. Field numbers follow
+//! iTerm2's `api.proto` (proto2); fields the client does not use are left out
+//! and skipped when decoding. Nested messages are flattened into
+//! `Parent`-prefixed names.
+
+/// Envelope of every message sent to iTerm2.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ClientOriginatedMessage {
+    /// Echoed back in the response
+    #[prost(int64, optional, tag = "1")]
+    pub id: Option<i64>,
+    #[prost(oneof = "Request", tags = "100, 103, 106, 107, 115, 117")]
+    pub submessage: Option<Request>,
+}
+
+/// Requests understood by the client.
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum Request {
+    #[prost(message, tag = "100")]
+    GetBuffer(GetBufferRequest),
+    #[prost(message, tag = "103")]
+    Notification(NotificationRequest),
+    #[prost(message, tag = "106")]
+    ListSessions(ListSessionsRequest),
+    #[prost(message, tag = "107")]
+    SendText(SendTextRequest),
+    #[prost(message, tag = "115")]
+    Variable(VariableRequest),
+    #[prost(message, tag = "117")]
+    Focus(FocusRequest),
+}
+
+/// Envelope of every message received from iTerm2.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ServerOriginatedMessage {
+    /// Id of the request being answered; unset for notifications
+    #[prost(int64, optional, tag = "1")]
+    pub id: Option<i64>,
+    #[prost(oneof = "Response", tags = "2, 100, 103, 106, 107, 115, 117, 1000")]
+    pub submessage: Option<Response>,
+}
+
+/// Responses and notifications understood by the client.
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum Response {
+    /// The request could not be parsed or dispatched
+    #[prost(string, tag = "2")]
+    Error(String),
+    #[prost(message, tag = "100")]
+    GetBuffer(GetBufferResponse),
+    #[prost(message, tag = "103")]
+    Notification(NotificationResponse),
+    #[prost(message, tag = "106")]
+    ListSessions(ListSessionsResponse),
+    #[prost(message, tag = "107")]
+    SendText(SendTextResponse),
+    #[prost(message, tag = "115")]
+    Variable(VariableResponse),
+    #[prost(message, tag = "117")]
+    Focus(FocusResponse),
+    /// Unsolicited notification for a subscription
+    #[prost(message, tag = "1000")]
+    Event(Notification),
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetBufferRequest {
+    #[prost(string, optional, tag = "1")]
+    pub session: Option<String>,
+    #[prost(message, optional, tag = "2")]
+    pub line_range: Option<LineRange>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LineRange {
+    #[prost(bool, optional, tag = "1")]
+    pub screen_contents_only: Option<bool>,
+    #[prost(int32, optional, tag = "2")]
+    pub trailing_lines: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetBufferResponse {
+    #[prost(enumeration = "GetBufferStatus", optional, tag = "1")]
+    pub status: Option<i32>,
+    #[prost(message, repeated, tag = "3")]
+    pub contents: Vec<LineContents>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum GetBufferStatus {
+    Ok = 0,
+    SessionNotFound = 1,
+    InvalidLineRange = 2,
+    RequestMalformed = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LineContents {
+    #[prost(string, optional, tag = "1")]
+    pub text: Option<String>,
+    #[prost(enumeration = "Continuation", optional, tag = "3")]
+    pub continuation: Option<i32>,
+}
+
+/// How a line of the buffer ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum Continuation {
+    /// Unused default
+    Unspecified = 0,
+    /// The line ends with a newline
+    HardEol = 1,
+    /// The line was wrapped at the right margin
+    SoftEol = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SendTextRequest {
+    #[prost(string, optional, tag = "1")]
+    pub session: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub text: Option<String>,
+    #[prost(bool, optional, tag = "3")]
+    pub suppress_broadcast: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SendTextResponse {
+    #[prost(enumeration = "SendTextStatus", optional, tag = "1")]
+    pub status: Option<i32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum SendTextStatus {
+    Ok = 0,
+    SessionNotFound = 1,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListSessionsRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListSessionsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub windows: Vec<ListSessionsWindow>,
+    /// Sessions hidden with "Bury Session"
+    #[prost(message, repeated, tag = "2")]
+    pub buried_sessions: Vec<SessionSummary>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListSessionsWindow {
+    #[prost(message, repeated, tag = "1")]
+    pub tabs: Vec<ListSessionsTab>,
+    #[prost(string, optional, tag = "2")]
+    pub window_id: Option<String>,
+    #[prost(int32, optional, tag = "4")]
+    pub number: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListSessionsTab {
+    #[prost(string, optional, tag = "2")]
+    pub tab_id: Option<String>,
+    #[prost(message, optional, tag = "3")]
+    pub root: Option<SplitTreeNode>,
+}
+
+/// Split panes of a tab.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SplitTreeNode {
+    #[prost(bool, optional, tag = "1")]
+    pub vertical: Option<bool>,
+    #[prost(message, repeated, tag = "2")]
+    pub links: Vec<SplitTreeLink>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SplitTreeLink {
+    #[prost(oneof = "SplitTreeChild", tags = "1, 2")]
+    pub child: Option<SplitTreeChild>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub enum SplitTreeChild {
+    #[prost(message, tag = "1")]
+    Session(SessionSummary),
+    #[prost(message, tag = "2")]
+    Node(Box<SplitTreeNode>),
+}
+
+impl SplitTreeNode {
+    /// Every session of the tree, depth first.
+    pub fn sessions(&self) -> Vec<&SessionSummary> {
+        let mut sessions = Vec::new();
+        for link in &self.links {
+            match &link.child {
+                Some(SplitTreeChild::Session(session)) => sessions.push(session),
+                Some(SplitTreeChild::Node(node)) => sessions.extend(node.sessions()),
+                None => {}
+            }
+        }
+        sessions
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SessionSummary {
+    #[prost(string, optional, tag = "1")]
+    pub unique_identifier: Option<String>,
+    /// Size of the session in character cells
+    #[prost(message, optional, tag = "3")]
+    pub grid_size: Option<Size>,
+    #[prost(string, optional, tag = "4")]
+    pub title: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Size {
+    #[prost(int32, optional, tag = "1")]
+    pub width: Option<i32>,
+    #[prost(int32, optional, tag = "2")]
+    pub height: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NotificationRequest {
+    #[prost(string, optional, tag = "1")]
+    pub session: Option<String>,
+    #[prost(bool, optional, tag = "2")]
+    pub subscribe: Option<bool>,
+    #[prost(enumeration = "NotificationType", optional, tag = "3")]
+    pub notification_type: Option<i32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum NotificationType {
+    /// Unused default
+    Unspecified = 0,
+    NotifyOnKeystroke = 1,
+    NotifyOnScreenUpdate = 2,
+    NotifyOnPrompt = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NotificationResponse {
+    #[prost(enumeration = "NotificationStatus", optional, tag = "1")]
+    pub status: Option<i32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum NotificationStatus {
+    Ok = 0,
+    SessionNotFound = 1,
+    RequestMalformed = 2,
+    NotSubscribed = 3,
+    AlreadySubscribed = 4,
+}
+
+/// Unsolicited notification; one field is set per notification.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Notification {
+    #[prost(message, optional, tag = "2")]
+    pub screen_update_notification: Option<ScreenUpdateNotification>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ScreenUpdateNotification {
+    #[prost(string, optional, tag = "1")]
+    pub session: Option<String>,
+}
+
+/// Reads session variables such as `tty`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VariableRequest {
+    #[prost(string, optional, tag = "1")]
+    pub session_id: Option<String>,
+    #[prost(string, repeated, tag = "3")]
+    pub get: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct VariableResponse {
+    #[prost(enumeration = "VariableStatus", optional, tag = "1")]
+    pub status: Option<i32>,
+    /// JSON-encoded values, in the order of `get`
+    #[prost(string, repeated, tag = "2")]
+    pub values: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum VariableStatus {
+    Ok = 0,
+    SessionNotFound = 1,
+    InvalidName = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FocusRequest {}
+
+/// Current focus, as a list of focus-change events.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FocusResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub notifications: Vec<FocusChangedNotification>,
+}
+
+/// One focus fact; exactly one field is set.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FocusChangedNotification {
+    #[prost(bool, optional, tag = "1")]
+    pub application_active: Option<bool>,
+    #[prost(message, optional, tag = "2")]
+    pub window: Option<FocusWindow>,
+    /// A tab selected in its window
+    #[prost(string, optional, tag = "3")]
+    pub selected_tab: Option<String>,
+    /// A session active in its tab
+    #[prost(string, optional, tag = "4")]
+    pub session: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FocusWindow {
+    #[prost(enumeration = "WindowStatus", optional, tag = "1")]
+    pub window_status: Option<i32>,
+    #[prost(string, optional, tag = "2")]
+    pub window_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum WindowStatus {
+    BecameKey = 0,
+    IsCurrent = 1,
+    ResignedKey = 2,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn send_text_uses_the_api_field_numbers() {
+        let message = ClientOriginatedMessage {
+            id: Some(7),
+            submessage: Some(Request::SendText(SendTextRequest {
+                session: Some("S".to_string()),
+                text: Some("ls".to_string()),
+                suppress_broadcast: None,
+            })),
+        };
+        // id (field 1, varint), send_text_request (field 107, length-delimited)
+        let expected = [0x08, 0x07, 0xda, 0x06, 0x07, 0x0a, 0x01, b'S', 0x12, 0x02, b'l', b's'];
+        assert_eq!(message.encode_to_vec(), expected);
+        assert_eq!(ClientOriginatedMessage::decode(&expected[..]).unwrap(), message);
+    }
+
+    #[test]
+    fn nested_split_panes_are_flattened() {
+        let session = |id: &str| SplitTreeLink {
+            child: Some(SplitTreeChild::Session(SessionSummary {
+                unique_identifier: Some(id.to_string()),
+                grid_size: None,
+                title: None,
+            })),
+        };
+        let root = SplitTreeNode {
+            vertical: Some(true),
+            links: vec![
+                session("A"),
+                SplitTreeLink {
+                    child: Some(SplitTreeChild::Node(Box::new(SplitTreeNode {
+                        vertical: Some(false),
+                        links: vec![session("B"), session("C")],
+                    }))),
+                },
+            ],
+        };
+
+        let decoded = SplitTreeNode::decode(&root.encode_to_vec()[..]).unwrap();
+        let ids: Vec<&str> = decoded
+            .sessions()
+            .iter()
+            .filter_map(|s| s.unique_identifier.as_deref())
+            .collect();
+        assert_eq!(ids, vec!["A", "B", "C"]);
+    }
+}
@@ -3,11 +3,16 @@
 //! This module provides:
 //! - `escape(input: &str) -> String` to safely escape single-line and multi-line
 //!   strings for embedding into AppleScript `-e` expressions.
+//! - `in_session` and `with_session` to address the current iTerm2 session or one
+//!   selected by id.
 //! - `osascript_with_timeout` to run `/usr/bin/osascript -e <expr>` with a timeout,
 //!   collecting stdout and normalizing line endings to `\n`.
+//! - `osascript_with_cancellation`, the same but also killing the child as soon as a
+//!   `CancellationToken` fires (used when the MCP client cancels a request).
 //! - `OsascriptRunner` trait and two implementations:
 //!     - `SystemOsascriptRunner` -> calls the real `osascript` binary.
-//!     - `MockOsascriptRunner` -> programmable in-memory runner for unit tests / CI.
+//!     - `MockOsascriptRunner` -> programmable in-memory runner for unit tests / CI,
+//!       capturing the scripts it receives.
 //!
 //! The design favors testability: production code can depend on the trait and get a
 //! `SystemOsascriptRunner`, while unit tests may provide `MockOsascriptRunner` to avoid
@@ -15,10 +20,11 @@
 
 use anyhow::{anyhow, Context, Result};
 use std::collections::VecDeque;
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 
 /// Reuse existing crate utility for per-line escaping of backslashes and double-quotes.
 /// This preserves consistent escaping behavior across the crate.
@@ -27,12 +33,12 @@ use crate::mcp::utilities::escape_applescript_string as util_escape;
 /// Escape a string for safe embedding in AppleScript `osascript -e` expressions.
 ///
 /// - Single-line input: returns a quoted string with backslashes and double-quotes escaped:
-///     e.g. input -> Hello "world" \ path
-///          returns -> "\"Hello \\\"world\\\" \\\\ path\""
+///   e.g. input -> Hello "world" \ path
+///   returns -> "\"Hello \\\"world\\\" \\\\ path\""
 ///
 /// - Multi-line input: returns a parenthesized AppleScript concatenation expression
 ///   that composes lines using `return`:
-///     ( "line1" & return & "line2" & return & "line3" )
+///   ( "line1" & return & "line2" & return & "line3" )
 ///
 /// The returned string is ready to be embedded into an AppleScript expression,
 /// for example: `return <escaped_expr>` or `tell application "iTerm2" to write text <escaped_expr>`.
@@ -63,6 +69,63 @@ pub fn escape(input: &str) -> String {
     }
 }
 
+/// Build a script running `command` (e.g. `write text "ls"`) in an iTerm2 session.
+///
+/// - `None`: the current session of the current window.
+/// - `Some(id)`: the session with that unique id, wherever it is; the script
+///   fails with an error naming the id if no session has it.
+pub fn in_session(session: Option<&str>, command: &str) -> String {
+    match session {
+        None => format!(
+            "tell application \"iTerm2\" to tell current session of current window to {}",
+            command
+        ),
+        Some(_) => with_session(session, &format!("tell s to {}", command)),
+    }
+}
+
+/// Build a script running the statements in `body` inside `tell application
+/// "iTerm2"`, with `w`, `t` and `s` bound to a window, tab and session.
+///
+/// Without `session` they are the current ones; with a unique id, the session
+/// having it (the script fails if none does). `body` may `return` a value.
+pub fn with_session(session: Option<&str>, body: &str) -> String {
+    let indent = |body: &str, depth: usize| {
+        body.lines()
+            .map(|line| format!("{}{}", " ".repeat(depth * 4), line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    match session {
+        None => format!(
+            r#"tell application "iTerm2"
+    set w to current window
+    set t to current tab of w
+    set s to current session of w
+{body}
+end tell"#,
+            body = indent(body, 1)
+        ),
+        Some(id) => format!(
+            r#"tell application "iTerm2"
+    repeat with w in windows
+        repeat with t in tabs of w
+            repeat with s in sessions of t
+                if (id of s) is {id} then
+{body}
+                    return
+                end if
+            end repeat
+        end repeat
+    end repeat
+    error "No iTerm2 session with id " & {id}
+end tell"#,
+            id = escape(id),
+            body = indent(body, 5)
+        ),
+    }
+}
+
 /// Run `/usr/bin/osascript` with the given `-e` expressions and a timeout (seconds).
 ///
 /// - `e_lines`: each item becomes a `-e` argument for osascript (they should be full AppleScript expressions,
@@ -71,16 +134,39 @@ pub fn escape(input: &str) -> String {
 ///
 /// Returns the stdout (UTF-8) with normalized line endings (LF) on success, or an error on failure/timeout.
 pub fn osascript_with_timeout(e_lines: &[&str], timeout_secs: u64) -> Result<String> {
+    osascript_with_cancellation(e_lines, timeout_secs, &CancellationToken::new())
+}
+
+/// Like `osascript_with_timeout`, but also kills the child when `cancel` fires.
+///
+/// Returns an error mentioning the cancellation so callers can tell it apart from a timeout.
+pub fn osascript_with_cancellation(
+    e_lines: &[&str],
+    timeout_secs: u64,
+    cancel: &CancellationToken,
+) -> Result<String> {
     let mut cmd = Command::new("/usr/bin/osascript");
     for line in e_lines {
         cmd.arg("-e").arg(line);
     }
     cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
-    let mut child = cmd
+    let child = cmd
         .spawn()
         .with_context(|| format!("failed to spawn /usr/bin/osascript with args {:?}", e_lines))?;
 
+    wait_for_output(child, "osascript", timeout_secs, cancel)
+}
+
+/// Poll a spawned child until it exits, the timeout expires or `cancel` fires.
+///
+/// On timeout or cancellation the child is killed and reaped before returning.
+fn wait_for_output(
+    mut child: Child,
+    name: &str,
+    timeout_secs: u64,
+    cancel: &CancellationToken,
+) -> Result<String> {
     let start = Instant::now();
     let timeout = Duration::from_secs(timeout_secs);
 
@@ -90,18 +176,25 @@ pub fn osascript_with_timeout(e_lines: &[&str], timeout_secs: u64) -> Result<Str
                 // Process finished; collect output
                 let output = child
                     .wait_with_output()
-                    .context("failed to collect osascript output")?;
+                    .with_context(|| format!("failed to collect {} output", name))?;
                 // Normalize line endings: convert CRLF and CR -> LF for predictable comparisons.
                 let mut out_str = String::from_utf8_lossy(&output.stdout).to_string();
                 out_str = out_str.replace("\r\n", "\n").replace('\r', "\n");
                 return Ok(out_str);
             }
             Ok(None) => {
+                if cancel.is_cancelled() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(anyhow!("{} cancelled by the client", name));
+                }
                 if start.elapsed() >= timeout {
                     // Timeout exceeded
                     let _ = child.kill();
+                    let _ = child.wait();
                     return Err(anyhow!(
-                        "osascript timed out after {} seconds",
+                        "{} timed out after {} seconds",
+                        name,
                         timeout_secs
                     ));
                 }
@@ -109,7 +202,7 @@ pub fn osascript_with_timeout(e_lines: &[&str], timeout_secs: u64) -> Result<Str
                 sleep(Duration::from_millis(50));
             }
             Err(e) => {
-                return Err(anyhow!("error while waiting for osascript process: {}", e));
+                return Err(anyhow!("error while waiting for {} process: {}", name, e));
             }
         }
     }
@@ -121,6 +214,22 @@ pub fn osascript_with_timeout(e_lines: &[&str], timeout_secs: u64) -> Result<Str
 pub trait OsascriptRunner: Send + Sync {
     /// Run the given apple-script `e_lines` with a timeout and return stdout.
     fn run(&self, e_lines: &[&str], timeout_secs: u64) -> Result<String>;
+
+    /// Run the script, aborting early when `cancel` fires.
+    ///
+    /// The default implementation ignores the token; runners that spawn a
+    /// process should override it to kill the child on cancellation.
+    fn run_cancellable(
+        &self,
+        e_lines: &[&str],
+        timeout_secs: u64,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        if cancel.is_cancelled() {
+            return Err(anyhow!("osascript cancelled by the client"));
+        }
+        self.run(e_lines, timeout_secs)
+    }
 }
 
 /// System runner that executes the real `/usr/bin/osascript`.
@@ -137,6 +246,15 @@ impl OsascriptRunner for SystemOsascriptRunner {
     fn run(&self, e_lines: &[&str], timeout_secs: u64) -> Result<String> {
         osascript_with_timeout(e_lines, timeout_secs)
     }
+
+    fn run_cancellable(
+        &self,
+        e_lines: &[&str],
+        timeout_secs: u64,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        osascript_with_cancellation(e_lines, timeout_secs, cancel)
+    }
 }
 
 /// A simple programmable in-memory mock `OsascriptRunner`.
@@ -144,10 +262,12 @@ impl OsascriptRunner for SystemOsascriptRunner {
 /// Behavior:
 /// - Provide a queue of responses (Vec<String>) that get returned in order for each `run` call.
 /// - If the queue is empty, `run` returns an error.
+/// - Every script received is captured, so tests can assert the generated AppleScript.
 /// - Useful for unit tests and CI where calling the real `osascript` is undesirable.
 #[derive(Debug, Clone)]
 pub struct MockOsascriptRunner {
     inner: Arc<Mutex<VecDeque<String>>>,
+    calls: Arc<Mutex<Vec<Vec<String>>>>,
 }
 
 impl MockOsascriptRunner {
@@ -155,14 +275,13 @@ impl MockOsascriptRunner {
     pub fn new(responses: Vec<String>) -> Self {
         Self {
             inner: Arc::new(Mutex::new(responses.into_iter().collect())),
+            calls: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
     /// Create an empty mock runner.
     pub fn empty() -> Self {
-        Self {
-            inner: Arc::new(Mutex::new(VecDeque::new())),
-        }
+        Self::new(Vec::new())
     }
 
     /// Push an additional response to the back of the queue.
@@ -170,16 +289,25 @@ impl MockOsascriptRunner {
         let mut q = self.inner.lock().unwrap();
         q.push_back(resp);
     }
+
+    /// The `-e` lines of every script run so far, in order.
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
 }
 
 impl OsascriptRunner for MockOsascriptRunner {
-    fn run(&self, _e_lines: &[&str], _timeout_secs: u64) -> Result<String> {
+    fn run(&self, e_lines: &[&str], _timeout_secs: u64) -> Result<String> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(e_lines.iter().map(|line| line.to_string()).collect());
         let mut q = self.inner.lock().unwrap();
         match q.pop_front() {
             Some(resp) => Ok(resp),
             None => Err(anyhow!(
                 "MockOsascriptRunner: no more responses available (called with {:?})",
-                _e_lines
+                e_lines
             )),
         }
     }
@@ -249,6 +377,23 @@ mod tests {
         assert!(out.contains("\t"));
     }
 
+    #[test]
+    fn scripts_address_the_current_or_a_selected_session() {
+        assert_eq!(
+            in_session(None, "write text \"ls\""),
+            "tell application \"iTerm2\" to tell current session of current window to write text \"ls\""
+        );
+        let script = in_session(Some("w0t0p1:AB\"CD"), "return contents");
+        assert!(script.contains("if (id of s) is \"w0t0p1:AB\\\"CD\" then"), "got: {}", script);
+        assert!(script.contains("tell s to return contents"), "got: {}", script);
+
+        let script = with_session(None, "set x to 1\nreturn x");
+        assert_eq!(
+            script,
+            "tell application \"iTerm2\"\n    set w to current window\n    set t to current tab of w\n    set s to current session of w\n    set x to 1\n    return x\nend tell"
+        );
+    }
+
     #[test]
     fn mock_runner_consumes_responses_in_order() {
         let responses = vec!["a".to_string(), "b".to_string()];
@@ -260,6 +405,54 @@ mod tests {
         let err = mock.run(&["ignore"], 1).unwrap_err();
         let msg = format!("{}", err);
         assert!(msg.contains("no more responses"));
+        assert_eq!(mock.calls().len(), 3);
+        assert_eq!(mock.calls()[0], vec!["ignore".to_string()]);
+    }
+
+    #[test]
+    fn wait_for_output_kills_child_on_cancel() {
+        let child = Command::new("sleep")
+            .arg("5")
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("sleep should be available");
+        let pid = child.id();
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        std::thread::spawn(move || {
+            sleep(Duration::from_millis(100));
+            trigger.cancel();
+        });
+
+        let start = Instant::now();
+        let err = wait_for_output(child, "sleep", 10, &cancel).unwrap_err();
+        assert!(err.to_string().contains("cancelled"), "got: {}", err);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // The child was killed and reaped, so the pid no longer exists
+        assert_ne!(unsafe { libc::kill(pid as libc::pid_t, 0) }, 0);
+    }
+
+    #[test]
+    fn wait_for_output_times_out() {
+        let child = Command::new("sleep")
+            .arg("5")
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("sleep should be available");
+
+        let err = wait_for_output(child, "sleep", 0, &CancellationToken::new()).unwrap_err();
+        assert!(err.to_string().contains("timed out"), "got: {}", err);
+    }
+
+    #[test]
+    fn default_run_cancellable_refuses_cancelled_token() {
+        let mock = MockOsascriptRunner::new(vec!["a".to_string()]);
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(mock.run_cancellable(&["ignore"], 1, &cancel).is_err());
+        assert_eq!(mock.run_cancellable(&["ignore"], 1, &CancellationToken::new()).unwrap(), "a");
     }
 
     // System runner smoke tests that actually call osascript — only run on macOS.
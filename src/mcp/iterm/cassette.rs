@@ -0,0 +1,264 @@
+//! Recorded `osascript` sessions ("cassettes") for offline tests.
+//!
+//! [`RecordingOsascriptRunner`] wraps a real runner and appends every script
+//! it runs, with its timeout and result, to a JSON cassette on disk.
+//! [`ReplayOsascriptRunner`] plays a cassette back: each call must send the
+//! exact script recorded at that position, and gets the recorded output or
+//! error in return. A session captured on macOS can then run anywhere,
+//! while still checking the AppleScript the code generates.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use super::applescript::OsascriptRunner;
+
+/// One script run and its result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The `-e` lines passed to the runner
+    pub script: Vec<String>,
+    /// Timeout requested by the caller, in seconds
+    pub timeout_secs: u64,
+    /// Output of a successful run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Error message of a failed run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Interaction {
+    fn new(e_lines: &[&str], timeout_secs: u64, result: &Result<String>) -> Self {
+        Interaction {
+            script: e_lines.iter().map(|line| line.to_string()).collect(),
+            timeout_secs,
+            output: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        }
+    }
+
+    fn result(&self) -> Result<String> {
+        match &self.error {
+            Some(error) => Err(anyhow!("{}", error)),
+            None => Ok(self.output.clone().unwrap_or_default()),
+        }
+    }
+}
+
+/// The on-disk format: the interactions in the order they happened.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Read a cassette written by [`RecordingOsascriptRunner`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = fs::read_to_string(path)
+            .with_context(|| format!("failed to read cassette {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("invalid cassette {}", path.display()))
+    }
+
+    /// Write the cassette as pretty-printed JSON.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n").with_context(|| format!("failed to write cassette {}", path.display()))
+    }
+}
+
+/// Runner that records every script run by an inner runner.
+///
+/// The cassette is rewritten after each call, so it is complete even if the
+/// process is killed. Failing to write it is logged, never reported to the
+/// caller.
+pub struct RecordingOsascriptRunner {
+    inner: Arc<dyn OsascriptRunner>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingOsascriptRunner {
+    /// Record the scripts run by `inner` into the cassette at `path`.
+    pub fn new(inner: Arc<dyn OsascriptRunner>, path: impl Into<PathBuf>) -> Self {
+        RecordingOsascriptRunner {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// The interactions recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+
+    fn record(&self, e_lines: &[&str], timeout_secs: u64, result: &Result<String>) {
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(Interaction::new(e_lines, timeout_secs, result));
+        if let Err(e) = cassette.save(&self.path) {
+            warn!("Failed to record osascript cassette: {:#}", e);
+        }
+    }
+}
+
+impl OsascriptRunner for RecordingOsascriptRunner {
+    fn run(&self, e_lines: &[&str], timeout_secs: u64) -> Result<String> {
+        let result = self.inner.run(e_lines, timeout_secs);
+        self.record(e_lines, timeout_secs, &result);
+        result
+    }
+
+    fn run_cancellable(
+        &self,
+        e_lines: &[&str],
+        timeout_secs: u64,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        let result = self.inner.run_cancellable(e_lines, timeout_secs, cancel);
+        self.record(e_lines, timeout_secs, &result);
+        result
+    }
+}
+
+/// Runner that plays a cassette back, asserting the scripts match.
+///
+/// Panics when a script differs from the recorded one or the cassette runs
+/// out, like a failed assertion in the test using it.
+#[derive(Debug)]
+pub struct ReplayOsascriptRunner {
+    interactions: Mutex<VecDeque<Interaction>>,
+    played: AtomicUsize,
+}
+
+impl ReplayOsascriptRunner {
+    /// Replay the given cassette.
+    pub fn new(cassette: Cassette) -> Self {
+        ReplayOsascriptRunner {
+            interactions: Mutex::new(cassette.interactions.into()),
+            played: AtomicUsize::new(0),
+        }
+    }
+
+    /// Replay the cassette stored at `path`.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    /// Number of recorded interactions not played yet.
+    pub fn remaining(&self) -> usize {
+        self.interactions.lock().unwrap().len()
+    }
+
+    /// Panic unless every recorded interaction was played.
+    pub fn assert_finished(&self) {
+        let remaining = self.interactions.lock().unwrap();
+        assert!(
+            remaining.is_empty(),
+            "cassette has {} unplayed interaction(s), next: {:?}",
+            remaining.len(),
+            remaining.front().map(|interaction| &interaction.script)
+        );
+    }
+}
+
+impl OsascriptRunner for ReplayOsascriptRunner {
+    fn run(&self, e_lines: &[&str], _timeout_secs: u64) -> Result<String> {
+        let next = self.interactions.lock().unwrap().pop_front();
+        let index = self.played.fetch_add(1, Ordering::SeqCst);
+
+        let Some(interaction) = next else {
+            panic!("cassette exhausted after {} interaction(s); unexpected script {:?}", index, e_lines);
+        };
+        assert_eq!(
+            interaction.script, e_lines,
+            "script {} differs from the recorded one",
+            index
+        );
+        interaction.result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::backend::iterm::ITermBackend;
+    use crate::mcp::backend::TerminalBackend;
+    use crate::mcp::iterm::MockOsascriptRunner;
+    use crate::mcp::types::TerminalTarget;
+
+    #[test]
+    fn interactions_are_written_to_disk_and_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        let mock = MockOsascriptRunner::new(vec!["hello\n".to_string()]);
+        let recorder = RecordingOsascriptRunner::new(Arc::new(mock), &path);
+
+        assert_eq!(recorder.run(&["return \"hello\""], 5).unwrap(), "hello\n");
+        // The mock is exhausted, so this run fails and the error is recorded
+        assert!(recorder.run(&["delay 1", "return 1"], 2).is_err());
+
+        let cassette = Cassette::load(&path).unwrap();
+        assert_eq!(cassette, recorder.cassette());
+        assert_eq!(cassette.interactions.len(), 2);
+        assert_eq!(cassette.interactions[0].timeout_secs, 5);
+        assert_eq!(cassette.interactions[1].script, vec!["delay 1", "return 1"]);
+        assert!(cassette.interactions[1].output.is_none());
+
+        let replay = ReplayOsascriptRunner::from_file(&path).unwrap();
+        assert_eq!(replay.run(&["return \"hello\""], 5).unwrap(), "hello\n");
+        assert_eq!(replay.remaining(), 1);
+        let error = replay.run(&["delay 1", "return 1"], 2).unwrap_err();
+        assert!(error.to_string().contains("no more responses"), "got: {}", error);
+        replay.assert_finished();
+    }
+
+    #[test]
+    #[should_panic(expected = "script 0 differs from the recorded one")]
+    fn replay_rejects_a_different_script() {
+        let replay = ReplayOsascriptRunner::new(Cassette {
+            interactions: vec![Interaction {
+                script: vec!["return 1".to_string()],
+                timeout_secs: 5,
+                output: Some("1\n".to_string()),
+                error: None,
+            }],
+        });
+        let _ = replay.run(&["return 2"], 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "cassette exhausted")]
+    fn replay_rejects_extra_scripts() {
+        let replay = ReplayOsascriptRunner::new(Cassette::default());
+        let _ = replay.run(&["return 1"], 5);
+    }
+
+    #[tokio::test]
+    async fn a_recorded_backend_session_replays_offline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("iterm.json");
+        let mock = MockOsascriptRunner::new(vec![
+            String::new(),
+            "A1B2\tzsh\t/dev/ttys001\ttrue\n".to_string(),
+        ]);
+        let recorder = Arc::new(RecordingOsascriptRunner::new(Arc::new(mock), &path));
+        let backend = ITermBackend::with_runner(recorder, 1);
+        let active = TerminalTarget::default();
+        backend.write_text(&active, "ls -la", &CancellationToken::new()).await.unwrap();
+        let recorded = backend.list_sessions().await.unwrap();
+
+        let replay = Arc::new(ReplayOsascriptRunner::from_file(&path).unwrap());
+        let backend = ITermBackend::with_runner(replay.clone(), 1);
+        backend.write_text(&active, "ls -la", &CancellationToken::new()).await.unwrap();
+        assert_eq!(backend.list_sessions().await.unwrap(), recorded);
+        replay.assert_finished();
+    }
+}
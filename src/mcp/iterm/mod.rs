@@ -6,21 +6,29 @@ This refactor moves AppleScript-specific logic into a separate `applescript` mod
 (`src/mcp/iterm/applescript.rs`) and updates `CommandExecutor` to accept an
 injectable `OsascriptRunner` implementation. In production the executor uses the
 `SystemOsascriptRunner`. In tests you can construct a `CommandExecutor` with a
-`MockOsascriptRunner` to avoid calling the system `osascript` binary.
+`MockOsascriptRunner` to avoid calling the system `osascript` binary. The iTerm2 backend
+passes a `PersistentOsascriptRunner` (`worker` module), which keeps one `osascript`
+process alive instead of spawning one per script. `RecordingOsascriptRunner` and
+`ReplayOsascriptRunner` (`cassette` module) capture those scripts on macOS and replay them
+in tests elsewhere. Terminal output is read by `ScreenReader` (`screen_reader` module), which
+asks iTerm2 for the session contents instead of reading the TTY device.
 */
 
 use anyhow::Result;
 use std::sync::Arc;
 use tracing::{debug, info};
 
+pub mod api;
 pub mod applescript;
+pub mod cassette;
+pub mod screen_reader;
+pub mod worker;
 pub mod control_char {
-    use super::*;
     use anyhow::{Context, Result};
     use std::fs::OpenOptions;
     use std::io::Write;
     use std::path::Path;
-    use tracing::{debug, error, info, warn};
+    use tracing::{debug, error, info};
 
     /// Control character sender for sending control characters to the terminal.
     ///
@@ -46,6 +54,15 @@ pub mod control_char {
             }
         }
 
+        /// Create a sender writing to the given TTY device.
+        pub fn new_for_tty(tty_path: impl Into<String>) -> Self {
+            let tty_path = tty_path.into();
+            debug!("ControlCharacterSender::new_for_tty({})", tty_path);
+            ControlCharacterSender {
+                tty_path: Some(tty_path),
+            }
+        }
+
         /// Initialize the sender by finding the active TTY.
         pub async fn initialize(&mut self) -> Result<()> {
             info!("Initializing ControlCharacterSender");
@@ -81,6 +98,11 @@ pub mod control_char {
 
             debug!("Mapped '{}' to control code: {}", letter, ctrl_code);
 
+            self.send_bytes(&[ctrl_code]).await
+        }
+
+        /// Write raw bytes to the TTY, e.g. an escape sequence for iTerm2 to interpret.
+        pub async fn send_bytes(&mut self, bytes: &[u8]) -> Result<()> {
             // Ensure we have a TTY path
             if self.tty_path.is_none() {
                 debug!("No TTY path set, initializing");
@@ -97,23 +119,23 @@ pub mod control_char {
                 return Err(anyhow::anyhow!("TTY path does not exist: {}", tty_path));
             }
             
-            // Write the control character to the TTY
-            self.write_to_tty(tty_path, ctrl_code)?;
+            // Write the bytes to the TTY
+            self.write_to_tty(tty_path, bytes)?;
             
             Ok(())
         }
         
-        /// Write a control character to the TTY file.
-        fn write_to_tty(&self, tty_path: &str, ctrl_code: u8) -> Result<()> {
+        /// Write bytes to the TTY file.
+        fn write_to_tty(&self, tty_path: &str, bytes: &[u8]) -> Result<()> {
             // Open the TTY device for writing
             let mut file = OpenOptions::new()
                 .write(true)
                 .open(tty_path)
                 .context(format!("Failed to open TTY device: {}", tty_path))?;
             
-            // Write the control character to the TTY
-            file.write_all(&[ctrl_code])
-                .context("Failed to write control character to TTY")?;
+            // Write the bytes to the TTY
+            file.write_all(bytes)
+                .context("Failed to write to TTY")?;
             
             Ok(())
         }
@@ -126,13 +148,12 @@ pub mod control_char {
 }
 
 pub mod tty_reader {
-    use super::*;
     use anyhow::{Context, Result};
     use regex::Regex;
     use std::fs::File;
     use std::io::Read;
     use std::path::Path;
-    use tracing::{debug, error, info, warn};
+    use tracing::{debug, error, info};
 
     /// TTY reader implementation for reading terminal output.
     ///
@@ -333,6 +354,7 @@ pub mod command_executor {
     use crate::mcp::iterm::applescript::{OsascriptRunner, SystemOsascriptRunner};
     use anyhow::Context;
     use tokio::task;
+    use tokio_util::sync::CancellationToken;
 
     /// CommandExecutor sends text/commands to iTerm via AppleScript.
     ///
@@ -378,24 +400,47 @@ pub mod command_executor {
         /// that writes the text into the current session and then execute it using
         /// the injected `OsascriptRunner`.
         pub async fn execute_command(&mut self, command: &str) -> Result<()> {
+            self.execute_command_cancellable(command, &CancellationToken::new())
+                .await
+        }
+
+        /// Execute a command, killing the `osascript` child if `cancel` fires first.
+        pub async fn execute_command_cancellable(
+            &mut self,
+            command: &str,
+            cancel: &CancellationToken,
+        ) -> Result<()> {
+            self.execute_in_session(None, command, cancel).await
+        }
+
+        /// Execute a command in the session with the given unique id, or in the
+        /// current session when `session` is `None`.
+        pub async fn execute_in_session(
+            &mut self,
+            session: Option<&str>,
+            command: &str,
+            cancel: &CancellationToken,
+        ) -> Result<()> {
             info!("Executing command in iTerm via AppleScript: {}", command);
             // Clone values to move into blocking task
             let cmd_owned = command.to_string();
+            let session = session.map(str::to_string);
             let runner = self.runner.clone();
             let timeout = self.default_timeout_secs;
+            let cancel = cancel.clone();
 
             // Use spawn_blocking to avoid blocking the async runtime while running osascript.
             let join_handle = task::spawn_blocking(move || {
-                // Build the AppleScript command using the applescript escape helper.
+                // Build the AppleScript command using the applescript helpers.
                 let escaped = crate::mcp::iterm::applescript::escape(&cmd_owned);
-                let applescript = format!(
-                    "tell application \"iTerm2\" to tell current session of current window to write text {}",
-                    escaped
+                let applescript = crate::mcp::iterm::applescript::in_session(
+                    session.as_deref(),
+                    &format!("write text {}", escaped),
                 );
 
                 // Run the script via injected runner.
                 runner
-                    .run(&[applescript.as_str()], timeout)
+                    .run_cancellable(&[applescript.as_str()], timeout, &cancel)
                     .map_err(|e| anyhow::anyhow!("osascript runner error: {}", e))
             });
 
@@ -417,19 +462,19 @@ pub mod command_executor {
 // `crate::mcp::iterm::{CommandExecutor, ControlCharacterSender, TtyReader}`
 pub use command_executor::CommandExecutor;
 pub use control_char::ControlCharacterSender;
+pub use screen_reader::ScreenReader;
 pub use tty_reader::TtyReader;
 
 // Re-export applescript helpers and runner types for convenience.
 pub use applescript::escape as escape_applescript;
-pub use applescript::osascript_with_timeout;
+pub use applescript::{osascript_with_cancellation, osascript_with_timeout};
 pub use applescript::{MockOsascriptRunner, OsascriptRunner, SystemOsascriptRunner};
+pub use cassette::{Cassette, RecordingOsascriptRunner, ReplayOsascriptRunner};
+pub use worker::{PersistentOsascriptRunner, ScriptWorker};
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-    
     mod tty_reader_tests {
-        use super::*;
         use crate::mcp::iterm::TtyReader;
         
         #[test]
@@ -483,18 +528,16 @@ mod tests {
             // Test custom buffer size and strip_ansi setting
             let reader = TtyReader::new_with_config(16384, false);
             assert_eq!(reader.get_buffer_size(), 16384);
-            assert_eq!(reader.is_strip_ansi_enabled(), false);
+            assert!(!reader.is_strip_ansi_enabled());
             
             // Test defaults
             let reader = TtyReader::new();
             assert_eq!(reader.get_buffer_size(), 8192);
-            assert_eq!(reader.is_strip_ansi_enabled(), true);
+            assert!(reader.is_strip_ansi_enabled());
         }
     }
     
     mod control_char_tests {
-        use super::*;
-        
         #[test]
         fn test_letter_to_control_char() {
             // Test A-Z mappings
@@ -0,0 +1,90 @@
+//! Screen reader that asks iTerm2 for the session contents.
+//!
+//! Reading the TTY device competes with the shell for its input and never
+//! sees what programs print, so [`ScreenReader`] instead runs an AppleScript
+//! returning the `contents` of the session (the visible screen plus
+//! scrollback) through an injectable [`OsascriptRunner`].
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use tokio::task;
+use tracing::{debug, info};
+
+use super::applescript::{in_session, OsascriptRunner};
+use crate::mcp::backend::last_captured_lines;
+
+/// Reads terminal output from iTerm2 via AppleScript.
+pub struct ScreenReader {
+    runner: Arc<dyn OsascriptRunner>,
+    default_timeout_secs: u64,
+}
+
+impl ScreenReader {
+    /// Create a reader running its script through `runner`.
+    pub fn new_with_runner(runner: Arc<dyn OsascriptRunner>, timeout_secs: u64) -> Self {
+        debug!("ScreenReader::new_with_runner()");
+        ScreenReader {
+            runner,
+            default_timeout_secs: timeout_secs,
+        }
+    }
+
+    /// Read the last `lines` lines of the active session.
+    ///
+    /// Blank rows below the cursor are dropped before counting lines.
+    pub async fn read_lines(&self, lines: usize) -> Result<String> {
+        self.read_session_lines(None, lines).await
+    }
+
+    /// Read the last `lines` lines of the session with the given unique id,
+    /// or of the active session when `session` is `None`.
+    pub async fn read_session_lines(&self, session: Option<&str>, lines: usize) -> Result<String> {
+        info!("Reading {} lines from iTerm2 via AppleScript", lines);
+        let script = in_session(session, "return contents");
+        let runner = self.runner.clone();
+        let timeout = self.default_timeout_secs;
+        let contents = task::spawn_blocking(move || runner.run(&[script.as_str()], timeout))
+            .await
+            .map_err(|e| anyhow!("failed to join osascript thread: {}", e))?
+            .context("Failed to read the session contents")?;
+
+        // iTerm2 ends rows with CR
+        let contents = contents.replace("\r\n", "\n").replace('\r', "\n");
+        Ok(last_captured_lines(&contents, lines))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::iterm::MockOsascriptRunner;
+
+    fn reader(dump: &str) -> ScreenReader {
+        let runner = MockOsascriptRunner::new(vec![dump.to_string()]);
+        ScreenReader::new_with_runner(Arc::new(runner), 1)
+    }
+
+    #[tokio::test]
+    async fn last_lines_of_the_screen_are_returned() {
+        let dump = "Last login: Mon\r$ cargo build\r   Compiling rs_iterm\r    Finished dev\r$ \r\r\r";
+        let screen = reader(dump).read_lines(3).await.unwrap();
+        assert_eq!(screen, "   Compiling rs_iterm\n    Finished dev\n$ ");
+
+        let screen = reader("one\r\ntwo\r\n").read_lines(10).await.unwrap();
+        assert_eq!(screen, "one\ntwo");
+    }
+
+    #[tokio::test]
+    async fn empty_screens_and_zero_lines_are_empty() {
+        assert_eq!(reader("\n\n\n").read_lines(5).await.unwrap(), "");
+        assert_eq!(reader("$ ls\n").read_lines(0).await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn runner_errors_are_reported() {
+        let reader = ScreenReader::new_with_runner(Arc::new(MockOsascriptRunner::empty()), 1);
+        let error = reader.read_lines(5).await.unwrap_err();
+        assert!(error.to_string().contains("session contents"), "got: {}", error);
+    }
+}
@@ -0,0 +1,377 @@
+//! Long-lived script interpreter speaking a line-delimited protocol over stdio.
+//!
+//! Spawning `/usr/bin/osascript` for every AppleScript costs tens of
+//! milliseconds; [`PersistentOsascriptRunner`] instead keeps one
+//! `osascript -l JavaScript` process running a small JXA loop that executes
+//! each AppleScript it receives with `run script`.
+//!
+//! The protocol is generic so any interpreter can stand in for `osascript`
+//! (tests use `sh`):
+//!
+//! - the worker is started with a delimiter as its last argument;
+//! - a request is the script followed by a line holding only the delimiter;
+//! - the reply is the script's output followed by `<delimiter> ok` or
+//!   `<delimiter> error`, in which case the output is the error message.
+//!
+//! The delimiter carries a random per-worker UUID, so scripts cannot forge it. A
+//! worker that times out, is cancelled or dies is killed and respawned on the
+//! next request.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use super::applescript::OsascriptRunner;
+
+/// How often a pending request checks for cancellation
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+/// JXA loop run by `osascript -l JavaScript`, implementing the worker protocol.
+const JXA_WORKER: &str = r#"
+ObjC.import('Foundation');
+
+function run(argv) {
+    var delimiter = argv[0];
+    var app = Application.currentApplication();
+    app.includeStandardAdditions = true;
+    var input = $.NSFileHandle.fileHandleWithStandardInput;
+    var output = $.NSFileHandle.fileHandleWithStandardOutput;
+    var pending = $.NSMutableData.data;
+    var buffer = '';
+
+    function write(text) {
+        output.writeData($(text).dataUsingEncoding($.NSUTF8StringEncoding));
+    }
+
+    // Next line of stdin, or null at EOF; waits for complete UTF-8 sequences
+    function readLine() {
+        while (buffer.indexOf('\n') < 0) {
+            var data = input.availableData;
+            if (data.length == 0) return null;
+            pending.appendData(data);
+            var text = $.NSString.alloc.initWithDataEncoding(pending, $.NSUTF8StringEncoding);
+            if (text.isNil()) continue;
+            buffer += text.js;
+            pending = $.NSMutableData.data;
+        }
+        var end = buffer.indexOf('\n');
+        var line = buffer.slice(0, end);
+        buffer = buffer.slice(end + 1);
+        return line;
+    }
+
+    for (;;) {
+        var lines = [];
+        var line;
+        while ((line = readLine()) !== null && line !== delimiter) lines.push(line);
+        if (line === null) return;
+
+        var result, status;
+        try {
+            var value = app.runScript(lines.join('\n'), {in: 'AppleScript'});
+            result = (value === undefined || value === null) ? '' : String(value);
+            status = 'ok';
+        } catch (e) {
+            result = String(e.message || e).replace(/\n/g, ' ');
+            status = 'error';
+        }
+        write((result.length ? result + '\n' : '') + delimiter + ' ' + status + '\n');
+    }
+}
+"#;
+
+/// A running worker process.
+struct WorkerProcess {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines of stdout, read by a helper thread; closed at EOF
+    lines: Receiver<String>,
+}
+
+impl WorkerProcess {
+    fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Kill the worker in `slot`, if any, so the next request starts a new one.
+fn discard(slot: &mut Option<WorkerProcess>) {
+    if let Some(process) = slot.take() {
+        process.kill();
+    }
+}
+
+/// A persistent interpreter driven through the delimiter protocol.
+pub struct ScriptWorker {
+    program: String,
+    args: Vec<String>,
+    delimiter: String,
+    process: Mutex<Option<WorkerProcess>>,
+}
+
+impl ScriptWorker {
+    /// Describe a worker started as `program args... <delimiter>`.
+    ///
+    /// The process is spawned on the first request.
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        ScriptWorker {
+            program: program.into(),
+            args,
+            delimiter: format!("--rs_iterm-{}--", uuid::Uuid::new_v4().simple()),
+            process: Mutex::new(None),
+        }
+    }
+
+    /// Pid of the running worker, if any.
+    pub fn pid(&self) -> Option<u32> {
+        self.process.lock().unwrap().as_ref().map(|process| process.child.id())
+    }
+
+    fn spawn(&self) -> Result<WorkerProcess> {
+        debug!("Starting script worker {} {:?}", self.program, self.args);
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .arg(&self.delimiter)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to start script worker {}", self.program))?;
+        let stdin = child.stdin.take().context("script worker has no stdin")?;
+        let stdout = child.stdout.take().context("script worker has no stdout")?;
+
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(WorkerProcess { child, stdin, lines })
+    }
+
+    /// Run `script` and return its output, killing the worker on timeout or cancellation.
+    ///
+    /// A worker found dead before the script is sent is restarted
+    /// transparently; one that dies while running it fails the request.
+    pub fn request(&self, script: &str, timeout: Duration, cancel: &CancellationToken) -> Result<String> {
+        let mut slot = self.process.lock().unwrap();
+
+        // Reap a worker that exited since the last request
+        if let Some(process) = slot.as_mut() {
+            if !matches!(process.child.try_wait(), Ok(None)) {
+                warn!("Script worker exited; restarting it");
+                *slot = None;
+            }
+        }
+        let process = match slot.as_mut() {
+            Some(process) => process,
+            None => slot.insert(self.spawn()?),
+        };
+
+        let mut request = String::with_capacity(script.len() + self.delimiter.len() + 2);
+        request.push_str(script);
+        if !script.ends_with('\n') {
+            request.push('\n');
+        }
+        request.push_str(&self.delimiter);
+        request.push('\n');
+        if let Err(e) = process.stdin.write_all(request.as_bytes()).and_then(|_| process.stdin.flush()) {
+            discard(&mut slot);
+            return Err(anyhow!("failed to send the script to the worker: {}", e));
+        }
+
+        let status_prefix = format!("{} ", self.delimiter);
+        let deadline = Instant::now() + timeout;
+        let mut output = String::new();
+        loop {
+            if cancel.is_cancelled() {
+                discard(&mut slot);
+                return Err(anyhow!("{} cancelled by the client", self.program));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                discard(&mut slot);
+                return Err(anyhow!("{} timed out after {:?}", self.program, timeout));
+            }
+
+            let line = match process.lines.recv_timeout(remaining.min(CANCEL_POLL)) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    discard(&mut slot);
+                    return Err(anyhow!("{} exited while running the script", self.program));
+                }
+            };
+            match line.strip_prefix(&status_prefix) {
+                Some("ok") => return Ok(output.replace("\r\n", "\n").replace('\r', "\n")),
+                Some(_) => return Err(anyhow!("{} failed: {}", self.program, output.trim_end())),
+                None => {
+                    output.push_str(&line);
+                    output.push('\n');
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ScriptWorker {
+    fn drop(&mut self) {
+        if let Some(process) = self.process.get_mut().unwrap().take() {
+            process.kill();
+        }
+    }
+}
+
+/// `OsascriptRunner` backed by one long-lived `osascript -l JavaScript` worker.
+///
+/// Unlike [`super::SystemOsascriptRunner`], AppleScript errors are returned
+/// as errors instead of an empty output.
+pub struct PersistentOsascriptRunner {
+    worker: ScriptWorker,
+}
+
+impl Default for PersistentOsascriptRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PersistentOsascriptRunner {
+    /// Runner starting the JXA worker on first use.
+    pub fn new() -> Self {
+        Self::with_worker(ScriptWorker::new(
+            "/usr/bin/osascript",
+            vec!["-l".to_string(), "JavaScript".to_string(), "-e".to_string(), JXA_WORKER.to_string()],
+        ))
+    }
+
+    /// Runner driving any interpreter that implements the worker protocol.
+    pub fn with_worker(worker: ScriptWorker) -> Self {
+        PersistentOsascriptRunner { worker }
+    }
+}
+
+impl OsascriptRunner for PersistentOsascriptRunner {
+    fn run(&self, e_lines: &[&str], timeout_secs: u64) -> Result<String> {
+        self.run_cancellable(e_lines, timeout_secs, &CancellationToken::new())
+    }
+
+    fn run_cancellable(&self, e_lines: &[&str], timeout_secs: u64, cancel: &CancellationToken) -> Result<String> {
+        // osascript joins `-e` arguments into one script, one per line
+        self.worker
+            .request(&e_lines.join("\n"), Duration::from_secs(timeout_secs), cancel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `sh` implementing the worker protocol: each request runs in `sh -c`.
+    const SH_WORKER: &str = r#"
+delimiter="$1"
+while :; do
+    script=""
+    complete=""
+    while IFS= read -r line; do
+        if [ "$line" = "$delimiter" ]; then complete=1; break; fi
+        script="$script$line
+"
+    done
+    [ -n "$complete" ] || exit 0
+    if out=$(sh -c "$script" 2>&1); then status=ok; else status=error; fi
+    [ -n "$out" ] && printf '%s\n' "$out"
+    printf '%s %s\n' "$delimiter" "$status"
+done
+"#;
+
+    fn sh_worker() -> ScriptWorker {
+        ScriptWorker::new("sh", vec!["-c".to_string(), SH_WORKER.to_string(), "sh-worker".to_string()])
+    }
+
+    #[test]
+    fn scripts_run_in_one_process() {
+        let runner = PersistentOsascriptRunner::with_worker(sh_worker());
+
+        assert_eq!(runner.run(&["echo hello"], 5).unwrap(), "hello\n");
+        let pid = runner.worker.pid().unwrap();
+
+        // Several `-e` lines form one script
+        assert_eq!(runner.run(&["x=4", "echo $((x * 2))", "echo done"], 5).unwrap(), "8\ndone\n");
+        assert_eq!(runner.run(&["true"], 5).unwrap(), "");
+
+        let error = runner.run(&["echo 'no such thing' >&2; exit 3"], 5).unwrap_err();
+        assert!(error.to_string().contains("no such thing"), "{}", error);
+
+        assert_eq!(runner.worker.pid(), Some(pid));
+    }
+
+    #[test]
+    fn worker_is_restarted_after_timeout_or_crash() {
+        let worker = sh_worker();
+        let cancel = CancellationToken::new();
+
+        worker.request("echo up", Duration::from_secs(5), &cancel).unwrap();
+        let first = worker.pid().unwrap();
+
+        let started = Instant::now();
+        let error = worker.request("sleep 5", Duration::from_millis(200), &cancel).unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(worker.pid(), None);
+
+        assert_eq!(worker.request("echo again", Duration::from_secs(5), &cancel).unwrap(), "again\n");
+        let second = worker.pid().unwrap();
+        assert_ne!(first, second);
+
+        // A worker killed behind our back is replaced on the next request
+        unsafe { libc::kill(second as libc::pid_t, libc::SIGKILL) };
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(worker.request("echo back", Duration::from_secs(5), &cancel).unwrap(), "back\n");
+        assert_ne!(worker.pid(), Some(second));
+    }
+
+    #[test]
+    fn cancellation_kills_the_running_script() {
+        let worker = sh_worker();
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            trigger.cancel();
+        });
+
+        let started = Instant::now();
+        let error = worker.request("sleep 5", Duration::from_secs(10), &cancel).unwrap_err();
+        assert!(error.to_string().contains("cancelled"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let output = worker.request("echo fresh", Duration::from_secs(5), &CancellationToken::new());
+        assert_eq!(output.unwrap(), "fresh\n");
+    }
+
+    #[test]
+    fn scripts_cannot_forge_the_delimiter() {
+        let worker = sh_worker();
+        let forged = "echo '--rs_iterm-1-0-- ok'; echo real";
+        let output = worker.request(forged, Duration::from_secs(5), &CancellationToken::new());
+        assert_eq!(output.unwrap(), "--rs_iterm-1-0-- ok\nreal\n");
+
+        // Not even the delimiter of another worker of this process
+        let other = sh_worker();
+        assert_ne!(other.delimiter, worker.delimiter);
+        let copied = format!("echo '{} ok'; echo real", other.delimiter);
+        let output = worker.request(&copied, Duration::from_secs(5), &CancellationToken::new());
+        assert_eq!(output.unwrap(), format!("{} ok\nreal\n", other.delimiter));
+    }
+}
@@ -0,0 +1,249 @@
+//! Tipos do protocolo JSON-RPC 2.0 usados pelo transporte MCP
+//!
+//! O MCP encapsula todas as mensagens em JSON-RPC 2.0. Este módulo contém apenas
+//! os envelopes de requisição/resposta e os códigos de erro padronizados; a
+//! semântica dos métodos MCP (`initialize`, `tools/list`, `tools/call`) fica no
+//! `Router`.
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// Versão do protocolo JSON-RPC suportada
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// JSON inválido recebido pelo servidor
+pub const PARSE_ERROR: i32 = -32700;
+
+/// O JSON enviado não é um objeto de requisição válido
+pub const INVALID_REQUEST: i32 = -32600;
+
+/// O método não existe ou não está disponível
+pub const METHOD_NOT_FOUND: i32 = -32601;
+
+/// Parâmetros inválidos para o método
+pub const INVALID_PARAMS: i32 = -32602;
+
+/// Erro interno do servidor
+pub const INTERNAL_ERROR: i32 = -32603;
+
+/// ID de uma requisição JSON-RPC: número, string ou null
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    /// ID numérico (inteiro)
+    Number(i64),
+    /// ID em string
+    String(String),
+    /// ID nulo (permitido pela especificação, usado também em erros sem ID)
+    Null,
+}
+
+impl RequestId {
+    /// Extrai o ID de uma mensagem possivelmente inválida, usando null se não houver
+    pub fn from_message(message: &Value) -> Self {
+        message
+            .get("id")
+            .and_then(|id| RequestId::deserialize(id).ok())
+            .unwrap_or(RequestId::Null)
+    }
+}
+
+impl From<i64> for RequestId {
+    fn from(id: i64) -> Self {
+        RequestId::Number(id)
+    }
+}
+
+impl From<String> for RequestId {
+    fn from(id: String) -> Self {
+        RequestId::String(id)
+    }
+}
+
+impl From<&str> for RequestId {
+    fn from(id: &str) -> Self {
+        RequestId::String(id.to_string())
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestId::Number(id) => write!(f, "{}", id),
+            RequestId::String(id) => write!(f, "{}", id),
+            RequestId::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// Distingue `"id": null` (Some(Null)) de um ID ausente (None)
+fn deserialize_present<'de, D>(deserializer: D) -> Result<Option<RequestId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    RequestId::deserialize(deserializer).map(Some)
+}
+
+/// Requisição JSON-RPC 2.0
+#[derive(Debug, Clone, Deserialize)]
+pub struct JsonRpcRequest {
+    /// Versão do protocolo (deve ser "2.0")
+    pub jsonrpc: String,
+
+    /// ID da requisição para correlação; ausente em notificações
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub id: Option<RequestId>,
+
+    /// Nome do método a ser invocado
+    pub method: String,
+
+    /// Parâmetros do método
+    #[serde(default)]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcRequest {
+    /// Notificações não têm ID e não recebem resposta
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+/// Resposta JSON-RPC 2.0
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonRpcResponse {
+    /// Versão do protocolo (sempre "2.0")
+    pub jsonrpc: &'static str,
+
+    /// ID da requisição original (null quando não pôde ser determinado)
+    pub id: RequestId,
+
+    /// Resultado da operação (para respostas de sucesso)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+
+    /// Detalhes do erro (para respostas de erro)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    /// Cria uma resposta de sucesso
+    pub fn success(id: RequestId, result: Value) -> Self {
+        JsonRpcResponse {
+            jsonrpc: JSONRPC_VERSION,
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    /// Cria uma resposta de erro
+    pub fn failure(id: RequestId, error: JsonRpcError) -> Self {
+        JsonRpcResponse {
+            jsonrpc: JSONRPC_VERSION,
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Notificação JSON-RPC 2.0 enviada pelo servidor (sem ID, sem resposta)
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonRpcNotification {
+    /// Versão do protocolo (sempre "2.0")
+    pub jsonrpc: &'static str,
+
+    /// Nome do método da notificação
+    pub method: String,
+
+    /// Parâmetros da notificação
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+}
+
+impl JsonRpcNotification {
+    /// Cria uma notificação com os parâmetros informados
+    pub fn new(method: impl Into<String>, params: Value) -> Self {
+        JsonRpcNotification {
+            jsonrpc: JSONRPC_VERSION,
+            method: method.into(),
+            params: Some(params),
+        }
+    }
+}
+
+/// Objeto de erro JSON-RPC 2.0
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonRpcError {
+    /// Código de erro
+    pub code: i32,
+
+    /// Mensagem de erro
+    pub message: String,
+
+    /// Dados adicionais do erro (opcional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    /// Cria um novo erro sem dados adicionais
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        JsonRpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Anexa dados adicionais ao erro
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn request_ids_may_be_numbers_strings_or_null() {
+        let parse = |value: Value| serde_json::from_value::<JsonRpcRequest>(value).unwrap().id;
+
+        assert_eq!(
+            parse(json!({"jsonrpc": "2.0", "id": 7, "method": "ping"})),
+            Some(RequestId::Number(7))
+        );
+        assert_eq!(
+            parse(json!({"jsonrpc": "2.0", "id": "abc", "method": "ping"})),
+            Some(RequestId::from("abc"))
+        );
+        assert_eq!(
+            parse(json!({"jsonrpc": "2.0", "id": null, "method": "ping"})),
+            Some(RequestId::Null)
+        );
+        assert_eq!(parse(json!({"jsonrpc": "2.0", "method": "ping"})), None);
+    }
+
+    #[test]
+    fn invalid_ids_are_rejected() {
+        let request = json!({"jsonrpc": "2.0", "id": {"nested": true}, "method": "ping"});
+        assert!(serde_json::from_value::<JsonRpcRequest>(request.clone()).is_err());
+        assert_eq!(RequestId::from_message(&request), RequestId::Null);
+    }
+
+    #[test]
+    fn response_echoes_id_type() {
+        let response = JsonRpcResponse::success(RequestId::Number(3), json!({}));
+        assert_eq!(serde_json::to_value(&response).unwrap()["id"], 3);
+
+        let response = JsonRpcResponse::failure(RequestId::Null, JsonRpcError::new(PARSE_ERROR, "x"));
+        assert!(serde_json::to_value(&response).unwrap()["id"].is_null());
+    }
+}
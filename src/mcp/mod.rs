@@ -1,6 +1,12 @@
+pub mod backend;
+pub mod framing;
+pub mod http;
 pub mod iterm;
+pub mod jsonrpc;
 pub mod router;
+pub mod schema;
 pub mod server;
+pub mod session;
 pub mod tools;
 pub mod types;
 pub mod utilities;
@@ -1,20 +1,33 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use anyhow::{Context, Result};
+use futures::future::join_all;
 use serde::{Deserialize, Serialize};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use serde_json::json;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
-use crate::mcp::tools::ToolHandler;
-use crate::mcp::types::ToolDefinition;
+use crate::mcp::framing::{write_frame, Frame, FrameError, FrameReader};
+use crate::mcp::jsonrpc::{self, JsonRpcError, JsonRpcRequest, JsonRpcResponse, RequestId};
+use crate::mcp::schema;
+use crate::mcp::session::Session;
+use crate::mcp::tools::{BlockingHandler, BlockingTool, Tool, ToolContext};
+use crate::mcp::types::{
+    CallToolParams, CallToolResult, CancelledParams, Framing, Implementation, InitializeParams, InitializeResult,
+    ServerCapabilities, TerminalBackendInfo, ToolDefinition, ToolsCapability, MCP_PROTOCOL_VERSION,
+    SUPPORTED_PROTOCOL_VERSIONS,
+};
 
-/// Estrutura que representa uma mensagem de requisição MCP
+/// Estrutura que representa uma mensagem de requisição no envelope legado
 #[derive(Debug, Clone, Deserialize)]
 struct McpRequest {
     /// ID da mensagem para correlação
-    id: String,
+    id: RequestId,
     
     /// Nome da ferramenta a ser invocada
     function: String,
@@ -23,11 +36,11 @@ struct McpRequest {
     arguments: serde_json::Value,
 }
 
-/// Estrutura que representa uma mensagem de resposta MCP
+/// Estrutura que representa uma mensagem de resposta no envelope legado
 #[derive(Debug, Clone, Serialize)]
 struct McpResponse {
     /// ID da mensagem original
-    id: String,
+    id: RequestId,
     
     /// Tipo de mensagem (response/error)
     #[serde(rename = "type")]
@@ -42,7 +55,7 @@ struct McpResponse {
     error: Option<McpError>,
 }
 
-/// Estrutura que representa um erro MCP
+/// Estrutura que representa um erro no envelope legado
 #[derive(Debug, Clone, Serialize)]
 struct McpError {
     /// Código de erro
@@ -56,10 +69,74 @@ struct McpError {
     data: Option<serde_json::Value>,
 }
 
+/// Contadores de mensagens e erros atualizados pelo loop de conexão
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionCounters {
+    /// Mensagens recebidas (incluindo frames inválidos)
+    pub messages: Arc<AtomicUsize>,
+    /// Erros de leitura, escrita e frames inválidos
+    pub errors: Arc<AtomicUsize>,
+}
+
+/// Cancela as requisições pendentes de uma conexão encerrada
+struct CancelOnDrop(Arc<Session>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel_all();
+    }
+}
+
+/// Requisição registrada na tabela de andamento da sessão
+///
+/// Se o futuro da requisição for descartado antes de `finish` (ex.: o cliente
+/// HTTP abandonou o `POST`), a entrada sai da tabela e o token é cancelado,
+/// interrompendo o trabalho que ainda estiver em execução.
+struct InFlight<'a> {
+    session: &'a Session,
+    id: Option<RequestId>,
+}
+
+impl<'a> InFlight<'a> {
+    fn begin(session: &'a Session, id: &RequestId) -> (Self, CancellationToken) {
+        let token = session.begin(id);
+        let in_flight = InFlight {
+            session,
+            id: Some(id.clone()),
+        };
+        (in_flight, token)
+    }
+
+    /// Remove a requisição concluída da tabela sem cancelá-la
+    fn finish(mut self) {
+        if let Some(id) = self.id.take() {
+            self.session.finish(&id);
+        }
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.session.cancel(&id);
+        }
+    }
+}
+
 /// Router MCP completo para gerenciar ferramentas e processar mensagens
 pub struct Router {
-    /// Ferramentas registradas com seus handlers
-    tools: Mutex<HashMap<String, (ToolDefinition, ToolHandler)>>,
+    /// Ferramentas registradas, indexadas pelo nome
+    tools: Mutex<HashMap<String, Arc<dyn Tool>>>,
+    /// Aceita o envelope legado `{id, function, arguments}` em vez de JSON-RPC
+    legacy_envelope: bool,
+    /// Backend de terminal informado no `initialize`
+    backend: Option<TerminalBackendInfo>,
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Router {
@@ -67,89 +144,140 @@ impl Router {
     pub fn new() -> Self {
         Router {
             tools: Mutex::new(HashMap::new()),
+            legacy_envelope: false,
+            backend: None,
         }
     }
 
-    /// Registra uma ferramenta no router
-    pub fn register_tool(&self, name: String, definition: ToolDefinition, handler: ToolHandler) {
+    /// Habilita ou desabilita o envelope legado `{id, function, arguments}`
+    pub fn with_legacy_envelope(mut self, enabled: bool) -> Self {
+        self.legacy_envelope = enabled;
+        self
+    }
+
+    /// Informa o backend de terminal ativo nas capacidades do `initialize`
+    pub fn with_backend_info(mut self, backend: TerminalBackendInfo) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Registra uma ferramenta no router, indexada pelo seu nome
+    pub fn register(&self, tool: Arc<dyn Tool>) {
         let mut guard = self.tools.lock().unwrap();
-        guard.insert(name, (definition, handler));
+        guard.insert(tool.name().to_string(), tool);
     }
 
-    /// Processa uma conexão TCP, implementando o protocolo MCP completo
-    pub async fn handle_connection(self: Arc<Self>, mut socket: TcpStream) -> Result<()> {
-        let addr = socket.peer_addr().unwrap_or_else(|_| "[unknown]".parse().unwrap());
-        info!("Processando conexão de {}", addr);
+    /// Registra um handler síncrono como ferramenta (executado em `spawn_blocking`)
+    pub fn register_tool(&self, name: String, definition: ToolDefinition, handler: BlockingHandler) {
+        let mut guard = self.tools.lock().unwrap();
+        guard.insert(name, Arc::new(BlockingTool::new(definition, handler)));
+    }
 
-        // Buffer para leitura dos dados
-        let mut buffer = vec![0u8; 8192]; // 8KB buffer inicial
-        let mut read_pos = 0;
+    /// Lista as definições das ferramentas registradas, ordenadas por nome
+    pub fn list_tools(&self) -> Vec<ToolDefinition> {
+        let guard = self.tools.lock().unwrap();
+        let mut definitions: Vec<ToolDefinition> =
+            guard.values().map(|tool| tool.definition()).collect();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        definitions
+    }
+
+    /// Processa uma conexão, implementando o protocolo MCP completo
+    ///
+    /// As mensagens são lidas com o enquadramento informado (newline,
+    /// Content-Length ou detecção automática) e as respostas são escritas no
+    /// mesmo formato. Frames inválidos recebem um erro de parse.
+    pub async fn handle_connection<S>(
+        self: Arc<Self>,
+        socket: S,
+        framing: Framing,
+        max_message_size: usize,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.serve_connection(socket, framing, max_message_size, ConnectionCounters::default())
+            .await
+    }
+
+    /// Processa uma conexão atualizando os contadores informados
+    ///
+    /// Cada mensagem é processada em sua própria task, de modo que a leitura
+    /// continua enquanto ferramentas executam e um `notifications/cancelled`
+    /// pode interromper uma chamada em andamento. As respostas são enviadas na
+    /// ordem em que ficam prontas. No EOF, aguarda as requisições pendentes
+    /// antes de encerrar; se a conexão cair antes disso, elas são canceladas.
+    pub async fn serve_connection<S>(
+        self: Arc<Self>,
+        socket: S,
+        framing: Framing,
+        max_message_size: usize,
+        counters: ConnectionCounters,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (reader, mut writer) = tokio::io::split(socket);
+        let mut frames = FrameReader::new(reader, framing, max_message_size);
+        let (outgoing_tx, mut outgoing_rx) = mpsc::unbounded_channel::<String>();
+        // Notificações de progresso e respostas compartilham o mesmo canal
+        let session = Arc::new(Session::with_outgoing(outgoing_tx.clone()));
+        let _cancel_pending = CancelOnDrop(session.clone());
+        let mut requests = JoinSet::new();
+        let mut reading = true;
 
-        // Loop principal de processamento de mensagens
         loop {
-            // Lê dados disponíveis
-            match socket.read(&mut buffer[read_pos..]).await {
-                Ok(0) => {
-                    // Conexão fechada pelo cliente
-                    debug!("Conexão fechada pelo cliente: {}", addr);
-                    break;
-                }
-                Ok(n) => {
-                    read_pos += n;
-                    debug!("Lidos {} bytes, total de {} bytes no buffer", n, read_pos);
-
-                    // Processa mensagens completas no buffer
-                    let mut processed_pos = 0;
-                    while processed_pos < read_pos {
-                        // Tenta encontrar um JSON válido terminado por newline (\n)
-                        if let Some(msg_end) = buffer[processed_pos..read_pos]
-                            .iter()
-                            .position(|&b| b == b'\n')
-                        {
-                            let msg_end = processed_pos + msg_end;
-                            
-                            // Extrai a mensagem JSON
-                            let message = std::str::from_utf8(&buffer[processed_pos..msg_end])
-                                .context("Falha ao converter bytes para UTF-8")?;
-                            
-                            // Processa a mensagem
-                            let response = self.process_message(message).await;
-                            
-                            // Envia a resposta
-                            if let Some(response_str) = response {
-                                socket.write_all(response_str.as_bytes()).await
-                                    .context("Falha ao enviar resposta")?;
-                                socket.write_all(b"\n").await
-                                    .context("Falha ao enviar newline")?;
-                                socket.flush().await
-                                    .context("Falha ao flush do socket")?;
-                            }
-                            
-                            // Atualiza posição processada
-                            processed_pos = msg_end + 1; // +1 para pular o \n
-                        } else {
-                            // Mensagem incompleta, precisamos ler mais dados
-                            break;
+            // Após o EOF, encerra quando todas as respostas pendentes foram enviadas
+            if !reading && requests.is_empty() && outgoing_rx.is_empty() {
+                break;
+            }
+
+            tokio::select! {
+                frame = frames.next_frame(), if reading => {
+                    match frame {
+                        Ok(Some(Frame::Message(message))) => {
+                            counters.messages.fetch_add(1, Ordering::Relaxed);
+
+                            let router = self.clone();
+                            let session = session.clone();
+                            let outgoing = outgoing_tx.clone();
+                            requests.spawn(async move {
+                                if let Some(response) =
+                                    router.process_session_message(&message, &session).await
+                                {
+                                    let _ = outgoing.send(response);
+                                }
+                            });
+                        }
+                        Ok(Some(Frame::Invalid(error))) => {
+                            warn!("Frame inválido: {}", error);
+                            counters.messages.fetch_add(1, Ordering::Relaxed);
+                            counters.errors.fetch_add(1, Ordering::Relaxed);
+                            let _ = outgoing_tx.send(self.create_frame_error_response(&error));
+                        }
+                        Ok(None) => {
+                            debug!("Conexão fechada pelo cliente");
+                            reading = false;
+                        }
+                        Err(e) => {
+                            error!("Erro ao ler do socket: {}", e);
+                            counters.errors.fetch_add(1, Ordering::Relaxed);
+                            return Err(anyhow::anyhow!("Erro de leitura: {}", e));
                         }
                     }
-                    
-                    // Move dados não processados para o início do buffer
-                    if processed_pos > 0 {
-                        buffer.copy_within(processed_pos..read_pos, 0);
-                        read_pos -= processed_pos;
-                    }
-                    
-                    // Verifica se o buffer está cheio e precisa ser expandido
-                    if read_pos == buffer.len() {
-                        // Aumenta o buffer em 50%
-                        let new_size = (buffer.len() * 3) / 2;
-                        buffer.resize(new_size, 0);
-                        debug!("Buffer expandido para {} bytes", new_size);
+                }
+                Some(response) = outgoing_rx.recv() => {
+                    if let Err(e) = write_frame(&mut writer, &response, frames.framing()).await {
+                        error!("Falha ao enviar resposta: {}", e);
+                        counters.errors.fetch_add(1, Ordering::Relaxed);
+                        return Err(e).context("Falha ao enviar resposta");
                     }
                 }
-                Err(e) => {
-                    error!("Erro ao ler do socket: {}", e);
-                    return Err(anyhow::anyhow!("Erro de leitura: {}", e));
+                Some(joined) = requests.join_next(), if !requests.is_empty() => {
+                    if let Err(e) = joined {
+                        error!("Falha na task da requisição: {}", e);
+                        counters.errors.fetch_add(1, Ordering::Relaxed);
+                    }
                 }
             }
         }
@@ -158,9 +286,340 @@ impl Router {
     }
 
     /// Processa uma mensagem MCP e retorna a resposta formatada
+    ///
+    /// Por padrão a mensagem é interpretada como JSON-RPC 2.0; com o envelope
+    /// legado habilitado, usa o formato `{id, function, arguments}`. Retorna
+    /// `None` quando não há resposta a enviar (notificações, ou um lote
+    /// composto apenas de notificações).
     pub async fn process_message(&self, message: &str) -> Option<String> {
+        self.process_session_message(message, &Session::new()).await
+    }
+
+    /// Processa uma mensagem no contexto de uma sessão
+    ///
+    /// Requisições em andamento ficam registradas na sessão para que um
+    /// `notifications/cancelled` posterior da mesma sessão possa cancelá-las.
+    pub async fn process_session_message(&self, message: &str, session: &Session) -> Option<String> {
         debug!("Processando mensagem: {}", message);
-        
+
+        if self.legacy_envelope {
+            return self.process_legacy_message(message).await;
+        }
+
+        // Parse da mensagem JSON
+        let value: serde_json::Value = match serde_json::from_str(message) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("Erro ao fazer parse da mensagem JSON: {}", e);
+                return Some(self.create_jsonrpc_error_response(
+                    RequestId::Null,
+                    JsonRpcError::new(jsonrpc::PARSE_ERROR, "Mensagem JSON inválida"),
+                ));
+            }
+        };
+
+        let reply = match value {
+            serde_json::Value::Array(batch) => self.process_batch(batch, session).await,
+            single => self
+                .process_request(single, session)
+                .await
+                .map(|response| serde_json::to_value(response).unwrap_or_default()),
+        }?;
+
+        match serde_json::to_string(&reply) {
+            Ok(json) => Some(json),
+            Err(e) => {
+                error!("Erro ao serializar resposta: {}", e);
+                Some(self.create_jsonrpc_error_response(
+                    RequestId::Null,
+                    JsonRpcError::new(jsonrpc::INTERNAL_ERROR, "Erro interno ao serializar resposta"),
+                ))
+            }
+        }
+    }
+
+    /// Processa um lote JSON-RPC
+    ///
+    /// As requisições do lote são executadas concorrentemente e as respostas
+    /// devolvidas em um array na ordem das requisições, omitindo notificações.
+    async fn process_batch(
+        &self,
+        batch: Vec<serde_json::Value>,
+        session: &Session,
+    ) -> Option<serde_json::Value> {
+        if batch.is_empty() {
+            warn!("Lote JSON-RPC vazio");
+            let response = JsonRpcResponse::failure(
+                RequestId::Null,
+                JsonRpcError::new(jsonrpc::INVALID_REQUEST, "Lote JSON-RPC vazio"),
+            );
+            return serde_json::to_value(response).ok();
+        }
+
+        debug!("Processando lote com {} mensagens", batch.len());
+        let responses: Vec<JsonRpcResponse> =
+            join_all(batch.into_iter().map(|request| self.process_request(request, session)))
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+
+        // Um lote só de notificações não recebe resposta
+        if responses.is_empty() {
+            return None;
+        }
+
+        serde_json::to_value(responses).ok()
+    }
+
+    /// Processa uma única requisição JSON-RPC já decodificada
+    async fn process_request(
+        &self,
+        value: serde_json::Value,
+        session: &Session,
+    ) -> Option<JsonRpcResponse> {
+        // Valida o envelope JSON-RPC, preservando o ID quando possível
+        let id_hint = RequestId::from_message(&value);
+        let request: JsonRpcRequest = match serde_json::from_value(value) {
+            Ok(req) => req,
+            Err(e) => {
+                warn!("Requisição JSON-RPC inválida: {}", e);
+                return Some(JsonRpcResponse::failure(
+                    id_hint,
+                    JsonRpcError::new(jsonrpc::INVALID_REQUEST, "Requisição JSON-RPC inválida"),
+                ));
+            }
+        };
+
+        if request.jsonrpc != jsonrpc::JSONRPC_VERSION {
+            return Some(JsonRpcResponse::failure(
+                request.id.unwrap_or(RequestId::Null),
+                JsonRpcError::new(
+                    jsonrpc::INVALID_REQUEST,
+                    format!("Versão JSON-RPC não suportada: {}", request.jsonrpc),
+                ),
+            ));
+        }
+
+        if request.method == "notifications/cancelled" {
+            self.handle_cancelled(request.params, session);
+            return None;
+        }
+
+        // Notificações do cliente não recebem resposta
+        if request.method.starts_with("notifications/") {
+            debug!("Notificação recebida: {}", request.method);
+            return None;
+        }
+
+        let result = match request.method.as_str() {
+            "initialize" => self.handle_initialize(request.params),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.list_tools() })),
+            "tools/call" => {
+                let (in_flight, cancellation) = match &request.id {
+                    Some(id) => {
+                        let (in_flight, token) = InFlight::begin(session, id);
+                        (Some(in_flight), token)
+                    }
+                    None => (None, CancellationToken::new()),
+                };
+                let result = self
+                    .handle_tool_call(request.params, cancellation.clone(), session)
+                    .await;
+                if let Some(in_flight) = in_flight {
+                    in_flight.finish();
+                }
+
+                // O cliente já descartou a requisição; não envia resposta tardia
+                if cancellation.is_cancelled() {
+                    info!(
+                        "Requisição {} cancelada pelo cliente; resposta descartada",
+                        request.id.unwrap_or(RequestId::Null)
+                    );
+                    return None;
+                }
+                result
+            }
+            method => {
+                warn!("Método não encontrado: {}", method);
+                Err(JsonRpcError::new(
+                    jsonrpc::METHOD_NOT_FOUND,
+                    format!("Método não encontrado: {}", method),
+                ))
+            }
+        };
+
+        // Requisições sem ID são notificações: executadas, mas sem resposta
+        let id = match request.id {
+            Some(id) => id,
+            None => {
+                debug!("Requisição {} sem ID tratada como notificação", request.method);
+                return None;
+            }
+        };
+
+        Some(match result {
+            Ok(result) => JsonRpcResponse::success(id, result),
+            Err(error) => JsonRpcResponse::failure(id, error),
+        })
+    }
+
+    /// Responde ao `initialize`, negociando a versão do protocolo
+    fn handle_initialize(
+        &self,
+        params: Option<serde_json::Value>,
+    ) -> Result<serde_json::Value, JsonRpcError> {
+        let params: InitializeParams = match params {
+            Some(params) => serde_json::from_value(params).map_err(|e| {
+                JsonRpcError::new(
+                    jsonrpc::INVALID_PARAMS,
+                    format!("Parâmetros de initialize inválidos: {}", e),
+                )
+            })?,
+            None => InitializeParams::default(),
+        };
+
+        if let Some(client) = &params.client_info {
+            info!("Cliente MCP conectado: {} {}", client.name, client.version);
+        }
+
+        // Usa a versão pedida pelo cliente se suportada, senão a mais recente
+        let protocol_version = match params.protocol_version {
+            Some(version) if SUPPORTED_PROTOCOL_VERSIONS.contains(&version.as_str()) => version,
+            _ => MCP_PROTOCOL_VERSION.to_string(),
+        };
+
+        let result = InitializeResult {
+            protocol_version,
+            capabilities: ServerCapabilities {
+                tools: Some(ToolsCapability { list_changed: false }),
+                experimental: self
+                    .backend
+                    .as_ref()
+                    .map(|backend| json!({ "terminalBackend": backend })),
+            },
+            server_info: Implementation {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        };
+
+        serde_json::to_value(result)
+            .map_err(|e| JsonRpcError::new(jsonrpc::INTERNAL_ERROR, e.to_string()))
+    }
+
+    /// Executa uma ferramenta em resposta a `tools/call`
+    ///
+    /// Erros da ferramenta são reportados no resultado com `isError`, como pede
+    /// o MCP; apenas ferramentas desconhecidas e parâmetros malformados viram
+    /// erros JSON-RPC. A ferramenta é aguardada sem segurar o lock do registro,
+    /// para que chamadas de um lote executem em paralelo. Se `cancellation`
+    /// disparar, retorna sem aguardar a ferramenta, que recebe o mesmo token no
+    /// `ToolContext` para interromper seu trabalho. Se o cliente enviou
+    /// `_meta.progressToken` e a sessão tem um canal de saída, a ferramenta
+    /// pode emitir `notifications/progress` por ele.
+    async fn handle_tool_call(
+        &self,
+        params: Option<serde_json::Value>,
+        cancellation: CancellationToken,
+        session: &Session,
+    ) -> Result<serde_json::Value, JsonRpcError> {
+        let params: CallToolParams = params
+            .ok_or_else(|| JsonRpcError::new(jsonrpc::INVALID_PARAMS, "Parâmetros ausentes"))
+            .and_then(|params| {
+                serde_json::from_value(params).map_err(|e| {
+                    JsonRpcError::new(
+                        jsonrpc::INVALID_PARAMS,
+                        format!("Parâmetros de tools/call inválidos: {}", e),
+                    )
+                })
+            })?;
+
+        let tool = self.tool(&params.name).ok_or_else(|| {
+            warn!("Ferramenta não encontrada: {}", params.name);
+            JsonRpcError::new(
+                jsonrpc::INVALID_PARAMS,
+                format!("Ferramenta não encontrada: {}", params.name),
+            )
+        })?;
+
+        let arguments = params.arguments.unwrap_or_else(|| json!({}));
+        self.validate_arguments(tool.as_ref(), &arguments)?;
+
+        let mut context = ToolContext::new(cancellation.clone());
+        let progress_token = params.meta.and_then(|meta| meta.progress_token);
+        if let (Some(token), Some(outgoing)) = (progress_token, session.outgoing()) {
+            context = context.with_progress(token, outgoing);
+        }
+
+        let outcome = tokio::select! {
+            outcome = tool.call(context, arguments) => outcome,
+            _ = cancellation.cancelled() => {
+                return Err(JsonRpcError::new(jsonrpc::INTERNAL_ERROR, "Requisição cancelada"));
+            }
+        };
+
+        let result = match outcome {
+            Ok(value) => CallToolResult::success(value),
+            Err(e) => {
+                error!("Erro ao executar handler: {}", e);
+                CallToolResult::error(format!("Erro ao executar ferramenta: {}", e))
+            }
+        };
+
+        serde_json::to_value(result)
+            .map_err(|e| JsonRpcError::new(jsonrpc::INTERNAL_ERROR, e.to_string()))
+    }
+
+    /// Trata `notifications/cancelled`, cancelando a requisição indicada
+    fn handle_cancelled(&self, params: Option<serde_json::Value>, session: &Session) {
+        let params: CancelledParams = match params.map(serde_json::from_value) {
+            Some(Ok(params)) => params,
+            _ => {
+                warn!("notifications/cancelled com parâmetros inválidos");
+                return;
+            }
+        };
+
+        if session.cancel(&params.request_id) {
+            info!(
+                "Requisição {} cancelada pelo cliente: {}",
+                params.request_id,
+                params.reason.as_deref().unwrap_or("sem motivo informado")
+            );
+        }
+    }
+
+    /// Valida os argumentos contra o esquema da ferramenta
+    ///
+    /// Retorna `-32602` com a lista de erros por campo em `data.errors`.
+    fn validate_arguments(
+        &self,
+        tool: &dyn Tool,
+        arguments: &serde_json::Value,
+    ) -> Result<(), JsonRpcError> {
+        schema::validate(&tool.input_schema(), arguments).map_err(|errors| {
+            warn!("Argumentos inválidos para {}: {:?}", tool.name(), errors);
+            JsonRpcError::new(
+                jsonrpc::INVALID_PARAMS,
+                format!("Argumentos inválidos para {}", tool.name()),
+            )
+            .with_data(json!({ "errors": errors }))
+        })
+    }
+
+    /// Obtém uma ferramenta registrada
+    ///
+    /// O lock do registro é liberado antes de retornar, para que a execução
+    /// da ferramenta não bloqueie outras requisições.
+    fn tool(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        let guard = self.tools.lock().unwrap();
+        guard.get(name).cloned()
+    }
+
+    /// Processa uma mensagem no envelope legado `{id, function, arguments}`
+    async fn process_legacy_message(&self, message: &str) -> Option<String> {
         // Parse da mensagem JSON
         let request: McpRequest = match serde_json::from_str(message) {
             Ok(req) => req,
@@ -176,13 +635,12 @@ impl Router {
         };
         
         // Verifica se a ferramenta existe
-        let tools = self.tools.lock().unwrap();
-        let (_, handler) = match tools.get(&request.function) {
+        let tool = match self.tool(&request.function) {
             Some(tool) => tool,
             None => {
                 warn!("Ferramenta não encontrada: {}", request.function);
                 return Some(self.create_error_response(
-                    &request.id,
+                    request.id.clone(),
                     -32601,
                     &format!("Ferramenta não encontrada: {}", request.function),
                     None,
@@ -190,8 +648,17 @@ impl Router {
             }
         };
         
-        // Executa o handler da ferramenta
-        match handler(request.arguments.clone()) {
+        if let Err(error) = self.validate_arguments(tool.as_ref(), &request.arguments) {
+            return Some(self.create_error_response(
+                request.id.clone(),
+                error.code,
+                &error.message,
+                error.data,
+            ));
+        }
+
+        // Executa a ferramenta
+        match tool.call(ToolContext::default(), request.arguments.clone()).await {
             Ok(result) => {
                 // Cria resposta de sucesso
                 let response = McpResponse {
@@ -206,7 +673,7 @@ impl Router {
                     Err(e) => {
                         error!("Erro ao serializar resposta: {}", e);
                         Some(self.create_error_response(
-                            &request.id,
+                            request.id.clone(),
                             -32603,
                             "Erro interno ao serializar resposta",
                             None,
@@ -217,7 +684,7 @@ impl Router {
             Err(e) => {
                 error!("Erro ao executar handler: {}", e);
                 Some(self.create_error_response(
-                    &request.id,
+                    request.id.clone(),
                     -32000,
                     &format!("Erro ao executar ferramenta: {}", e),
                     None,
@@ -226,16 +693,50 @@ impl Router {
         }
     }
 
-    /// Cria uma resposta de erro formatada
+    /// Cria uma resposta de erro JSON-RPC 2.0 formatada
+    pub fn create_jsonrpc_error_response(&self, id: RequestId, error: JsonRpcError) -> String {
+        let response = JsonRpcResponse::failure(id, error);
+
+        serde_json::to_string(&response).unwrap_or_else(|_| {
+            r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32603,"message":"Erro interno ao criar resposta de erro"}}"#
+                .to_string()
+        })
+    }
+
+    /// Cria a resposta de erro de parse para um frame descartado pelo transporte
+    pub fn create_frame_error_response(&self, error: &FrameError) -> String {
+        let data = match error {
+            FrameError::Oversized { size, limit } => Some(json!({ "size": size, "limit": limit })),
+            _ => None,
+        };
+
+        if self.legacy_envelope {
+            return self.create_error_response(
+                "invalid-request",
+                jsonrpc::PARSE_ERROR,
+                &error.to_string(),
+                data,
+            );
+        }
+
+        let mut rpc_error = JsonRpcError::new(jsonrpc::PARSE_ERROR, error.to_string());
+        if let Some(data) = data {
+            rpc_error = rpc_error.with_data(data);
+        }
+        self.create_jsonrpc_error_response(RequestId::Null, rpc_error)
+    }
+
+    /// Cria uma resposta de erro formatada no envelope legado
     pub fn create_error_response(
         &self,
-        id: &str,
+        id: impl Into<RequestId>,
         code: i32,
         message: &str,
         data: Option<serde_json::Value>,
     ) -> String {
+        let id = id.into();
         let response = McpResponse {
-            id: id.to_string(),
+            id: id.clone(),
             response_type: "error".to_string(),
             result: None,
             error: Some(McpError {
@@ -247,8 +748,8 @@ impl Router {
         
         serde_json::to_string(&response).unwrap_or_else(|_| {
             format!(
-                r#"{{"id":"{}","type":"error","error":{{"code":-32603,"message":"Erro interno ao criar resposta de erro"}}}}"#,
-                id
+                r#"{{"id":{},"type":"error","error":{{"code":-32603,"message":"Erro interno ao criar resposta de erro"}}}}"#,
+                serde_json::to_string(&id).unwrap_or_else(|_| "null".to_string())
             )
         })
     }
@@ -257,7 +758,6 @@ impl Router {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Arc;
     
     #[test]
     fn test_create_error_response() {
@@ -0,0 +1,496 @@
+//! Esquemas JSON das ferramentas
+//!
+//! Os esquemas anunciados em `tools/list` são gerados a partir dos tipos de
+//! parâmetros em `types.rs` (via `schemars`), de modo que o contrato exposto ao
+//! cliente e a desserialização nos handlers não divergem. Antes de executar uma
+//! ferramenta, o router valida os argumentos contra o esquema e devolve
+//! `-32602` com os erros por campo.
+//!
+//! O validador cobre o subconjunto de JSON Schema que o `schemars` gera:
+//! `type`, `properties`, `required`, `additionalProperties`, `items`, `enum`,
+//! `const`, `anyOf`/`oneOf`/`allOf`, `$ref` locais (`$defs`/`definitions`),
+//! limites numéricos, de tamanho e os formatos de inteiro (`uint32` etc.).
+//! Esquemas com palavras-chave de validação fora desse subconjunto são
+//! recusados, para que nenhuma restrição seja ignorada em silêncio.
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Gera o esquema de parâmetros de um tipo, pronto para `inputSchema`
+pub fn schema_for<T: JsonSchema>() -> Value {
+    let mut schema = schemars::schema_for!(T).to_value();
+    if let Value::Object(map) = &mut schema {
+        // Metadados do documento não fazem parte do `inputSchema` do MCP
+        map.remove("$schema");
+        map.remove("title");
+    }
+    schema
+}
+
+/// Erro de validação de um campo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    /// Caminho do campo (ex.: `linesOfOutput`, `items[2]`); vazio para a raiz
+    pub field: String,
+    /// Descrição do problema
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Palavras-chave de validação que o validador não implementa
+///
+/// Um esquema que as use é recusado em vez de aceitar valores sem checá-las.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "not",
+    "if",
+    "then",
+    "else",
+    "pattern",
+    "patternProperties",
+    "propertyNames",
+    "dependentRequired",
+    "dependentSchemas",
+    "dependencies",
+    "prefixItems",
+    "contains",
+    "uniqueItems",
+    "multipleOf",
+    "minProperties",
+    "maxProperties",
+    "unevaluatedProperties",
+    "unevaluatedItems",
+    "$dynamicRef",
+    "$recursiveRef",
+];
+
+/// Limite de `$ref` encadeados sem consumir o valor (esquemas recursivos)
+const MAX_REF_DEPTH: usize = 32;
+
+/// Valida um valor contra um esquema, retornando todos os erros encontrados
+pub fn validate(schema: &Value, value: &Value) -> Result<(), Vec<FieldError>> {
+    let mut validator = Validator {
+        root: schema,
+        ref_depth: 0,
+        errors: Vec::new(),
+    };
+    validator.validate_at(schema, value, "");
+    if validator.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(validator.errors)
+    }
+}
+
+/// Estado de uma validação: o esquema raiz resolve os `$ref`
+struct Validator<'a> {
+    root: &'a Value,
+    ref_depth: usize,
+    errors: Vec<FieldError>,
+}
+
+impl<'a> Validator<'a> {
+    fn validate_at(&mut self, schema: &'a Value, value: &Value, path: &str) {
+        let schema = match schema {
+            Value::Object(schema) => schema,
+            // `true` ou ausente aceita qualquer valor; `false` não aceita nenhum
+            Value::Bool(false) => {
+                self.errors.push(FieldError::new(path, "campo não permitido"));
+                return;
+            }
+            _ => return,
+        };
+
+        if let Some(keyword) = UNSUPPORTED_KEYWORDS.iter().find(|keyword| schema.contains_key(**keyword)) {
+            self.errors.push(FieldError::new(
+                path,
+                format!("esquema usa a palavra-chave não suportada `{}`", keyword),
+            ));
+            return;
+        }
+
+        if let Some(reference) = schema.get("$ref") {
+            match reference.as_str().and_then(|reference| self.resolve(reference)) {
+                Some(_) if self.ref_depth >= MAX_REF_DEPTH => {
+                    self.errors.push(FieldError::new(path, "esquema com `$ref` recursivo"));
+                    return;
+                }
+                Some(target) => {
+                    self.ref_depth += 1;
+                    self.validate_at(target, value, path);
+                    self.ref_depth -= 1;
+                }
+                None => {
+                    self.errors.push(FieldError::new(
+                        path,
+                        format!("referência de esquema não resolvida: {}", reference),
+                    ));
+                    return;
+                }
+            }
+        }
+
+        if let Some(Value::Array(branches)) = schema.get("allOf") {
+            for branch in branches {
+                self.validate_at(branch, value, path);
+            }
+        }
+        if let Some(Value::Array(branches)) = schema.get("anyOf") {
+            if self.count_matches(branches, value, path) == 0 {
+                self.errors.push(FieldError::new(path, "valor não corresponde a nenhuma alternativa"));
+            }
+        }
+        if let Some(Value::Array(branches)) = schema.get("oneOf") {
+            match self.count_matches(branches, value, path) {
+                1 => {}
+                0 => self.errors.push(FieldError::new(path, "valor não corresponde a nenhuma alternativa")),
+                matches => self.errors.push(FieldError::new(
+                    path,
+                    format!("valor corresponde a {} alternativas, esperada exatamente uma", matches),
+                )),
+            }
+        }
+
+        if let Some(expected) = schema.get("type") {
+            if !matches_type(expected, value) {
+                self.errors.push(FieldError::new(
+                    path,
+                    format!("esperado {}, recebido {}", describe_type(expected), type_name(value)),
+                ));
+                return;
+            }
+        }
+
+        if let Some(allowed) = schema.get("const") {
+            if allowed != value {
+                self.errors.push(FieldError::new(path, format!("valor deve ser {}", allowed)));
+            }
+        }
+
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(value) {
+                self.errors.push(FieldError::new(
+                    path,
+                    format!("valor deve ser um de {}", Value::Array(allowed.clone())),
+                ));
+            }
+        }
+
+        match value {
+            Value::Object(object) => self.validate_object(schema, object, path),
+            Value::Array(items) => {
+                let length = items.len() as u64;
+                if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                    if length < min {
+                        self.errors.push(FieldError::new(path, format!("lista deve ter ao menos {} itens", min)));
+                    }
+                }
+                if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                    if length > max {
+                        self.errors.push(FieldError::new(path, format!("lista deve ter no máximo {} itens", max)));
+                    }
+                }
+                if let Some(item_schema) = schema.get("items") {
+                    for (index, item) in items.iter().enumerate() {
+                        self.validate_at(item_schema, item, &format!("{}[{}]", path, index));
+                    }
+                }
+            }
+            Value::Number(number) => self.validate_number(schema, number, path),
+            Value::String(text) => {
+                let length = text.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                    if length < min {
+                        self.errors
+                            .push(FieldError::new(path, format!("texto deve ter ao menos {} caracteres", min)));
+                    }
+                }
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                    if length > max {
+                        self.errors
+                            .push(FieldError::new(path, format!("texto deve ter no máximo {} caracteres", max)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Conta as alternativas que aceitam o valor, sem acumular seus erros
+    fn count_matches(&mut self, branches: &'a [Value], value: &Value, path: &str) -> usize {
+        let outer = std::mem::take(&mut self.errors);
+        let mut matches = 0;
+        for branch in branches {
+            self.validate_at(branch, value, path);
+            if self.errors.is_empty() {
+                matches += 1;
+            }
+            self.errors.clear();
+        }
+        self.errors = outer;
+        matches
+    }
+
+    fn validate_number(&mut self, schema: &Map<String, Value>, number: &serde_json::Number, path: &str) {
+        let number = number.as_f64().unwrap_or_default();
+        // O `schemars` limita os inteiros de Rust pelo `format` (ex.: `uint32`),
+        // às vezes sem `maximum`
+        let range = schema.get("format").and_then(Value::as_str).and_then(integer_range);
+        let minimum = schema.get("minimum").and_then(Value::as_f64);
+        let maximum = schema.get("maximum").and_then(Value::as_f64);
+        if let Some(minimum) = minimum.or(range.map(|(min, _)| min)) {
+            if number < minimum {
+                self.errors.push(FieldError::new(path, format!("valor deve ser >= {}", minimum)));
+            }
+        }
+        if let Some(maximum) = maximum.or(range.map(|(_, max)| max)) {
+            if number > maximum {
+                self.errors.push(FieldError::new(path, format!("valor deve ser <= {}", maximum)));
+            }
+        }
+        if let Some(minimum) = schema.get("exclusiveMinimum").and_then(Value::as_f64) {
+            if number <= minimum {
+                self.errors.push(FieldError::new(path, format!("valor deve ser > {}", minimum)));
+            }
+        }
+        if let Some(maximum) = schema.get("exclusiveMaximum").and_then(Value::as_f64) {
+            if number >= maximum {
+                self.errors.push(FieldError::new(path, format!("valor deve ser < {}", maximum)));
+            }
+        }
+    }
+
+    fn validate_object(&mut self, schema: &'a Map<String, Value>, object: &Map<String, Value>, path: &str) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    self.errors
+                        .push(FieldError::new(&join(path, name), "campo obrigatório ausente"));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (name, value) in object {
+            let field = join(path, name);
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => self.validate_at(property, value, &field),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        self.errors.push(FieldError::new(&field, "campo desconhecido"));
+                    }
+                    Some(additional @ Value::Object(_)) => self.validate_at(additional, value, &field),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// Resolve um `$ref` local (`#/$defs/...`, `#/definitions/...`) no esquema raiz
+    fn resolve(&self, reference: &str) -> Option<&'a Value> {
+        self.root.pointer(reference.strip_prefix('#')?)
+    }
+}
+
+/// Faixa dos formatos de inteiro gerados pelo `schemars`
+fn integer_range(format: &str) -> Option<(f64, f64)> {
+    let range = match format {
+        "uint8" => (0.0, u8::MAX as f64),
+        "uint16" => (0.0, u16::MAX as f64),
+        "uint32" => (0.0, u32::MAX as f64),
+        "uint64" | "uint" => (0.0, u64::MAX as f64),
+        "int8" => (i8::MIN as f64, i8::MAX as f64),
+        "int16" => (i16::MIN as f64, i16::MAX as f64),
+        "int32" => (i32::MIN as f64, i32::MAX as f64),
+        "int64" | "int" => (i64::MIN as f64, i64::MAX as f64),
+        _ => return None,
+    };
+    Some(range)
+}
+
+fn matches_type(expected: &Value, value: &Value) -> bool {
+    match expected {
+        Value::String(name) => matches_type_name(name, value),
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|name| matches_type_name(name, value)),
+        _ => true,
+    }
+}
+
+fn matches_type_name(name: &str, value: &Value) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => match value {
+            Value::Number(number) => {
+                number.is_i64() || number.is_u64() || number.as_f64().is_some_and(|n| n.fract() == 0.0)
+            }
+            _ => false,
+        },
+        _ => true,
+    }
+}
+
+fn describe_type(expected: &Value) -> String {
+    match expected {
+        Value::Array(names) => names
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" ou "),
+        Value::String(name) => name.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::types::{ReadTerminalOutputParams, WriteToTerminalParams};
+    use serde_json::json;
+
+    #[test]
+    fn derived_schema_matches_serde_names() {
+        let schema = schema_for::<ReadTerminalOutputParams>();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], json!(["linesOfOutput"]));
+        assert_eq!(schema["properties"]["linesOfOutput"]["type"], "integer");
+        assert!(schema.get("$schema").is_none());
+
+        // A entrada documentada pelo esquema desserializa no tipo
+        let input = json!({ "linesOfOutput": 5 });
+        assert!(validate(&schema, &input).is_ok());
+        let params: ReadTerminalOutputParams = serde_json::from_value(input).unwrap();
+        assert_eq!(params.lines_of_output, 5);
+    }
+
+    #[test]
+    fn validation_reports_each_field() {
+        let schema = schema_for::<ReadTerminalOutputParams>();
+        let errors = validate(&schema, &json!({ "lines_of_output": 5 })).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![FieldError::new("linesOfOutput", "campo obrigatório ausente")]
+        );
+
+        let errors = validate(&schema, &json!({ "linesOfOutput": -1 })).unwrap_err();
+        assert_eq!(errors[0].field, "linesOfOutput");
+        assert!(errors[0].message.contains(">= 0"), "{:?}", errors);
+
+        let schema = schema_for::<WriteToTerminalParams>();
+        let errors = validate(&schema, &json!({ "command": 42 })).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![FieldError::new("command", "esperado string, recebido integer")]
+        );
+
+        let errors = validate(&schema, &json!("ls")).unwrap_err();
+        assert_eq!(errors[0].field, "");
+    }
+
+    #[test]
+    fn nested_items_and_enums_are_checked() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "keys": { "type": "array", "items": { "type": "string", "enum": ["a", "b"] } },
+                "label": { "type": ["string", "null"], "maxLength": 3 }
+            },
+            "additionalProperties": false
+        });
+
+        assert!(validate(&schema, &json!({ "keys": ["a"], "label": null })).is_ok());
+
+        let errors = validate(&schema, &json!({ "keys": ["a", "c"], "label": "longo", "x": 1 })).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["keys[1]", "label", "x"]);
+    }
+
+    #[test]
+    fn integer_formats_bound_the_value() {
+        let schema = schema_for::<ReadTerminalOutputParams>();
+        assert_eq!(schema["properties"]["linesOfOutput"]["format"], "uint32");
+
+        assert!(validate(&schema, &json!({ "linesOfOutput": u32::MAX })).is_ok());
+        let errors = validate(&schema, &json!({ "linesOfOutput": u32::MAX as u64 + 1 })).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![FieldError::new("linesOfOutput", "valor deve ser <= 4294967295")]
+        );
+
+        // O formato não duplica o `minimum` já declarado
+        let errors = validate(&schema, &json!({ "linesOfOutput": -1 })).unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+    }
+
+    #[test]
+    fn references_and_alternatives_are_followed() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "direction": { "$ref": "#/$defs/Direction" },
+                "size": { "anyOf": [{ "$ref": "#/definitions/Size" }, { "type": "null" }] },
+                "mode": { "oneOf": [{ "const": "a" }, { "const": "b" }] }
+            },
+            "$defs": { "Direction": { "type": "string", "enum": ["up", "down"] } },
+            "definitions": { "Size": { "type": "integer", "format": "uint16" } }
+        });
+
+        assert!(validate(&schema, &json!({ "direction": "up", "size": null, "mode": "b" })).is_ok());
+        assert!(validate(&schema, &json!({ "size": 80 })).is_ok());
+
+        let errors = validate(&schema, &json!({ "direction": "left", "size": 70000, "mode": "c" })).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["direction", "mode", "size"]);
+    }
+
+    #[test]
+    fn unknown_keywords_and_references_fail_closed() {
+        let schema = json!({ "type": "string", "pattern": "^a" });
+        let errors = validate(&schema, &json!("b")).unwrap_err();
+        assert!(errors[0].message.contains("pattern"), "{:?}", errors);
+
+        let schema = json!({ "properties": { "x": { "$ref": "#/$defs/Missing" } } });
+        let errors = validate(&schema, &json!({ "x": 1 })).unwrap_err();
+        assert_eq!(errors[0].field, "x");
+        assert!(errors[0].message.contains("não resolvida"), "{:?}", errors);
+
+        let schema = json!({ "$ref": "#" });
+        let errors = validate(&schema, &json!(1)).unwrap_err();
+        assert!(errors[0].message.contains("recursivo"), "{:?}", errors);
+    }
+}
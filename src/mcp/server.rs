@@ -1,17 +1,29 @@
+use std::fmt;
+use std::fs;
+use std::io;
 use std::net::SocketAddr;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
 use anyhow::{Context, Result};
-use tokio::net::TcpListener;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
 use tokio::sync::{broadcast, oneshot, Mutex};
 use tokio::time::{timeout, interval};
 use tracing::{error, info, warn, debug};
 
-use crate::mcp::router::Router;
+use crate::mcp::backend::pty::PtyConfig;
+use crate::mcp::backend::{
+    detect_backend, ITermApiBackend, ITermBackend, KittyBackend, PtyBackend, ScreenBackend, TerminalBackend, TmuxBackend, WeztermBackend,
+};
+use crate::mcp::http::{HttpLimits, HttpTransport, MCP_ENDPOINT};
+use crate::mcp::router::{ConnectionCounters, Router};
 use crate::mcp::tools::register_tools;
-use crate::mcp::utilities::check_iterm_availability;
+use crate::mcp::types::{Backend, Framing, ServerConfig, TerminalBackendInfo, Transport};
+use crate::mcp::utilities::{check_iterm_availability, current_uid};
 
 /// Estatísticas do servidor
 #[derive(Debug, Clone)]
@@ -26,12 +38,41 @@ pub struct ServerStats {
     pub total_errors: usize,
 }
 
+/// Endereço em que o servidor aceita conexões
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Endereço TCP
+    Tcp(SocketAddr),
+    /// Caminho de um socket Unix
+    Unix(PathBuf),
+    /// Endereço do endpoint HTTP
+    Http(SocketAddr),
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(addr) => write!(f, "{}", addr),
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+            Endpoint::Http(addr) => write!(f, "http://{}{}", addr, MCP_ENDPOINT),
+        }
+    }
+}
+
 /// Servidor MCP para iTerm com gerenciamento robusto
 pub struct McpServer {
     /// Endereço do servidor
-    address: SocketAddr,
+    endpoint: Endpoint,
     /// Router para processar mensagens
     router: Arc<Router>,
+    /// Exige que o iTerm2 esteja em execução para iniciar
+    require_iterm: bool,
+    /// Recusa conexões Unix de usuários diferentes do dono do servidor
+    require_same_uid: bool,
+    /// Enquadramento das mensagens nos transportes de stream
+    framing: Framing,
+    /// Tamanho máximo de uma mensagem em bytes
+    max_message_size: usize,
     /// Contador de conexões ativas
     active_connections: Arc<AtomicUsize>,
     /// Contador total de conexões
@@ -47,24 +88,105 @@ pub struct McpServer {
 impl McpServer {
     /// Cria um novo servidor MCP
     pub fn new(address: String, port: u16) -> Result<Self> {
-        let addr: SocketAddr = format!("{}:{}", address, port)
-            .parse()
-            .context("Falha ao analisar o endereço de socket")?;
+        Self::with_config(ServerConfig {
+            address,
+            port,
+            ..ServerConfig::default()
+        })
+    }
 
-        // Registra as ferramentas
-        let tools = register_tools();
+    /// Cria um novo servidor MCP a partir de uma configuração completa
+    ///
+    /// Com `Backend::Auto`, o backend é detectado pelo ambiente em que o
+    /// servidor foi iniciado (veja [`detect_backend`]).
+    pub fn with_config(config: ServerConfig) -> Result<Self> {
+        let kind = match config.backend {
+            Backend::Auto => {
+                let detected = detect_backend(
+                    |name| std::env::var(name).ok(),
+                    || cfg!(target_os = "macos") && check_iterm_availability(),
+                );
+                info!("Backend de terminal detectado: {:?}", detected);
+                detected
+            }
+            kind => kind,
+        };
+
+        // Registra as ferramentas sobre o backend escolhido
+        let backend: Arc<dyn TerminalBackend> = match kind {
+            Backend::Auto => unreachable!("o backend automático é resolvido acima"),
+            Backend::ITerm2 => match &config.record_applescript {
+                Some(path) => {
+                    info!("Gravando os scripts AppleScript em {}", path.display());
+                    Arc::new(ITermBackend::recording(path.clone()))
+                }
+                None => Arc::new(ITermBackend::new()),
+            },
+            Backend::ITerm2Api => Arc::new(ITermApiBackend::with_config(config.iterm2_api.clone())),
+            Backend::Pty => Arc::new(PtyBackend::new()),
+            Backend::Vt => Arc::new(PtyBackend::with_config(PtyConfig::vt())),
+            Backend::Tmux => Arc::new(TmuxBackend::with_config(config.tmux.clone())),
+            Backend::Screen => Arc::new(ScreenBackend::with_config(config.screen.clone())),
+            Backend::Wezterm => Arc::new(WeztermBackend::with_config(config.wezterm.clone())),
+            Backend::Kitty => Arc::new(KittyBackend::with_config(config.kitty.clone())),
+        };
+        let require_iterm = kind == Backend::ITerm2;
+        let backend_info = TerminalBackendInfo {
+            name: backend.name().to_string(),
+            capabilities: backend.capabilities(),
+        };
+        let tools = register_tools(backend);
         info!("Ferramentas registradas: {}", tools.len());
 
+        if config.legacy_protocol {
+            warn!("Envelope legado {{id, function, arguments}} habilitado; clientes JSON-RPC não serão atendidos");
+        }
+
         // Cria o roteador MCP e registra as ferramentas
-        let router = Arc::new(Router::new());
-        for (name, (definition, handler)) in tools {
+        let router = Arc::new(
+            Router::new()
+                .with_legacy_envelope(config.legacy_protocol)
+                .with_backend_info(backend_info),
+        );
+        for (name, tool) in tools {
             info!("Registrando ferramenta: {}", name);
-            router.register_tool(name, definition, handler);
+            router.register(tool);
         }
 
+        let mut server = Self::with_router(config, router)?;
+        server.require_iterm = require_iterm;
+        Ok(server)
+    }
+
+    /// Cria um servidor MCP que atende um router já configurado
+    ///
+    /// Não registra as ferramentas do iTerm nem exige que o iTerm2 esteja em
+    /// execução, o que permite servir conjuntos de ferramentas próprios (ex.: testes).
+    pub fn with_router(config: ServerConfig, router: Arc<Router>) -> Result<Self> {
+        let address = || -> Result<SocketAddr> {
+            format!("{}:{}", config.address, config.port)
+                .parse()
+                .context("Falha ao analisar o endereço de socket")
+        };
+
+        let endpoint = match config.transport {
+            Transport::Unix => Endpoint::Unix(
+                config
+                    .unix_socket
+                    .clone()
+                    .context("Transporte Unix requer o caminho do socket")?,
+            ),
+            Transport::Http => Endpoint::Http(address()?),
+            Transport::Tcp | Transport::Stdio => Endpoint::Tcp(address()?),
+        };
+
         Ok(McpServer {
-            address: addr,
+            endpoint,
             router,
+            require_iterm: false,
+            require_same_uid: config.require_same_uid,
+            framing: config.framing,
+            max_message_size: config.max_message_size,
             active_connections: Arc::new(AtomicUsize::new(0)),
             total_connections: Arc::new(AtomicUsize::new(0)),
             total_messages: Arc::new(AtomicUsize::new(0)),
@@ -86,19 +208,26 @@ impl McpServer {
     /// Inicia o servidor e retorna um handle para shutdown
     pub async fn start(mut self) -> Result<ServerHandle> {
         // Verifica se o iTerm2 está disponível
-        if !check_iterm_availability() {
-            error!("iTerm2 não está em execução. Certifique-se de que o aplicativo está aberto.");
-            return Err(anyhow::anyhow!("iTerm2 não está em execução"));
+        if self.require_iterm {
+            if !check_iterm_availability() {
+                error!("iTerm2 não está em execução. Certifique-se de que o aplicativo está aberto.");
+                return Err(anyhow::anyhow!("iTerm2 não está em execução"));
+            }
+
+            info!("iTerm2 detectado e disponível");
         }
 
-        info!("iTerm2 detectado e disponível");
+        if let Endpoint::Http(addr) = self.endpoint {
+            return self.start_http(addr).await;
+        }
 
-        // Cria o listener TCP
-        let listener = TcpListener::bind(&self.address)
-            .await
-            .context("Falha ao vincular o servidor ao endereço")?;
+        // Cria o listener (TCP ou socket Unix)
+        let listener = Listener::bind(&self.endpoint).await?;
+        self.endpoint = listener.local_endpoint()?;
+
+        info!("Servidor vinculado ao endereço {}", self.endpoint);
 
-        info!("Servidor vinculado ao endereço {}", self.address);
+        let server_uid = current_uid();
 
         // Canal para shutdown
         let (shutdown_tx, _) = broadcast::channel(1);
@@ -109,15 +238,9 @@ impl McpServer {
 
         // Cria handle para o servidor
         let handle = ServerHandle {
-            address: self.address,
+            endpoint: self.endpoint.clone(),
             shutdown_tx: shutdown_tx.clone(),
             stopped_rx,
-            stats: ServerStats {
-                total_connections: 0,
-                active_connections: 0,
-                total_messages: 0,
-                total_errors: 0,
-            },
             active_connections: self.active_connections.clone(),
             total_connections: self.total_connections.clone(),
             total_messages: self.total_messages.clone(),
@@ -126,6 +249,8 @@ impl McpServer {
 
         // Clona as referências necessárias para a task
         let router = self.router.clone();
+        let framing = self.framing;
+        let max_message_size = self.max_message_size;
         let active_connections = self.active_connections.clone();
         let total_connections = self.total_connections.clone();
         let total_messages = self.total_messages.clone();
@@ -134,7 +259,7 @@ impl McpServer {
 
         // Spawn da task principal do servidor
         tokio::spawn(async move {
-            info!("Servidor MCP do iTerm iniciado em {}", self.address);
+            info!("Servidor MCP do iTerm iniciado em {}", self.endpoint);
 
             // Loop principal do servidor
             loop {
@@ -142,8 +267,16 @@ impl McpServer {
                     // Aceita novas conexões
                     accept_result = listener.accept() => {
                         match accept_result {
-                            Ok((socket, addr)) => {
+                            Ok((socket, peer)) => {
+                                let addr = peer.description;
                                 info!("Nova conexão de {}", addr);
+
+                                // Sockets Unix podem exigir que o cliente seja o mesmo usuário do servidor
+                                if self.require_same_uid && peer.uid.is_some_and(|uid| uid != server_uid) {
+                                    warn!("Conexão de {} recusada: uid diferente do servidor ({})", addr, server_uid);
+                                    total_errors.fetch_add(1, Ordering::Relaxed);
+                                    continue;
+                                }
                                 
                                 // Incrementa contadores
                                 total_connections.fetch_add(1, Ordering::Relaxed);
@@ -161,15 +294,19 @@ impl McpServer {
                                     // Configura timeout para a conexão (30 minutos)
                                     let connection_timeout = Duration::from_secs(1800);
                                     
-                                    let result = tokio::select! {
+                                    tokio::select! {
                                         // Processa a conexão com timeout
                                         result = timeout(connection_timeout, 
                                             Self::handle_connection_with_stats(
-                                                router_clone, 
-                                                socket, 
-                                                addr,
-                                                total_messages_clone.clone(),
-                                                total_errors_clone.clone()
+                                                RouterWrapper {
+                                                    router: router_clone,
+                                                    framing,
+                                                    max_message_size,
+                                                    total_messages: total_messages_clone.clone(),
+                                                    total_errors: total_errors_clone.clone(),
+                                                },
+                                                socket,
+                                                addr.clone(),
                                             )
                                         ) => {
                                             match result {
@@ -231,6 +368,70 @@ impl McpServer {
                 );
             }
 
+            listener.cleanup();
+
+            info!("Servidor MCP parado");
+            let _ = stopped_tx.send(());
+        });
+
+        Ok(handle)
+    }
+
+    /// Inicia o transporte HTTP (POST + SSE) no endereço informado
+    async fn start_http(self, addr: SocketAddr) -> Result<ServerHandle> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .context("Falha ao vincular o servidor HTTP ao endereço")?;
+        let endpoint = Endpoint::Http(listener.local_addr().context("Falha ao obter o endereço local")?);
+
+        info!("Servidor HTTP vinculado a {}", endpoint);
+
+        let transport = HttpTransport::with_counters(
+            self.router.clone(),
+            HttpLimits {
+                max_message_size: self.max_message_size,
+                ..HttpLimits::default()
+            },
+            self.active_connections.clone(),
+            self.total_connections.clone(),
+            self.total_messages.clone(),
+            self.total_errors.clone(),
+        );
+
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let (stopped_tx, stopped_rx) = oneshot::channel();
+
+        let handle = ServerHandle {
+            endpoint: endpoint.clone(),
+            shutdown_tx: shutdown_tx.clone(),
+            stopped_rx,
+            active_connections: self.active_connections.clone(),
+            total_connections: self.total_connections.clone(),
+            total_messages: self.total_messages.clone(),
+            total_errors: self.total_errors.clone(),
+        };
+
+        let mut shutdown_rx = shutdown_tx.subscribe();
+        let app = transport.app();
+        let total_errors = self.total_errors.clone();
+
+        tokio::spawn(async move {
+            info!("Servidor MCP do iTerm iniciado em {}", endpoint);
+
+            let result = axum::serve(listener, app)
+                .with_graceful_shutdown(async move {
+                    let _ = shutdown_rx.recv().await;
+                    info!("Recebido sinal de shutdown, parando servidor HTTP...");
+                    // Streams SSE abertos impediriam o shutdown ordenado
+                    transport.close_streams();
+                })
+                .await;
+
+            if let Err(e) = result {
+                error!("Erro no servidor HTTP: {}", e);
+                total_errors.fetch_add(1, Ordering::Relaxed);
+            }
+
             info!("Servidor MCP parado");
             let _ = stopped_tx.send(());
         });
@@ -240,143 +441,237 @@ impl McpServer {
 
     /// Processa uma conexão e atualiza estatísticas
     async fn handle_connection_with_stats(
-        router: Arc<Router>,
-        socket: tokio::net::TcpStream,
-        addr: SocketAddr,
-        total_messages: Arc<AtomicUsize>,
-        total_errors: Arc<AtomicUsize>,
+        router_wrapper: RouterWrapper,
+        socket: Box<dyn Connection>,
+        addr: String,
     ) -> Result<()> {
-        // Cria um wrapper que conta mensagens
+        debug!("Iniciando processamento da conexão de {}", addr);
+
+        router_wrapper.handle_connection(socket, &addr).await
+    }
+
+    /// Atende uma única sessão MCP em stdin/stdout até o EOF
+    ///
+    /// Usado quando o servidor é iniciado como subprocesso pelo host MCP. Toda a
+    /// saída de log deve ir para stderr, já que stdout carrega apenas respostas.
+    pub async fn run_stdio(self) -> Result<ServerStats> {
+        // O host espera resposta ao initialize mesmo sem iTerm2; as ferramentas
+        // reportam o erro quando forem chamadas
+        if self.require_iterm && !check_iterm_availability() {
+            warn!("iTerm2 não está em execução; as ferramentas falharão até que o aplicativo seja aberto");
+        }
+
+        info!("Servidor MCP do iTerm atendendo via stdio");
+
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+
         let router_wrapper = RouterWrapper {
-            router,
-            total_messages,
-            total_errors,
+            router: self.router.clone(),
+            framing: self.framing,
+            max_message_size: self.max_message_size,
+            total_messages: self.total_messages.clone(),
+            total_errors: self.total_errors.clone(),
         };
 
-        router_wrapper.handle_connection(socket).await
+        let stdio = tokio::io::join(tokio::io::stdin(), tokio::io::stdout());
+        let result = router_wrapper.handle_connection(stdio, "stdio").await;
+
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+        result?;
+
+        info!("EOF em stdin, encerrando sessão stdio");
+        Ok(self.get_stats())
+    }
+}
+
+/// Stream de uma conexão aceita, independente do transporte
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+/// Informações do cliente de uma conexão aceita
+struct PeerInfo {
+    /// Descrição usada nos logs
+    description: String,
+    /// UID do processo cliente (apenas sockets Unix)
+    uid: Option<u32>,
+}
+
+/// Listener dos transportes orientados a conexão
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    /// Cria o listener para o endpoint configurado
+    async fn bind(endpoint: &Endpoint) -> Result<Self> {
+        match endpoint {
+            Endpoint::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)
+                    .await
+                    .context("Falha ao vincular o servidor ao endereço")?;
+                Ok(Listener::Tcp(listener))
+            }
+            Endpoint::Unix(path) => Ok(Listener::Unix(bind_unix_socket(path)?, path.clone())),
+            Endpoint::Http(_) => Err(anyhow::anyhow!("O endpoint HTTP não usa o listener de conexões")),
+        }
+    }
+
+    /// Endpoint efetivo (resolve a porta quando o bind foi feito na porta 0)
+    fn local_endpoint(&self) -> Result<Endpoint> {
+        match self {
+            Listener::Tcp(listener) => Ok(Endpoint::Tcp(
+                listener.local_addr().context("Falha ao obter o endereço local")?,
+            )),
+            Listener::Unix(_, path) => Ok(Endpoint::Unix(path.clone())),
+        }
+    }
+
+    /// Aceita a próxima conexão
+    async fn accept(&self) -> io::Result<(Box<dyn Connection>, PeerInfo)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (socket, addr) = listener.accept().await?;
+                let peer = PeerInfo {
+                    description: addr.to_string(),
+                    uid: None,
+                };
+                Ok((Box::new(socket), peer))
+            }
+            Listener::Unix(listener, path) => {
+                let (socket, _) = listener.accept().await?;
+                let cred = socket.peer_cred()?;
+                let pid = cred
+                    .pid()
+                    .map(|pid| pid.to_string())
+                    .unwrap_or_else(|| "?".to_string());
+                let peer = PeerInfo {
+                    description: format!("{} (uid={}, pid={})", path.display(), cred.uid(), pid),
+                    uid: Some(cred.uid()),
+                };
+                Ok((Box::new(socket), peer))
+            }
+        }
+    }
+
+    /// Libera recursos do listener (remove o arquivo do socket Unix)
+    fn cleanup(&self) {
+        if let Listener::Unix(_, path) = self {
+            match fs::remove_file(path) {
+                Ok(()) => info!("Socket {} removido", path.display()),
+                Err(e) => warn!("Falha ao remover o socket {}: {}", path.display(), e),
+            }
+        }
     }
 }
 
+/// Cria um socket Unix acessível apenas pelo dono (0600)
+///
+/// O socket é vinculado dentro de um diretório temporário privado (0700) ao
+/// lado do destino e só então movido para o caminho final, já com 0600: ele
+/// nunca fica acessível com permissões abertas, sem mexer na umask do
+/// processo (compartilhada por todas as threads do runtime).
+fn bind_unix_socket(path: &Path) -> Result<UnixListener> {
+    remove_stale_socket(path)?;
+
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // Nomes curtos: o caminho do socket tem limite de ~104 bytes
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    let private_dir = parent.join(format!(".rs_iterm-{}", &nonce[..12]));
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)
+        .with_context(|| format!("Falha ao criar o diretório temporário {}", private_dir.display()))?;
+
+    let staged = private_dir.join("s");
+    let result = UnixListener::bind(&staged)
+        .with_context(|| format!("Falha ao vincular o socket Unix {}", path.display()))
+        .and_then(|listener| {
+            fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Falha ao ajustar permissões de {}", path.display()))?;
+            fs::rename(&staged, path)
+                .with_context(|| format!("Falha ao mover o socket para {}", path.display()))?;
+            Ok(listener)
+        });
+
+    let _ = fs::remove_file(&staged);
+    if let Err(e) = fs::remove_dir(&private_dir) {
+        warn!("Falha ao remover o diretório temporário {}: {}", private_dir.display(), e);
+    }
+    result
+}
+
+/// Remove um socket deixado por uma execução anterior que não encerrou corretamente
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Falha ao inspecionar {}", path.display()))
+        }
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(anyhow::anyhow!(
+            "{} existe e não é um socket; recusando sobrescrever",
+            path.display()
+        ));
+    }
+
+    // Se alguém aceita conexões no socket, ele não está obsoleto
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(anyhow::anyhow!(
+            "O socket {} já está em uso por outro servidor",
+            path.display()
+        ));
+    }
+
+    warn!("Removendo socket obsoleto {}", path.display());
+    fs::remove_file(path)
+        .with_context(|| format!("Falha ao remover o socket obsoleto {}", path.display()))
+}
+
 /// Wrapper do Router para contar estatísticas
 struct RouterWrapper {
     router: Arc<Router>,
+    framing: Framing,
+    max_message_size: usize,
     total_messages: Arc<AtomicUsize>,
     total_errors: Arc<AtomicUsize>,
 }
 
 impl RouterWrapper {
-    async fn handle_connection(&self, mut socket: tokio::net::TcpStream) -> Result<()> {
-        use tokio::io::{AsyncReadExt, AsyncWriteExt};
-        
-        let addr = socket.peer_addr().unwrap_or_else(|_| "[unknown]".parse().unwrap());
+    async fn handle_connection<S>(&self, socket: S, addr: &str) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         debug!("RouterWrapper processando conexão de {}", addr);
 
-        // Buffer para leitura dos dados
-        let mut buffer = vec![0u8; 8192];
-        let mut read_pos = 0;
-
-        loop {
-            match socket.read(&mut buffer[read_pos..]).await {
-                Ok(0) => {
-                    debug!("Conexão fechada pelo cliente: {}", addr);
-                    break;
-                }
-                Ok(n) => {
-                    read_pos += n;
-                    debug!("Lidos {} bytes, total de {} bytes no buffer", n, read_pos);
-
-                    // Processa mensagens completas no buffer
-                    let mut processed_pos = 0;
-                    while processed_pos < read_pos {
-                        // Tenta encontrar um JSON válido terminado por newline
-                        if let Some(msg_end) = buffer[processed_pos..read_pos]
-                            .iter()
-                            .position(|&b| b == b'\n')
-                        {
-                            let msg_end = processed_pos + msg_end;
-                            
-                            // Extrai a mensagem JSON
-                            let message = match std::str::from_utf8(&buffer[processed_pos..msg_end]) {
-                                Ok(msg) => msg,
-                                Err(e) => {
-                                    error!("Falha ao converter bytes para UTF-8: {}", e);
-                                    self.total_errors.fetch_add(1, Ordering::Relaxed);
-                                    processed_pos = msg_end + 1;
-                                    continue;
-                                }
-                            };
-                            
-                            // Incrementa contador de mensagens
-                            self.total_messages.fetch_add(1, Ordering::Relaxed);
-                            
-                            // Processa a mensagem
-                            let response = self.router.process_message(message).await;
-                            
-                            // Envia a resposta
-                            if let Some(response_str) = response {
-                                if let Err(e) = socket.write_all(response_str.as_bytes()).await {
-                                    error!("Falha ao enviar resposta: {}", e);
-                                    self.total_errors.fetch_add(1, Ordering::Relaxed);
-                                    return Err(e.into());
-                                }
-                                
-                                if let Err(e) = socket.write_all(b"\n").await {
-                                    error!("Falha ao enviar newline: {}", e);
-                                    self.total_errors.fetch_add(1, Ordering::Relaxed);
-                                    return Err(e.into());
-                                }
-                                
-                                if let Err(e) = socket.flush().await {
-                                    error!("Falha ao flush do socket: {}", e);
-                                    self.total_errors.fetch_add(1, Ordering::Relaxed);
-                                    return Err(e.into());
-                                }
-                            }
-                            
-                            // Atualiza posição processada
-                            processed_pos = msg_end + 1;
-                        } else {
-                            // Mensagem incompleta, precisamos ler mais dados
-                            break;
-                        }
-                    }
-                    
-                    // Move dados não processados para o início do buffer
-                    if processed_pos > 0 {
-                        buffer.copy_within(processed_pos..read_pos, 0);
-                        read_pos -= processed_pos;
-                    }
-                    
-                    // Verifica se o buffer está cheio e precisa ser expandido
-                    if read_pos == buffer.len() {
-                        // Aumenta o buffer em 50%
-                        let new_size = (buffer.len() * 3) / 2;
-                        buffer.resize(new_size, 0);
-                        debug!("Buffer expandido para {} bytes", new_size);
-                    }
-                }
-                Err(e) => {
-                    error!("Erro ao ler do socket: {}", e);
-                    self.total_errors.fetch_add(1, Ordering::Relaxed);
-                    return Err(anyhow::anyhow!("Erro de leitura: {}", e));
-                }
-            }
-        }
+        let counters = ConnectionCounters {
+            messages: self.total_messages.clone(),
+            errors: self.total_errors.clone(),
+        };
 
-        Ok(())
+        self.router
+            .clone()
+            .serve_connection(socket, self.framing, self.max_message_size, counters)
+            .await
     }
 }
 
 /// Handle para controlar o servidor após iniciado
 pub struct ServerHandle {
     /// Endereço onde o servidor está rodando
-    pub address: SocketAddr,
+    pub endpoint: Endpoint,
     /// Canal para enviar sinal de shutdown
     shutdown_tx: broadcast::Sender<()>,
     /// Canal para aguardar o servidor parar
     stopped_rx: oneshot::Receiver<()>,
-    /// Estatísticas do servidor
-    stats: ServerStats,
     /// Referências aos contadores atômicos
     active_connections: Arc<AtomicUsize>,
     total_connections: Arc<AtomicUsize>,
@@ -447,8 +742,27 @@ pub enum HealthStatus {
 
 /// Inicia o servidor MCP para o iTerm (função de compatibilidade)
 pub async fn start_server(address: String, port: u16) -> Result<oneshot::Receiver<()>> {
-    let server = McpServer::new(address, port)?;
+    start_server_with_config(ServerConfig {
+        address,
+        port,
+        ..ServerConfig::default()
+    })
+    .await
+}
+
+/// Inicia o servidor MCP para o iTerm a partir de uma configuração completa
+pub async fn start_server_with_config(config: ServerConfig) -> Result<oneshot::Receiver<()>> {
+    let server = McpServer::with_config(config)?;
     let handle = server.start().await?;
+
+    // Ctrl-C encerra o servidor de forma ordenada (e remove o socket Unix)
+    let ctrl_c_tx = handle.shutdown_tx.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("Ctrl-C recebido, encerrando servidor");
+            let _ = ctrl_c_tx.send(());
+        }
+    });
     
     // Spawn de uma task para monitorar a saúde periodicamente
     let handle_clone = Arc::new(Mutex::new(Some(handle)));
@@ -505,6 +819,34 @@ pub async fn start_server(address: String, port: u16) -> Result<oneshot::Receive
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    use crate::mcp::types::ToolDefinition;
+
+    // Router com uma única ferramenta de eco
+    fn echo_router() -> Arc<Router> {
+        let router = Router::new();
+        router.register_tool(
+            "test:echo".to_string(),
+            ToolDefinition {
+                name: "test:echo".to_string(),
+                description: "Ferramenta de eco para testes".to_string(),
+                parameters: Default::default(),
+            },
+            Arc::new(|_ctx, params| Ok(json!({ "received": params }))),
+        );
+        Arc::new(router)
+    }
+
+    fn unix_config(path: &Path) -> ServerConfig {
+        ServerConfig {
+            transport: Transport::Unix,
+            unix_socket: Some(path.to_path_buf()),
+            require_same_uid: true,
+            ..ServerConfig::default()
+        }
+    }
 
     #[tokio::test]
     async fn test_server_stats() {
@@ -519,7 +861,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_health_status() {
-        let server = McpServer::new("127.0.0.1".to_string(), 0).unwrap();
+        let _server = McpServer::new("127.0.0.1".to_string(), 0).unwrap();
         
         // Simula diferentes cenários
         let mut stats = ServerStats {
@@ -548,4 +890,283 @@ mod tests {
         
         matches!(health, HealthStatus::Unhealthy { .. });
     }
+
+    #[tokio::test]
+    async fn test_unix_socket_transport() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcp.sock");
+
+        // Simula um socket deixado por uma execução anterior
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let server = McpServer::with_router(unix_config(&path), echo_router()).unwrap();
+        let handle = server.start().await.unwrap();
+        assert_eq!(handle.endpoint, Endpoint::Unix(path.clone()));
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+        // O diretório privado usado no bind não fica para trás
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\"params\":{\"name\":\"test:echo\",\"arguments\":{\"a\":1}}}\n")
+            .await
+            .unwrap();
+
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await.unwrap();
+        let response: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["isError"], false);
+        drop(writer);
+
+        handle.shutdown().unwrap();
+        handle.wait_for_shutdown().await.unwrap();
+        assert!(!path.exists(), "o socket deve ser removido no shutdown");
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_refuses_to_replace_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not-a-socket");
+        fs::write(&path, "dados").unwrap();
+
+        let server = McpServer::with_router(unix_config(&path), echo_router()).unwrap();
+        assert!(server.start().await.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "dados");
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_in_use_is_not_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcp.sock");
+
+        let first = McpServer::with_router(unix_config(&path), echo_router())
+            .unwrap()
+            .start()
+            .await
+            .unwrap();
+
+        let second = McpServer::with_router(unix_config(&path), echo_router()).unwrap();
+        let err = second.start().await.err().expect("o segundo servidor deve falhar");
+        assert!(err.to_string().contains("em uso"), "erro inesperado: {}", err);
+
+        first.shutdown().unwrap();
+        first.wait_for_shutdown().await.unwrap();
+    }
+
+    // Wrapper com limites pequenos para exercitar o enquadramento
+    fn test_wrapper(framing: Framing, max_message_size: usize) -> RouterWrapper {
+        RouterWrapper {
+            router: echo_router(),
+            framing,
+            max_message_size,
+            total_messages: Arc::new(AtomicUsize::new(0)),
+            total_errors: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_content_length_connection_survives_bad_frames() {
+        let wrapper = test_wrapper(Framing::Auto, 256);
+        let (client, server) = tokio::io::duplex(64 * 1024);
+
+        let ping = "{\n  \"jsonrpc\": \"2.0\",\n  \"id\": 1,\n  \"method\": \"ping\"\n}";
+        let oversized = format!("{{\"pad\":\"{}\"}}", "x".repeat(512));
+        let mut input = Vec::new();
+        input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", ping.len(), ping).as_bytes());
+        input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", oversized.len(), oversized).as_bytes());
+        input.extend_from_slice(b"Content-Length: 4\r\n\r\n\"\xff\xfe\"");
+        let last = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+        input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", last.len(), last).as_bytes());
+
+        let (mut client_reader, mut client_writer) = tokio::io::split(client);
+        client_writer.write_all(&input).await.unwrap();
+        client_writer.shutdown().await.unwrap();
+
+        wrapper.handle_connection(server, "teste").await.unwrap();
+
+        let mut output = String::new();
+        client_reader.read_to_string(&mut output).await.unwrap();
+
+        // Cada resposta usa o mesmo enquadramento Content-Length
+        let bodies: Vec<serde_json::Value> = output
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|frame| {
+                let (length, body) = frame.split_once("\r\n\r\n").unwrap();
+                assert_eq!(length.parse::<usize>().unwrap(), body.len());
+                serde_json::from_str(body).unwrap()
+            })
+            .collect();
+
+        // As mensagens são processadas concorrentemente; a ordem das respostas pode variar
+        assert_eq!(bodies.len(), 4, "saída: {}", output);
+        assert!(bodies.iter().any(|body| body["id"] == 1 && body["result"].is_object()));
+        assert!(bodies.iter().any(|body| body["id"] == 2 && body["result"].is_object()));
+
+        let errors: Vec<&serde_json::Value> =
+            bodies.iter().filter(|body| body["id"].is_null()).collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|body| body["error"]["code"] == -32700));
+        assert!(errors.iter().any(|body| body["error"]["data"]["limit"] == 256));
+
+        assert_eq!(wrapper.total_messages.load(Ordering::Relaxed), 4);
+        assert_eq!(wrapper.total_errors.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_newline_connection_reports_oversized_lines() {
+        let wrapper = test_wrapper(Framing::Newline, 64);
+        let (client, server) = tokio::io::duplex(64 * 1024);
+
+        let (client_reader, mut client_writer) = tokio::io::split(client);
+        let input = format!(
+            "{}\n{}\n",
+            "y".repeat(1024),
+            r#"{"jsonrpc":"2.0","id":7,"method":"ping"}"#
+        );
+        client_writer.write_all(input.as_bytes()).await.unwrap();
+        client_writer.shutdown().await.unwrap();
+
+        wrapper.handle_connection(server, "teste").await.unwrap();
+
+        let mut lines = BufReader::new(client_reader).lines();
+        let first: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let second: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+
+        assert_eq!(first["error"]["code"], -32700);
+        assert_eq!(second["id"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_connection_cancellation_suppresses_response() {
+        let router = echo_router();
+        let cancelled = Arc::new(AtomicUsize::new(0));
+        let observed = cancelled.clone();
+        router.register_tool(
+            "test:wait".to_string(),
+            ToolDefinition {
+                name: "test:wait".to_string(),
+                description: "Aguarda o cancelamento".to_string(),
+                parameters: Default::default(),
+            },
+            Arc::new(move |ctx, _| {
+                while !ctx.is_cancelled() {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                observed.fetch_add(1, Ordering::SeqCst);
+                Ok(json!("tarde demais"))
+            }),
+        );
+
+        let wrapper = RouterWrapper {
+            router,
+            framing: Framing::Newline,
+            max_message_size: 1024,
+            total_messages: Arc::new(AtomicUsize::new(0)),
+            total_errors: Arc::new(AtomicUsize::new(0)),
+        };
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let connection = tokio::spawn(async move { wrapper.handle_connection(server, "teste").await });
+
+        let (client_reader, mut client_writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(client_reader).lines();
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\"params\":{\"name\":\"test:wait\"}}\n")
+            .await
+            .unwrap();
+
+        // A conexão continua atendendo enquanto a ferramenta executa
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"ping\"}\n")
+            .await
+            .unwrap();
+        let pong: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(pong["id"], 2);
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"notifications/cancelled\",\"params\":{\"requestId\":1}}\n")
+            .await
+            .unwrap();
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"ping\"}\n")
+            .await
+            .unwrap();
+        client_writer.shutdown().await.unwrap();
+
+        // Apenas o segundo ping é respondido; a chamada cancelada não gera resposta
+        let pong: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(pong["id"], 3);
+        assert!(lines.next_line().await.unwrap().is_none());
+
+        connection.await.unwrap().unwrap();
+
+        // O handler observa o cancelamento na thread bloqueante
+        let started = std::time::Instant::now();
+        while cancelled.load(Ordering::SeqCst) == 0 {
+            assert!(started.elapsed() < Duration::from_secs(2), "o handler não foi cancelado");
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_streams_progress_before_result() {
+        let router = echo_router();
+        router.register_tool(
+            "test:progress".to_string(),
+            ToolDefinition {
+                name: "test:progress".to_string(),
+                description: "Reporta progresso".to_string(),
+                parameters: Default::default(),
+            },
+            Arc::new(|ctx, _| {
+                ctx.report_progress(0.5, None, Some("metade"));
+                Ok(json!("pronto"))
+            }),
+        );
+
+        let wrapper = RouterWrapper {
+            router,
+            framing: Framing::Newline,
+            max_message_size: 1024,
+            total_messages: Arc::new(AtomicUsize::new(0)),
+            total_errors: Arc::new(AtomicUsize::new(0)),
+        };
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let connection = tokio::spawn(async move { wrapper.handle_connection(server, "teste").await });
+
+        let (client_reader, mut client_writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(client_reader).lines();
+
+        client_writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"tools/call\",\"params\":{\"name\":\"test:progress\",\"_meta\":{\"progressToken\":42}}}\n")
+            .await
+            .unwrap();
+        client_writer.shutdown().await.unwrap();
+
+        let progress: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(progress["method"], "notifications/progress");
+        assert_eq!(progress["params"]["progressToken"], 42);
+        assert_eq!(progress["params"]["progress"], 0.5);
+        assert_eq!(progress["params"]["message"], "metade");
+        assert!(progress["params"].get("total").is_none());
+
+        let result: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(result["id"], 1);
+        assert_eq!(result["result"]["content"][0]["text"], "pronto");
+        assert!(lines.next_line().await.unwrap().is_none());
+
+        connection.await.unwrap().unwrap();
+    }
 }
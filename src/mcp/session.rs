@@ -0,0 +1,166 @@
+//! Estado de uma sessão MCP (uma conexão de stream ou uma sessão HTTP)
+//!
+//! Mantém a tabela de requisições em andamento com seus tokens de
+//! cancelamento, consultada quando o cliente envia `notifications/cancelled`,
+//! e o canal por onde o servidor envia mensagens ao cliente fora das
+//! respostas (ex.: `notifications/progress`).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error};
+
+use crate::mcp::jsonrpc::{JsonRpcNotification, RequestId};
+
+/// Canal de mensagens do servidor para o cliente
+pub type Outgoing = mpsc::UnboundedSender<String>;
+
+/// Estado compartilhado pelas requisições de uma sessão
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Requisições em andamento indexadas pelo ID
+    in_flight: Mutex<HashMap<RequestId, CancellationToken>>,
+    /// Canal de saída para o cliente (se houver um aberto)
+    outgoing: Mutex<Option<Outgoing>>,
+}
+
+impl Session {
+    /// Cria uma sessão sem requisições em andamento e sem canal de saída
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cria uma sessão que envia mensagens do servidor pelo canal informado
+    pub fn with_outgoing(outgoing: Outgoing) -> Self {
+        Session {
+            outgoing: Mutex::new(Some(outgoing)),
+            ..Self::default()
+        }
+    }
+
+    /// Substitui (ou remove) o canal de saída
+    pub fn set_outgoing(&self, outgoing: Option<Outgoing>) {
+        *self.outgoing.lock().unwrap() = outgoing;
+    }
+
+    /// Canal de saída atual, se houver
+    pub fn outgoing(&self) -> Option<Outgoing> {
+        self.outgoing.lock().unwrap().clone()
+    }
+
+    /// Envia uma mensagem ao cliente
+    ///
+    /// Retorna `false` se não há canal aberto ou se o cliente o fechou.
+    pub fn send(&self, message: String) -> bool {
+        let mut outgoing = self.outgoing.lock().unwrap();
+        match outgoing.as_ref() {
+            Some(sender) if sender.send(message).is_ok() => true,
+            Some(_) => {
+                // O cliente fechou o canal
+                *outgoing = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Envia uma notificação JSON-RPC ao cliente
+    pub fn notify(&self, method: &str, params: Value) -> bool {
+        match serde_json::to_string(&JsonRpcNotification::new(method, params)) {
+            Ok(message) => self.send(message),
+            Err(e) => {
+                error!("Erro ao serializar notificação {}: {}", method, e);
+                false
+            }
+        }
+    }
+
+    /// Registra uma requisição em andamento e retorna seu token de cancelamento
+    pub fn begin(&self, id: &RequestId) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.in_flight
+            .lock()
+            .unwrap()
+            .insert(id.clone(), token.clone());
+        token
+    }
+
+    /// Remove uma requisição concluída da tabela
+    pub fn finish(&self, id: &RequestId) {
+        self.in_flight.lock().unwrap().remove(id);
+    }
+
+    /// Cancela uma requisição em andamento, retornando se ela existia
+    pub fn cancel(&self, id: &RequestId) -> bool {
+        match self.in_flight.lock().unwrap().remove(id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => {
+                debug!("Cancelamento de requisição desconhecida ou já concluída: {}", id);
+                false
+            }
+        }
+    }
+
+    /// Cancela todas as requisições em andamento (ex.: a conexão caiu)
+    pub fn cancel_all(&self) {
+        for (_, token) in self.in_flight.lock().unwrap().drain() {
+            token.cancel();
+        }
+    }
+
+    /// Número de requisições em andamento
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn cancel_fires_token_and_forgets_request() {
+        let session = Session::new();
+        let token = session.begin(&RequestId::Number(1));
+        session.begin(&RequestId::from("b"));
+        assert_eq!(session.in_flight(), 2);
+
+        assert!(session.cancel(&RequestId::Number(1)));
+        assert!(token.is_cancelled());
+        assert!(!session.cancel(&RequestId::Number(1)));
+
+        session.finish(&RequestId::from("b"));
+        assert_eq!(session.in_flight(), 0);
+
+        let token = session.begin(&RequestId::Null);
+        session.cancel_all();
+        assert!(token.is_cancelled());
+        assert_eq!(session.in_flight(), 0);
+    }
+
+    #[test]
+    fn notify_uses_outgoing_channel_until_closed() {
+        let session = Session::new();
+        assert!(!session.notify("notifications/message", json!({})));
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        session.set_outgoing(Some(sender));
+        assert!(session.notify("notifications/message", json!({ "data": "oi" })));
+
+        let message: Value = serde_json::from_str(&receiver.try_recv().unwrap()).unwrap();
+        assert_eq!(message["jsonrpc"], "2.0");
+        assert_eq!(message["method"], "notifications/message");
+        assert!(message.get("id").is_none());
+
+        drop(receiver);
+        assert!(!session.send("perdida".to_string()));
+        assert!(session.outgoing().is_none());
+    }
+}
@@ -8,9 +8,11 @@
 //! These are lightweight checks to ensure the initial code shape is correct and
 //! the tool registration exposes the expected tool names and parameter keys.
 
+use std::sync::Arc;
+
 use serde_json::Value;
-use std::collections::HashMap;
 
+use crate::mcp::backend::ITermBackend;
 use crate::mcp::tools::register_tools;
 use crate::mcp::utilities::{escape_applescript_string, letter_to_control_char};
 
@@ -82,10 +84,10 @@ fn test_letter_to_control_char_invalid() {
 
 #[test]
 fn test_register_tools_contains_expected_tools_and_schemas() {
-    let tools = register_tools();
+    let tools = register_tools(Arc::new(ITermBackend::new()));
 
     // Expected tool names from the current implementation
-    let expected = vec![
+    let expected = [
         "iterm-mcp:write_to_terminal",
         "iterm-mcp:read_terminal_output",
         "iterm-mcp:send_control_character",
@@ -100,13 +102,12 @@ fn test_register_tools_contains_expected_tools_and_schemas() {
         );
 
         // Inspect the definition and ensure parameter schema has expected structure
-        let (def, _handler) = tools.get(*name).expect("tool must exist");
+        let def = tools.get(*name).expect("tool must exist").definition();
         // parameters is a HashMap<String, serde_json::Value>
         // We expect at least a "properties" entry which contains the parameter names.
-        let properties_val = def.parameters.get("properties").expect(&format!(
-            "Tool '{}' should have 'properties' in its parameters",
-            name
-        ));
+        let properties_val = def.parameters.get("properties").unwrap_or_else(|| {
+            panic!("Tool '{}' should have 'properties' in its parameters", name)
+        });
 
         // Ensure properties is an object and contains the expected parameter key per tool
         match properties_val {
@@ -137,7 +138,7 @@ fn test_register_tools_contains_expected_tools_and_schemas() {
 // Extra sanity test: ensure registered tool count is at least 3
 #[test]
 fn test_register_tools_minimum_count() {
-    let tools = register_tools();
+    let tools = register_tools(Arc::new(ITermBackend::new()));
     assert!(
         tools.len() >= 3,
         "Expected at least 3 tools to be registered, got {}",
@@ -0,0 +1,395 @@
+//! Testes do transporte HTTP (POST + SSE)
+//!
+//! Sobem o servidor em uma porta local com um conjunto de ferramentas de teste
+//! e o exercitam com um cliente HTTP real.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::mcp::http::{HttpLimits, HttpTransport, SESSION_HEADER};
+use crate::mcp::router::Router;
+use crate::mcp::tools::ToolContext;
+use crate::mcp::server::{Endpoint, McpServer};
+use crate::mcp::types::{ServerConfig, ToolDefinition, Transport};
+
+// Handler mock que apenas retorna o que recebeu
+fn echo_handler(_ctx: ToolContext, params: Value) -> Result<Value> {
+    Ok(json!({ "received": params }))
+}
+
+// Router com a ferramenta de eco registrada
+fn mock_router() -> Arc<Router> {
+    let router = Router::new();
+    router.register_tool(
+        "test:echo".to_string(),
+        ToolDefinition {
+            name: "test:echo".to_string(),
+            description: "Ferramenta de eco para testes".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(echo_handler),
+    );
+    Arc::new(router)
+}
+
+fn http_config() -> ServerConfig {
+    ServerConfig {
+        transport: Transport::Http,
+        address: "127.0.0.1".to_string(),
+        port: 0,
+        ..ServerConfig::default()
+    }
+}
+
+const INITIALIZE: &str = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"http-test","version":"0.0.1"}}}"#;
+
+// Envia um POST para o endpoint MCP, opcionalmente com a sessão
+async fn post(
+    client: &reqwest::Client,
+    url: &str,
+    session: Option<&str>,
+    body: &str,
+) -> reqwest::Response {
+    let mut request = client
+        .post(url)
+        .header("content-type", "application/json")
+        .header("accept", "application/json, text/event-stream")
+        .body(body.to_string());
+    if let Some(session) = session {
+        request = request.header(SESSION_HEADER, session);
+    }
+    request.send().await.unwrap()
+}
+
+// Inicializa uma sessão e retorna o ID recebido no cabeçalho
+async fn initialize(client: &reqwest::Client, url: &str) -> String {
+    let response = post(client, url, None, INITIALIZE).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let session = response
+        .headers()
+        .get(SESSION_HEADER)
+        .expect("initialize deve retornar Mcp-Session-Id")
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let body = json_body(response).await;
+    assert_eq!(body["result"]["serverInfo"]["name"], "rs_iterm");
+    session
+}
+
+// Decodifica o corpo da resposta como JSON
+async fn json_body(response: reqwest::Response) -> Value {
+    serde_json::from_str(&response.text().await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_http_session_lifecycle() {
+    let server = McpServer::with_router(http_config(), mock_router()).unwrap();
+    let handle = server.start().await.unwrap();
+    let url = match &handle.endpoint {
+        Endpoint::Http(addr) => format!("http://{}/mcp", addr),
+        other => panic!("endpoint inesperado: {}", other),
+    };
+    let client = reqwest::Client::new();
+
+    let session = initialize(&client, &url).await;
+
+    // Requisições sem sessão ou com sessão desconhecida são recusadas
+    let list = r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#;
+    assert_eq!(post(&client, &url, None, list).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        post(&client, &url, Some("sessao-inexistente"), list).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let response = post(&client, &url, Some(&session), list).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["result"]["tools"][0]["name"], "test:echo");
+
+    let call = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"test:echo","arguments":{"x":42}}}"#;
+    let body = json_body(post(&client, &url, Some(&session), call).await).await;
+    assert_eq!(body["id"], 3);
+    assert_eq!(body["result"]["isError"], false);
+
+    // Notificações são aceitas sem corpo de resposta
+    let notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    let response = post(&client, &url, Some(&session), notification).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+    assert_eq!(handle.get_stats().active_connections, 1);
+
+    // DELETE encerra a sessão
+    let response = client
+        .delete(&url)
+        .header(SESSION_HEADER, &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        post(&client, &url, Some(&session), list).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let stats = handle.get_stats();
+    assert_eq!(stats.total_connections, 1);
+    assert_eq!(stats.active_connections, 0);
+
+    handle.shutdown().unwrap();
+    handle.wait_for_shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_http_sse_stream_delivers_server_messages() {
+    let transport = HttpTransport::new(mock_router());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+    let app = transport.app();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let client = reqwest::Client::new();
+    let session = initialize(&client, &url).await;
+
+    let mut stream = client
+        .get(&url)
+        .header("accept", "text/event-stream")
+        .header(SESSION_HEADER, &session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(stream.status(), StatusCode::OK);
+    assert!(stream.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/event-stream"));
+
+    let notification = r#"{"jsonrpc":"2.0","method":"notifications/message","params":{"level":"info","data":"oi"}}"#;
+    assert_eq!(transport.notify_all(notification), 1);
+
+    let mut received = String::new();
+    while !received.contains("\n\n") {
+        let chunk = stream.chunk().await.unwrap().expect("stream SSE encerrado");
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    assert!(received.contains("event: message"), "recebido: {}", received);
+    assert!(received.contains(&format!("data: {}", notification)), "recebido: {}", received);
+
+    transport.close_streams();
+}
+
+#[tokio::test]
+async fn test_http_progress_goes_to_sse_stream() {
+    let router = mock_router();
+    router.register_tool(
+        "test:progress".to_string(),
+        ToolDefinition {
+            name: "test:progress".to_string(),
+            description: "Reporta progresso".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(|ctx: ToolContext, _| {
+            ctx.report_progress(1.0, Some(2.0), None);
+            Ok(json!("ok"))
+        }),
+    );
+    let transport = HttpTransport::new(router);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+    let app = transport.app();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let client = reqwest::Client::new();
+    let session = initialize(&client, &url).await;
+    let mut stream = client
+        .get(&url)
+        .header("accept", "text/event-stream")
+        .header(SESSION_HEADER, &session)
+        .send()
+        .await
+        .unwrap();
+
+    // A resposta volta no corpo do POST; o progresso segue pelo stream SSE
+    let call = r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"test:progress","_meta":{"progressToken":"p"}}}"#;
+    let body = json_body(post(&client, &url, Some(&session), call).await).await;
+    assert_eq!(body["result"]["content"][0]["text"], "ok");
+
+    let mut received = String::new();
+    while !received.contains("\n\n") {
+        let chunk = stream.chunk().await.unwrap().expect("stream SSE encerrado");
+        received.push_str(&String::from_utf8_lossy(&chunk));
+    }
+    let data = received
+        .lines()
+        .find_map(|line| line.strip_prefix("data: "))
+        .expect("evento SSE sem dados");
+    let progress: Value = serde_json::from_str(data).unwrap();
+    assert_eq!(progress["method"], "notifications/progress");
+    assert_eq!(progress["params"], json!({ "progressToken": "p", "progress": 1.0, "total": 2.0 }));
+
+    transport.close_streams();
+}
+
+#[tokio::test]
+async fn test_http_rejects_foreign_origin() {
+    let server = McpServer::with_router(http_config(), mock_router()).unwrap();
+    let handle = server.start().await.unwrap();
+    let url = format!("{}", handle.endpoint);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(&url)
+        .header("origin", "http://evil.example")
+        .body(INITIALIZE)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = client
+        .post(&url)
+        .header("origin", "http://localhost:5173")
+        .body(INITIALIZE)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    handle.shutdown().unwrap();
+    handle.wait_for_shutdown().await.unwrap();
+}
+
+// Sobe o transporte em uma porta local e retorna a URL do endpoint
+async fn serve(transport: &Arc<HttpTransport>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+    let app = transport.app();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    url
+}
+
+#[tokio::test]
+async fn test_http_sessions_expire_and_are_limited() {
+    let limits = HttpLimits {
+        idle_timeout: Duration::from_millis(500),
+        max_sessions: 1,
+        ..HttpLimits::default()
+    };
+    let transport = HttpTransport::with_limits(mock_router(), limits);
+    let url = serve(&transport).await;
+    let client = reqwest::Client::new();
+
+    let session = initialize(&client, &url).await;
+    assert_eq!(post(&client, &url, None, INITIALIZE).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // Cada requisição adia a expiração
+    let ping = r#"{"jsonrpc":"2.0","id":2,"method":"ping"}"#;
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(post(&client, &url, Some(&session), ping).await.status(), StatusCode::OK);
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(post(&client, &url, Some(&session), ping).await.status(), StatusCode::OK);
+
+    // Ociosa além do limite, a sessão expira e libera a vaga
+    tokio::time::sleep(Duration::from_millis(700)).await;
+    assert_eq!(post(&client, &url, Some(&session), ping).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(transport.session_count(), 0);
+    initialize(&client, &url).await;
+}
+
+#[tokio::test]
+async fn test_http_abandoned_post_leaves_no_request_in_flight() {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let observed = cancelled.clone();
+    let router = mock_router();
+    router.register_tool(
+        "test:wait".to_string(),
+        ToolDefinition {
+            name: "test:wait".to_string(),
+            description: "Espera o cancelamento".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(move |ctx: ToolContext, _| {
+            let started = Instant::now();
+            while !ctx.is_cancelled() && started.elapsed() < Duration::from_secs(5) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            observed.store(ctx.is_cancelled(), Ordering::SeqCst);
+            Ok(json!("fim"))
+        }),
+    );
+    let transport = HttpTransport::new(router);
+    let url = serve(&transport).await;
+    let client = reqwest::Client::new();
+    let session = initialize(&client, &url).await;
+
+    // O cliente desiste do POST antes da resposta
+    let call = r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"test:wait"}}"#;
+    let abandoned = client
+        .post(&url)
+        .header("content-type", "application/json")
+        .header(SESSION_HEADER, &session)
+        .body(call)
+        .timeout(Duration::from_millis(200))
+        .send()
+        .await;
+    assert!(abandoned.is_err());
+
+    let started = Instant::now();
+    while !cancelled.load(Ordering::SeqCst) {
+        assert!(started.elapsed() < Duration::from_secs(3), "a ferramenta não foi cancelada");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(transport.session(&session).unwrap().in_flight(), 0);
+}
+
+#[tokio::test]
+async fn test_http_session_requires_successful_initialize() {
+    let transport = HttpTransport::new(mock_router());
+    let url = serve(&transport).await;
+    let client = reqwest::Client::new();
+
+    // Um initialize recusado não deixa sessão registrada
+    let invalid = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":5}}"#;
+    let response = post(&client, &url, None, invalid).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(response.headers().get(SESSION_HEADER).is_none());
+    assert_eq!(json_body(response).await["error"]["code"], -32602);
+    assert_eq!(transport.session_count(), 0);
+
+    // Nem é possível reinicializar dentro de uma sessão existente
+    let session = initialize(&client, &url).await;
+    let response = post(&client, &url, Some(&session), INITIALIZE).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(transport.session_count(), 1);
+}
+
+#[tokio::test]
+async fn test_http_post_body_is_limited() {
+    let limits = HttpLimits {
+        max_message_size: 1024,
+        ..HttpLimits::default()
+    };
+    let transport = HttpTransport::with_limits(mock_router(), limits);
+    let url = serve(&transport).await;
+    let client = reqwest::Client::new();
+    let session = initialize(&client, &url).await;
+
+    let call = json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": { "name": "test:echo", "arguments": { "texto": "x".repeat(2048) } }
+    });
+    let response = post(&client, &url, Some(&session), &call.to_string()).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(json_body(response).await["error"]["code"], -32600);
+
+    let call = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"test:echo","arguments":{}}}"#;
+    assert_eq!(post(&client, &url, Some(&session), call).await.status(), StatusCode::OK);
+}
@@ -0,0 +1,359 @@
+//! Testes do backend da API websocket do iTerm2
+//!
+//! Um servidor websocket local faz o papel do iTerm2: mantém janelas, abas e
+//! sessões em memória, responde às mensagens protobuf e envia notificações de
+//! atualização de tela, de modo que o backend roda de ponta a ponta no Linux.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use prost::Message as _;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request as HttpRequest, Response as HttpResponse};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+
+use crate::mcp::backend::{ControlKey, ITermApiBackend, TerminalBackend};
+use crate::mcp::iterm::api::proto::*;
+use crate::mcp::iterm::api::{ApiConfig, ApiCredentials, ApiEndpoint, API_SUBPROTOCOL};
+use crate::mcp::types::TerminalTarget;
+
+// Largura da tela do iTerm2 falso; linhas maiores quebram com SoftEol
+const COLUMNS: usize = 20;
+
+// Estado do iTerm2 falso
+#[derive(Default)]
+struct FakeITerm {
+    // Linhas de cada sessão
+    buffers: HashMap<String, Vec<String>>,
+    // Sessões inscritas em atualizações de tela
+    subscriptions: Vec<String>,
+    // Cabeçalhos recebidos no handshake
+    headers: Vec<(String, String)>,
+    // Tipos de requisição recebidos, na ordem
+    requests: Vec<&'static str>,
+}
+
+// Servidor websocket que finge ser o iTerm2
+struct StandIn {
+    address: String,
+    state: Arc<Mutex<FakeITerm>>,
+    disconnect: broadcast::Sender<()>,
+}
+
+impl StandIn {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let state = Arc::new(Mutex::new(FakeITerm::default()));
+        {
+            let mut state = state.lock().unwrap();
+            for session in ["s1", "s2", "s3"] {
+                state.buffers.insert(session.to_string(), vec![format!("{} $", session)]);
+            }
+        }
+        let (disconnect, _) = broadcast::channel(4);
+
+        let accept_state = state.clone();
+        let accept_disconnect = disconnect.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = accept_state.clone();
+                let disconnect = accept_disconnect.subscribe();
+                tokio::spawn(serve(stream, state, disconnect));
+            }
+        });
+
+        StandIn { address, state, disconnect }
+    }
+
+    fn backend(&self) -> ITermApiBackend {
+        ITermApiBackend::with_config(ApiConfig {
+            endpoint: ApiEndpoint::Tcp(self.address.clone()),
+            credentials: ApiCredentials {
+                cookie: Some("c00kie".to_string()),
+                key: Some("k3y".to_string()),
+            },
+            advisory_name: "rs_iterm-tests".to_string(),
+            request_timeout: Duration::from_secs(2),
+        })
+    }
+
+    fn header(&self, name: &str) -> Option<String> {
+        let state = self.state.lock().unwrap();
+        state.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.clone())
+    }
+}
+
+async fn serve(stream: tokio::net::TcpStream, state: Arc<Mutex<FakeITerm>>, mut disconnect: broadcast::Receiver<()>) {
+    let handshake_state = state.clone();
+    // Assinatura imposta pelo tungstenite
+    #[allow(clippy::result_large_err)]
+    let callback = move |request: &HttpRequest, mut response: HttpResponse| -> Result<HttpResponse, ErrorResponse> {
+        let mut state = handshake_state.lock().unwrap();
+        for (name, value) in request.headers() {
+            state.headers.push((name.to_string(), value.to_str().unwrap_or_default().to_string()));
+        }
+        // O cliente recusa o handshake se o subprotocolo não for confirmado
+        response
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", API_SUBPROTOCOL.parse().unwrap());
+        Ok(response)
+    };
+    let socket = tokio_tungstenite::accept_hdr_async(stream, callback).await.unwrap();
+    let (mut sink, mut frames) = socket.split();
+
+    loop {
+        let frame = tokio::select! {
+            frame = frames.next() => frame,
+            _ = disconnect.recv() => break,
+        };
+        let Some(Ok(Message::Binary(bytes))) = frame else { break };
+        let message = ClientOriginatedMessage::decode(&bytes[..]).unwrap();
+        let (response, update) = respond(&state, message.submessage.unwrap());
+        let reply = ServerOriginatedMessage {
+            id: message.id,
+            submessage: Some(response),
+        };
+        sink.send(Message::Binary(reply.encode_to_vec().into())).await.unwrap();
+
+        if let Some(session) = update {
+            let event = ServerOriginatedMessage {
+                id: None,
+                submessage: Some(Response::Event(Notification {
+                    screen_update_notification: Some(ScreenUpdateNotification { session: Some(session) }),
+                })),
+            };
+            sink.send(Message::Binary(event.encode_to_vec().into())).await.unwrap();
+        }
+    }
+}
+
+// Responde a uma requisição; devolve também a sessão cuja tela mudou
+fn respond(state: &Mutex<FakeITerm>, request: Request) -> (Response, Option<String>) {
+    let mut state = state.lock().unwrap();
+    match request {
+        Request::SendText(request) => {
+            state.requests.push("send_text");
+            let session = request.session.unwrap_or_default();
+            let Some(buffer) = state.buffers.get_mut(&session) else {
+                return (
+                    Response::SendText(SendTextResponse { status: Some(SendTextStatus::SessionNotFound as i32) }),
+                    None,
+                );
+            };
+            let text = request.text.unwrap_or_default();
+            match text.strip_suffix('\r') {
+                Some(command) => {
+                    buffer.last_mut().unwrap().push_str(&format!(" {}", command));
+                    buffer.push(format!("{} $", session));
+                }
+                None => buffer.push(format!("^{}", (text.as_bytes()[0] + b'@') as char)),
+            }
+            let subscribed = state.subscriptions.contains(&session);
+            (
+                Response::SendText(SendTextResponse { status: Some(SendTextStatus::Ok as i32) }),
+                subscribed.then_some(session),
+            )
+        }
+        Request::GetBuffer(request) => {
+            state.requests.push("get_buffer");
+            let Some(buffer) = state.buffers.get(request.session()) else {
+                return (
+                    Response::GetBuffer(GetBufferResponse {
+                        status: Some(GetBufferStatus::SessionNotFound as i32),
+                        contents: vec![],
+                    }),
+                    None,
+                );
+            };
+            let mut contents = Vec::new();
+            for line in buffer {
+                let chars: Vec<char> = line.chars().collect();
+                let chunks: Vec<String> = chars.chunks(COLUMNS).map(|c| c.iter().collect()).collect();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let continuation = if i + 1 < chunks.len() { Continuation::SoftEol } else { Continuation::HardEol };
+                    contents.push(LineContents {
+                        text: Some(chunk.clone()),
+                        continuation: Some(continuation as i32),
+                    });
+                }
+            }
+            let trailing = request.line_range.and_then(|r| r.trailing_lines).unwrap_or(i32::MAX) as usize;
+            let contents = contents.split_off(contents.len().saturating_sub(trailing));
+            (
+                Response::GetBuffer(GetBufferResponse {
+                    status: Some(GetBufferStatus::Ok as i32),
+                    contents,
+                }),
+                None,
+            )
+        }
+        Request::ListSessions(_) => {
+            state.requests.push("list_sessions");
+            let summary = |id: &str| SplitTreeLink {
+                child: Some(SplitTreeChild::Session(SessionSummary {
+                    unique_identifier: Some(id.to_string()),
+                    grid_size: Some(Size {
+                        width: Some(COLUMNS as i32),
+                        height: Some(24),
+                    }),
+                    title: Some(format!("shell {}", id)),
+                })),
+            };
+            let tab = |id: &str, sessions: &[&str]| ListSessionsTab {
+                tab_id: Some(id.to_string()),
+                root: Some(SplitTreeNode {
+                    vertical: Some(true),
+                    links: sessions.iter().map(|s| summary(s)).collect(),
+                }),
+            };
+            (
+                Response::ListSessions(ListSessionsResponse {
+                    windows: vec![
+                        ListSessionsWindow {
+                            tabs: vec![tab("t1", &["s1"])],
+                            window_id: Some("w1".to_string()),
+                            number: Some(0),
+                        },
+                        ListSessionsWindow {
+                            tabs: vec![tab("t2", &["s2", "s3"])],
+                            window_id: Some("w2".to_string()),
+                            number: Some(1),
+                        },
+                    ],
+                    buried_sessions: vec![],
+                }),
+                None,
+            )
+        }
+        Request::Focus(_) => {
+            state.requests.push("focus");
+            let window = |id: &str, status: WindowStatus| FocusChangedNotification {
+                window: Some(FocusWindow {
+                    window_status: Some(status as i32),
+                    window_id: Some(id.to_string()),
+                }),
+                ..Default::default()
+            };
+            (
+                Response::Focus(FocusResponse {
+                    notifications: vec![
+                        window("w1", WindowStatus::ResignedKey),
+                        window("w2", WindowStatus::IsCurrent),
+                        FocusChangedNotification {
+                            selected_tab: Some("t2".to_string()),
+                            ..Default::default()
+                        },
+                        FocusChangedNotification {
+                            session: Some("s3".to_string()),
+                            ..Default::default()
+                        },
+                    ],
+                }),
+                None,
+            )
+        }
+        Request::Notification(request) => {
+            state.requests.push("notification");
+            state.subscriptions.push(request.session().to_string());
+            (
+                Response::Notification(NotificationResponse { status: Some(NotificationStatus::Ok as i32) }),
+                None,
+            )
+        }
+        Request::Variable(request) => {
+            state.requests.push("variable");
+            assert_eq!(request.get, vec!["tty".to_string()]);
+            // Sessão sem tty: nenhum processo a consultar
+            (
+                Response::Variable(VariableResponse {
+                    status: Some(VariableStatus::Ok as i32),
+                    values: vec!["\"\"".to_string()],
+                }),
+                None,
+            )
+        }
+    }
+}
+
+fn target(session: Option<&str>, window: Option<&str>) -> TerminalTarget {
+    TerminalTarget {
+        session: session.map(str::to_string),
+        window: window.map(str::to_string),
+    }
+}
+
+#[tokio::test]
+async fn test_iterm_api_write_and_read_active_session() {
+    let stand_in = StandIn::start().await;
+    let backend = stand_in.backend();
+    let cancel = CancellationToken::new();
+
+    // Sessão ativa: aba selecionada da janela atual (w2 -> t2 -> s3)
+    backend.write_text(&target(None, None), "echo hello", &cancel).await.unwrap();
+    let screen = backend.read_screen(&target(None, None), 10).await.unwrap();
+    assert_eq!(screen, "s3 $ echo hello\ns3 $");
+
+    // Linhas quebradas pela largura da tela voltam inteiras
+    let long = "echo a-command-longer-than-the-screen";
+    backend.write_text(&target(Some("s1"), None), long, &cancel).await.unwrap();
+    let screen = backend.read_screen(&target(Some("s1"), None), 1).await.unwrap();
+    assert_eq!(screen, "s1 $");
+    let screen = backend.read_screen(&target(None, Some("w1")), 10).await.unwrap();
+    assert_eq!(screen, format!("s1 $ {}\ns1 $", long));
+
+    // Teclas de controle vão como o caractere bruto
+    backend.send_control(&target(Some("s2"), None), ControlKey::parse("c").unwrap()).await.unwrap();
+    assert_eq!(backend.read_screen(&target(Some("s2"), None), 1).await.unwrap(), "^C");
+
+    // O handshake leva o subprotocolo, o nome e as credenciais
+    assert_eq!(stand_in.header("sec-websocket-protocol").as_deref(), Some(API_SUBPROTOCOL));
+    assert_eq!(stand_in.header("x-iterm2-advisory-name").as_deref(), Some("rs_iterm-tests"));
+    assert_eq!(stand_in.header("x-iterm2-cookie").as_deref(), Some("c00kie"));
+    assert_eq!(stand_in.header("x-iterm2-key").as_deref(), Some("k3y"));
+
+    // Uma inscrição em atualizações de tela por sessão escrita
+    let state = stand_in.state.lock().unwrap();
+    assert_eq!(state.subscriptions, vec!["s3".to_string(), "s1".to_string()]);
+}
+
+#[tokio::test]
+async fn test_iterm_api_lists_sessions_and_queries_process() {
+    let stand_in = StandIn::start().await;
+    let backend = stand_in.backend();
+
+    let sessions = backend.list_sessions().await.unwrap();
+    let ids: Vec<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, vec!["s1", "s2", "s3"]);
+    assert_eq!(sessions[1].name, "shell s2");
+    assert_eq!(sessions[1].window.as_deref(), Some("w2"));
+    assert_eq!(sessions[1].tab.as_deref(), Some("t2"));
+    assert_eq!((sessions[1].columns, sessions[1].rows), (Some(COLUMNS as u16), Some(24)));
+    let active: Vec<&str> = sessions.iter().filter(|s| s.active).map(|s| s.id.as_str()).collect();
+    assert_eq!(active, vec!["s3"]);
+
+    assert!(backend.query_process(&target(Some("s2"), None)).await.unwrap().is_none());
+    assert_eq!(stand_in.state.lock().unwrap().requests.last(), Some(&"variable"));
+}
+
+#[tokio::test]
+async fn test_iterm_api_errors_and_reconnects() {
+    let stand_in = StandIn::start().await;
+    let backend = stand_in.backend();
+    let cancel = CancellationToken::new();
+
+    let error = backend.read_screen(&target(Some("nope"), None), 5).await.unwrap_err();
+    assert!(error.to_string().contains("SessionNotFound"), "{}", error);
+    assert!(backend.write_text(&target(Some("nope"), None), "ls", &cancel).await.is_err());
+    assert!(backend.read_screen(&target(Some("s1"), Some("w2")), 5).await.is_err());
+    assert!(backend.read_screen(&target(None, Some("w9")), 5).await.is_err());
+
+    // O iTerm2 fecha a conexão; a próxima chamada reconecta
+    stand_in.disconnect.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(backend.read_screen(&target(Some("s1"), None), 5).await.unwrap(), "s1 $");
+}
@@ -5,7 +5,10 @@
 #![cfg(test)]
 
 mod basic_tests;
+mod http_tests;
+mod iterm_api_tests;
 mod router_tests;
+mod tools_tests;
 
 #[cfg(target_os = "macos")]
 mod integration_applescript;
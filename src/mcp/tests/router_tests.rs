@@ -1,12 +1,17 @@
 //! Testes para o módulo router
 
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use async_trait::async_trait;
 use serde_json::json;
 
 use crate::mcp::router::Router;
-use crate::mcp::types::ToolDefinition;
+use crate::mcp::session::Session;
+use crate::mcp::tools::{Tool, ToolContext};
+use crate::mcp::types::{ToolDefinition, MCP_PROTOCOL_VERSION};
 
 // Mock para testar envio e recebimento de mensagens MCP
 struct MockConnection {
@@ -26,27 +31,36 @@ impl MockConnection {
         self.output_messages.push(message);
     }
 
+    // Entrega cada mensagem de entrada ao router e guarda as respostas
+    async fn run(&mut self, router: &Router) {
+        for message in self.input_messages.clone() {
+            if let Some(response) = router.process_message(&message).await {
+                self.send_message(response);
+            }
+        }
+    }
+
     fn get_responses(&self) -> &[String] {
         &self.output_messages
     }
 }
 
 // Handler mock que apenas retorna o que recebeu
-fn echo_handler(params: serde_json::Value) -> Result<serde_json::Value> {
+fn echo_handler(_ctx: ToolContext, params: serde_json::Value) -> Result<serde_json::Value> {
     Ok(json!({
         "received": params
     }))
 }
 
 // Handler mock que sempre retorna erro
-fn error_handler(_: serde_json::Value) -> Result<serde_json::Value> {
+fn error_handler(_ctx: ToolContext, _: serde_json::Value) -> Result<serde_json::Value> {
     Err(anyhow::anyhow!("Erro simulado para teste"))
 }
 
 #[tokio::test]
 async fn test_router_process_message() {
-    // Cria o router
-    let router = Router::new();
+    // Cria o router com o envelope legado
+    let router = Router::new().with_legacy_envelope(true);
     
     // Registra uma ferramenta de teste
     let tool_def = ToolDefinition {
@@ -76,8 +90,8 @@ async fn test_router_process_message() {
 
 #[tokio::test]
 async fn test_router_invalid_json() {
-    // Cria o router
-    let router = Router::new();
+    // Cria o router com o envelope legado
+    let router = Router::new().with_legacy_envelope(true);
     
     // Mensagem JSON inválida
     let message = r#"{"id":"test-1","function":invalid json}"#;
@@ -93,8 +107,8 @@ async fn test_router_invalid_json() {
 
 #[tokio::test]
 async fn test_router_unknown_function() {
-    // Cria o router
-    let router = Router::new();
+    // Cria o router com o envelope legado
+    let router = Router::new().with_legacy_envelope(true);
     
     // Mensagem com função desconhecida
     let message = r#"{"id":"test-1","function":"unknown:function","arguments":{}}"#;
@@ -111,8 +125,8 @@ async fn test_router_unknown_function() {
 
 #[tokio::test]
 async fn test_router_handler_error() {
-    // Cria o router
-    let router = Router::new();
+    // Cria o router com o envelope legado
+    let router = Router::new().with_legacy_envelope(true);
     
     // Registra uma ferramenta que sempre retorna erro
     let tool_def = ToolDefinition {
@@ -142,8 +156,8 @@ async fn test_router_handler_error() {
 
 #[tokio::test]
 async fn test_multiple_tools() {
-    // Cria o router
-    let router = Router::new();
+    // Cria o router com o envelope legado
+    let router = Router::new().with_legacy_envelope(true);
     
     // Registra múltiplas ferramentas
     let echo_tool_def = ToolDefinition {
@@ -207,3 +221,464 @@ async fn test_create_error_response() {
     assert_eq!(response_json["error"]["message"], "Mensagem de teste");
     assert_eq!(response_json["error"]["data"]["detail"], "Informação adicional");
 }
+
+// Cria um router JSON-RPC com as ferramentas de eco e erro registradas
+fn jsonrpc_router() -> Router {
+    let router = Router::new();
+
+    router.register_tool(
+        "test:echo".to_string(),
+        ToolDefinition {
+            name: "test:echo".to_string(),
+            description: "Ferramenta de eco para testes".to_string(),
+            parameters: serde_json::from_value(json!({
+                "type": "object",
+                "properties": { "param1": { "type": "string" } }
+            }))
+            .unwrap(),
+        },
+        Arc::new(echo_handler),
+    );
+
+    router.register_tool(
+        "test:error".to_string(),
+        ToolDefinition {
+            name: "test:error".to_string(),
+            description: "Ferramenta de erro para testes".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(error_handler),
+    );
+
+    router
+}
+
+#[tokio::test]
+async fn test_jsonrpc_session() {
+    let router = jsonrpc_router();
+
+    let mut connection = MockConnection::new(vec![
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05","capabilities":{},"clientInfo":{"name":"test","version":"1.0"}}}"#.to_string(),
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#.to_string(),
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#.to_string(),
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"test:echo","arguments":{"param1":"value1"}}}"#.to_string(),
+    ]);
+    connection.run(&router).await;
+
+    // A notificação não gera resposta
+    let responses = connection.get_responses();
+    assert_eq!(responses.len(), 3);
+
+    let initialize: serde_json::Value = serde_json::from_str(&responses[0]).unwrap();
+    assert_eq!(initialize["jsonrpc"], "2.0");
+    assert_eq!(initialize["id"], 1);
+    assert_eq!(initialize["result"]["protocolVersion"], "2024-11-05");
+    assert_eq!(initialize["result"]["serverInfo"]["name"], "rs_iterm");
+    assert!(initialize["result"]["capabilities"]["tools"].is_object());
+
+    let list: serde_json::Value = serde_json::from_str(&responses[1]).unwrap();
+    let tools = list["result"]["tools"].as_array().unwrap();
+    assert_eq!(tools.len(), 2);
+    assert_eq!(tools[0]["name"], "test:echo");
+    assert_eq!(tools[0]["inputSchema"]["type"], "object");
+    assert!(tools[0].get("parameters").is_none());
+
+    let call: serde_json::Value = serde_json::from_str(&responses[2]).unwrap();
+    assert_eq!(call["id"], 3);
+    assert_eq!(call["result"]["isError"], false);
+    assert_eq!(call["result"]["content"][0]["type"], "text");
+    let text = call["result"]["content"][0]["text"].as_str().unwrap();
+    let echoed: serde_json::Value = serde_json::from_str(text).unwrap();
+    assert_eq!(echoed["received"]["param1"], "value1");
+}
+
+#[tokio::test]
+async fn test_jsonrpc_initialize_unknown_version() {
+    let router = jsonrpc_router();
+
+    let message = r#"{"jsonrpc":"2.0","id":"init","method":"initialize","params":{"protocolVersion":"1999-01-01"}}"#;
+    let response = router.process_message(message).await.unwrap();
+
+    // Versões desconhecidas recebem a versão mais recente suportada
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["result"]["protocolVersion"], MCP_PROTOCOL_VERSION);
+}
+
+#[tokio::test]
+async fn test_jsonrpc_tool_error_is_reported_in_result() {
+    let router = jsonrpc_router();
+
+    let message = r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"test:error","arguments":{}}}"#;
+    let response = router.process_message(message).await.unwrap();
+
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert!(response_json.get("error").is_none());
+    assert_eq!(response_json["result"]["isError"], true);
+    assert!(response_json["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap()
+        .contains("Erro simulado"));
+}
+
+#[tokio::test]
+async fn test_jsonrpc_unknown_tool_and_method() {
+    let router = jsonrpc_router();
+
+    let message = r#"{"jsonrpc":"2.0","id":8,"method":"tools/call","params":{"name":"unknown:tool"}}"#;
+    let response = router.process_message(message).await.unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["id"], 8);
+    assert_eq!(response_json["error"]["code"], -32602);
+
+    let message = r#"{"jsonrpc":"2.0","id":9,"method":"resources/list"}"#;
+    let response = router.process_message(message).await.unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["error"]["code"], -32601);
+}
+
+#[tokio::test]
+async fn test_jsonrpc_parse_and_envelope_errors() {
+    let router = jsonrpc_router();
+
+    let response = router.process_message("{not json").await.unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["id"], serde_json::Value::Null);
+    assert_eq!(response_json["error"]["code"], -32700);
+
+    // Envelope legado sem o modo de compatibilidade é uma requisição inválida
+    let message = r#"{"id":"test-1","function":"test:echo","arguments":{}}"#;
+    let response = router.process_message(message).await.unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["id"], "test-1");
+    assert_eq!(response_json["error"]["code"], -32600);
+
+    let message = r#"{"jsonrpc":"1.0","id":10,"method":"ping"}"#;
+    let response = router.process_message(message).await.unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn test_jsonrpc_batch_is_answered_in_order() {
+    let router = jsonrpc_router();
+
+    // Duas chamadas lentas no mesmo lote devem executar em paralelo
+    router.register_tool(
+        "test:slow".to_string(),
+        ToolDefinition {
+            name: "test:slow".to_string(),
+            description: "Ferramenta lenta para testes".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(|_ctx, params| {
+            std::thread::sleep(Duration::from_millis(400));
+            Ok(params)
+        }),
+    );
+
+    let batch = r#"[
+        {"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"test:slow","arguments":{"n":1}}},
+        {"jsonrpc":"2.0","method":"notifications/initialized"},
+        {"jsonrpc":"2.0","id":"dois","method":"tools/call","params":{"name":"test:slow","arguments":{"n":2}}},
+        {"jsonrpc":"2.0","id":3,"method":"unknown/method"},
+        42
+    ]"#;
+
+    let started = Instant::now();
+    let response = router.process_message(batch).await.unwrap();
+    assert!(
+        started.elapsed() < Duration::from_millis(750),
+        "o lote levou {:?}",
+        started.elapsed()
+    );
+
+    // Notificações são omitidas; as demais respostas seguem a ordem do lote
+    let responses: Vec<serde_json::Value> = serde_json::from_str(&response).unwrap();
+    assert_eq!(responses.len(), 4);
+    assert_eq!(responses[0]["id"], 1);
+    assert_eq!(responses[0]["result"]["isError"], false);
+    assert_eq!(responses[1]["id"], "dois");
+    assert_eq!(responses[2]["id"], 3);
+    assert_eq!(responses[2]["error"]["code"], -32601);
+    assert!(responses[3]["id"].is_null());
+    assert_eq!(responses[3]["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn test_jsonrpc_batch_edge_cases() {
+    let router = jsonrpc_router();
+
+    // Lote vazio é uma requisição inválida com resposta única
+    let response = router.process_message("[]").await.unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert!(response_json.is_object());
+    assert_eq!(response_json["error"]["code"], -32600);
+
+    // Lote só de notificações não recebe resposta
+    let batch = r#"[{"jsonrpc":"2.0","method":"notifications/initialized"},{"jsonrpc":"2.0","method":"ping"}]"#;
+    assert!(router.process_message(batch).await.is_none());
+}
+
+#[tokio::test]
+async fn test_jsonrpc_requests_without_id_are_notifications() {
+    let router = jsonrpc_router();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    router.register_tool(
+        "test:count".to_string(),
+        ToolDefinition {
+            name: "test:count".to_string(),
+            description: "Conta chamadas".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(move |_ctx, _| Ok(json!(counter.fetch_add(1, Ordering::SeqCst) + 1))),
+    );
+
+    // A ferramenta é executada, mas nenhuma resposta é enviada
+    let message = r#"{"jsonrpc":"2.0","method":"tools/call","params":{"name":"test:count"}}"#;
+    assert!(router.process_message(message).await.is_none());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let message = r#"{"jsonrpc":"2.0","method":"unknown/method"}"#;
+    assert!(router.process_message(message).await.is_none());
+}
+
+#[tokio::test]
+async fn test_jsonrpc_numeric_and_null_ids() {
+    let router = jsonrpc_router();
+
+    let response = router
+        .process_message(r#"{"jsonrpc":"2.0","id":12345678901,"method":"ping"}"#)
+        .await
+        .unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["id"], 12345678901_i64);
+
+    // `"id": null` é uma requisição (não notificação) e recebe resposta
+    let response = router
+        .process_message(r#"{"jsonrpc":"2.0","id":null,"method":"ping"}"#)
+        .await
+        .unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert!(response_json["id"].is_null());
+    assert!(response_json["result"].is_object());
+
+    // IDs que não são número, string ou null tornam a requisição inválida
+    let response = router
+        .process_message(r#"{"jsonrpc":"2.0","id":{"a":1},"method":"ping"}"#)
+        .await
+        .unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn test_legacy_envelope_accepts_numeric_ids() {
+    let router = Router::new().with_legacy_envelope(true);
+    router.register_tool(
+        "test:echo".to_string(),
+        ToolDefinition {
+            name: "test:echo".to_string(),
+            description: "Ferramenta de eco para testes".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(echo_handler),
+    );
+
+    let message = r#"{"id":42,"function":"test:echo","arguments":{"x":1}}"#;
+    let response = router.process_message(message).await.unwrap();
+    let response_json: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response_json["id"], 42);
+    assert_eq!(response_json["type"], "response");
+}
+
+// Registra uma ferramenta que só termina quando for cancelada (ou após 5s)
+fn register_wait_tool(router: &Router, observed_cancel: Arc<AtomicUsize>) {
+    router.register_tool(
+        "test:wait".to_string(),
+        ToolDefinition {
+            name: "test:wait".to_string(),
+            description: "Aguarda o cancelamento".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(move |ctx: ToolContext, _| {
+            let started = Instant::now();
+            while started.elapsed() < Duration::from_secs(5) {
+                if ctx.is_cancelled() {
+                    observed_cancel.fetch_add(1, Ordering::SeqCst);
+                    return Err(anyhow::anyhow!("cancelado"));
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Ok(json!("terminou"))
+        }),
+    );
+}
+
+#[tokio::test]
+async fn test_jsonrpc_cancelled_request_gets_no_response() {
+    let router = Arc::new(jsonrpc_router());
+    let observed_cancel = Arc::new(AtomicUsize::new(0));
+    register_wait_tool(&router, observed_cancel.clone());
+    let session = Arc::new(Session::new());
+
+    let call = {
+        let router = router.clone();
+        let session = session.clone();
+        tokio::spawn(async move {
+            let message = r#"{"jsonrpc":"2.0","id":"longa","method":"tools/call","params":{"name":"test:wait"}}"#;
+            router.process_session_message(message, &session).await
+        })
+    };
+
+    // Aguarda a requisição ser registrada como em andamento
+    while session.in_flight() == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    let cancel = r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":"longa","reason":"usuário desistiu"}}"#;
+    assert!(router.process_session_message(cancel, &session).await.is_none());
+
+    let started = Instant::now();
+    assert!(call.await.unwrap().is_none(), "a resposta tardia deve ser descartada");
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(session.in_flight(), 0);
+
+    // O handler observa o cancelamento pelo contexto
+    while observed_cancel.load(Ordering::SeqCst) == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    // Cancelar uma requisição desconhecida é ignorado
+    let unknown = r#"{"jsonrpc":"2.0","method":"notifications/cancelled","params":{"requestId":99}}"#;
+    assert!(router.process_session_message(unknown, &session).await.is_none());
+}
+
+fn register_progress_tool(router: &Router) {
+    router.register_tool(
+        "test:progress".to_string(),
+        ToolDefinition {
+            name: "test:progress".to_string(),
+            description: "Reporta progresso em três etapas".to_string(),
+            parameters: Default::default(),
+        },
+        Arc::new(|ctx: ToolContext, _| {
+            for step in 1..=3 {
+                ctx.report_progress(step as f64, Some(3.0), Some(&format!("etapa {}", step)));
+            }
+            Ok(json!({ "progresso": ctx.wants_progress() }))
+        }),
+    );
+}
+
+#[tokio::test]
+async fn test_jsonrpc_progress_notifications_precede_response() {
+    let router = jsonrpc_router();
+    register_progress_tool(&router);
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let session = Session::with_outgoing(sender);
+
+    let message = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"test:progress","_meta":{"progressToken":"tok-1"}}}"#;
+    let response = router.process_session_message(message, &session).await.unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["content"][0]["text"], r#"{"progresso":true}"#);
+
+    let mut notifications = Vec::new();
+    while let Ok(message) = receiver.try_recv() {
+        notifications.push(serde_json::from_str::<serde_json::Value>(&message).unwrap());
+    }
+    assert_eq!(notifications.len(), 3);
+    for (index, notification) in notifications.iter().enumerate() {
+        assert_eq!(notification["method"], "notifications/progress");
+        assert!(notification.get("id").is_none());
+        assert_eq!(notification["params"]["progressToken"], "tok-1");
+        assert_eq!(notification["params"]["progress"], (index + 1) as f64);
+        assert_eq!(notification["params"]["total"], 3.0);
+    }
+    assert_eq!(notifications[2]["params"]["message"], "etapa 3");
+
+    // Sem progressToken nenhuma notificação é enviada
+    let message = r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"test:progress","_meta":{}}}"#;
+    let response = router.process_session_message(message, &session).await.unwrap();
+    assert!(response.contains(r#"{\"progresso\":false}"#));
+
+    let message = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"test:progress","_meta":{"progressToken":7}}}"#;
+    router.process_session_message(message, &Session::new()).await.unwrap();
+    assert!(receiver.try_recv().is_err());
+}
+
+// Ferramenta assíncrona que aguarda sem bloquear a thread do runtime
+struct SleepTool;
+
+#[async_trait]
+impl Tool for SleepTool {
+    fn name(&self) -> &str {
+        "test:sleep"
+    }
+
+    fn description(&self) -> &str {
+        "Aguarda o tempo pedido e devolve os argumentos"
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": { "ms": { "type": "integer" } },
+            "required": ["ms"]
+        })
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: serde_json::Value) -> Result<serde_json::Value> {
+        let ms = arguments["ms"].as_u64().unwrap_or(0);
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        Ok(arguments)
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_async_tools_run_concurrently_on_current_thread_runtime() {
+    let router = jsonrpc_router();
+    router.register(Arc::new(SleepTool));
+
+    let tools = router.list_tools();
+    let sleep = tools.iter().find(|tool| tool.name == "test:sleep").unwrap();
+    assert_eq!(sleep.parameters["required"], json!(["ms"]));
+
+    let batch = r#"[
+        {"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"test:sleep","arguments":{"ms":300}}},
+        {"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"test:sleep","arguments":{"ms":300}}},
+        {"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"test:echo","arguments":{"x":1}}}
+    ]"#;
+
+    let started = Instant::now();
+    let response = router.process_message(batch).await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(550), "o lote levou {:?}", started.elapsed());
+
+    let responses: Vec<serde_json::Value> = serde_json::from_str(&response).unwrap();
+    assert_eq!(responses.len(), 3);
+    for response in &responses {
+        assert_eq!(response["result"]["isError"], false, "resposta: {}", response);
+    }
+}
+
+#[tokio::test]
+async fn test_jsonrpc_invalid_arguments_are_rejected_before_call() {
+    let router = jsonrpc_router();
+    router.register(Arc::new(SleepTool));
+
+    let message = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"test:sleep","arguments":{"ms":"dez"}}}"#;
+    let response: serde_json::Value =
+        serde_json::from_str(&router.process_message(message).await.unwrap()).unwrap();
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(response["error"]["message"], "Argumentos inválidos para test:sleep");
+    assert_eq!(
+        response["error"]["data"]["errors"],
+        json!([{ "field": "ms", "message": "esperado integer, recebido string" }])
+    );
+
+    let message = r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"test:sleep"}}"#;
+    let response: serde_json::Value =
+        serde_json::from_str(&router.process_message(message).await.unwrap()).unwrap();
+    assert_eq!(response["error"]["data"]["errors"][0]["field"], "ms");
+    assert_eq!(response["error"]["data"]["errors"][0]["message"], "campo obrigatório ausente");
+}
@@ -0,0 +1,548 @@
+//! Testes das ferramentas MCP sobre backends de terminal
+//!
+//! As ferramentas dependem apenas da trait `TerminalBackend`; aqui um backend
+//! em memória registra as operações recebidas, sem iTerm2 nem TTY, e os backends
+//! PTY e VT exercitam as ferramentas de ponta a ponta com um shell real.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+use crate::mcp::backend::pty::{Emulation, PtyConfig};
+use crate::mcp::backend::{
+    BackendCapabilities, ControlKey, NewSession, PtyBackend, SessionLayout, SessionUpdate, TerminalBackend,
+    TerminalSession,
+};
+use crate::mcp::router::Router;
+use crate::mcp::session::Session;
+use crate::mcp::tools::register_tools;
+use crate::mcp::types::{ProcessInfo, TerminalBackendInfo, TerminalTarget};
+
+/// Backend que registra as operações e devolve uma tela fixa
+#[derive(Default)]
+struct RecordingBackend {
+    written: Mutex<Vec<String>>,
+    keys: Mutex<Vec<ControlKey>>,
+    targets: Mutex<Vec<TerminalTarget>>,
+    sessions: Vec<TerminalSession>,
+    created: Mutex<Vec<NewSession>>,
+    closed: Mutex<Vec<TerminalTarget>>,
+    updates: Mutex<Vec<SessionUpdate>>,
+    process: Option<ProcessInfo>,
+    capabilities: BackendCapabilities,
+    /// Atraso de cada escrita, simulando um terminal que não lê a entrada
+    write_delay: Duration,
+}
+
+#[async_trait]
+impl TerminalBackend for RecordingBackend {
+    fn name(&self) -> &'static str {
+        "recording"
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        self.capabilities
+    }
+
+    async fn write_text(&self, target: &TerminalTarget, text: &str, _cancel: &CancellationToken) -> Result<()> {
+        tokio::time::sleep(self.write_delay).await;
+        self.targets.lock().unwrap().push(target.clone());
+        self.written.lock().unwrap().push(text.to_string());
+        Ok(())
+    }
+
+    async fn read_screen(&self, target: &TerminalTarget, lines: usize) -> Result<String> {
+        self.targets.lock().unwrap().push(target.clone());
+        let screen = ["$ ls", "Cargo.toml", "src", "$"];
+        Ok(screen[screen.len().saturating_sub(lines)..].join("\n"))
+    }
+
+    async fn send_control(&self, target: &TerminalTarget, key: ControlKey) -> Result<()> {
+        self.targets.lock().unwrap().push(target.clone());
+        self.keys.lock().unwrap().push(key);
+        Ok(())
+    }
+
+    async fn list_sessions(&self) -> Result<Vec<TerminalSession>> {
+        Ok(self.sessions.clone())
+    }
+
+    async fn query_process(&self, _target: &TerminalTarget) -> Result<Option<ProcessInfo>> {
+        Ok(self.process.clone())
+    }
+
+    async fn create_session(&self, target: &TerminalTarget, new: &NewSession) -> Result<String> {
+        self.targets.lock().unwrap().push(target.clone());
+        let mut created = self.created.lock().unwrap();
+        created.push(new.clone());
+        Ok(format!("new-{}", created.len()))
+    }
+
+    async fn close_session(&self, target: &TerminalTarget) -> Result<()> {
+        self.closed.lock().unwrap().push(target.clone());
+        Ok(())
+    }
+
+    async fn update_session(&self, target: &TerminalTarget, update: &SessionUpdate) -> Result<()> {
+        self.targets.lock().unwrap().push(target.clone());
+        self.updates.lock().unwrap().push(update.clone());
+        Ok(())
+    }
+}
+
+fn router_with(backend: Arc<RecordingBackend>) -> Router {
+    let router = Router::new();
+    for (_, tool) in register_tools(backend) {
+        router.register(tool);
+    }
+    router
+}
+
+async fn call(router: &Router, name: &str, arguments: serde_json::Value) -> serde_json::Value {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": { "name": name, "arguments": arguments }
+    });
+    let response = router.process_message(&request.to_string()).await.unwrap();
+    serde_json::from_str(&response).unwrap()
+}
+
+/// Lê as últimas linhas da saída pela ferramenta `read_terminal_output`
+async fn read_output(router: &Router, lines: u32) -> String {
+    let response = call(router, "iterm-mcp:read_terminal_output", json!({ "linesOfOutput": lines })).await;
+    let text = response["result"]["content"][0]["text"].as_str().unwrap().to_string();
+    let output: serde_json::Value = serde_json::from_str(&text).unwrap();
+    output["output"].as_str().unwrap().to_string()
+}
+
+/// Lê a saída até que uma linha terminada em `expected` apareça (o prompt
+/// pode chegar depois do eco do primeiro comando)
+async fn wait_for_line(router: &Router, expected: &str) -> String {
+    let started = Instant::now();
+    loop {
+        let screen = read_output(router, 20).await;
+        if screen.lines().any(|line| line.ends_with(expected)) {
+            return screen;
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "saída sem {:?}:\n{}", expected, screen);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// Aguarda até que `name` seja o processo em primeiro plano do terminal
+async fn wait_for_process(backend: &PtyBackend, name: &str) {
+    let started = Instant::now();
+    loop {
+        let process = backend.query_process(&TerminalTarget::default()).await.unwrap();
+        if process.as_ref().map(|p| p.name.as_str()) == Some(name) {
+            return;
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "{} não assumiu: {:?}", name, process);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_tools_only_talk_to_the_backend() {
+    let backend = Arc::new(RecordingBackend::default());
+    let router = router_with(backend.clone());
+
+    let response = call(&router, "iterm-mcp:write_to_terminal", json!({ "command": "ls" })).await;
+    assert_eq!(response["result"]["isError"], false);
+    assert_eq!(*backend.written.lock().unwrap(), vec!["ls".to_string()]);
+
+    let response = call(&router, "iterm-mcp:read_terminal_output", json!({ "linesOfOutput": 2 })).await;
+    let output: serde_json::Value =
+        serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(output["output"], "src\n$");
+
+    let response = call(&router, "iterm-mcp:send_control_character", json!({ "letter": "c" })).await;
+    assert_eq!(response["result"]["isError"], false);
+    assert_eq!(*backend.keys.lock().unwrap(), vec![ControlKey { letter: 'C', code: 3 }]);
+
+    // Sem `session`/`window`, as ferramentas agem na sessão ativa
+    assert!(backend.targets.lock().unwrap().iter().all(TerminalTarget::is_active));
+}
+
+#[tokio::test]
+async fn test_slow_writes_report_progress_until_done() {
+    let backend = Arc::new(RecordingBackend {
+        write_delay: Duration::from_millis(1500),
+        ..RecordingBackend::default()
+    });
+    let router = router_with(backend.clone());
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let session = Session::with_outgoing(sender);
+
+    let message = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "tools/call",
+        "params": {
+            "name": "iterm-mcp:write_to_terminal",
+            "arguments": { "command": "ls" },
+            "_meta": { "progressToken": "escrita" }
+        }
+    });
+    let response = router.process_session_message(&message.to_string(), &session).await.unwrap();
+    let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+    assert_eq!(response["result"]["isError"], false);
+
+    // Todas as notificações chegaram antes do resultado, com progresso crescente
+    let mut notifications = Vec::new();
+    while let Ok(message) = receiver.try_recv() {
+        notifications.push(serde_json::from_str::<serde_json::Value>(&message).unwrap());
+    }
+    let messages: Vec<&str> = notifications
+        .iter()
+        .map(|notification| notification["params"]["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        vec!["Enviando comando ao terminal", "Aguardando o terminal há 1s", "Comando concluído"]
+    );
+    let progress: Vec<f64> = notifications
+        .iter()
+        .map(|notification| notification["params"]["progress"].as_f64().unwrap())
+        .collect();
+    assert_eq!(progress, vec![0.0, 1.0, 2.0]);
+    assert_eq!(notifications[2]["params"]["total"], 2.0);
+}
+
+#[tokio::test]
+async fn test_session_and_window_are_passed_to_the_backend() {
+    let backend = Arc::new(RecordingBackend::default());
+    let router = router_with(backend.clone());
+    let target = TerminalTarget {
+        session: Some("deploy".to_string()),
+        window: Some("2".to_string()),
+    };
+
+    let arguments = json!({ "command": "ls", "session": "deploy", "window": "2" });
+    call(&router, "iterm-mcp:write_to_terminal", arguments).await;
+    let arguments = json!({ "linesOfOutput": 1, "session": "deploy", "window": "2" });
+    call(&router, "iterm-mcp:read_terminal_output", arguments).await;
+    let arguments = json!({ "letter": "c", "session": "deploy", "window": "2" });
+    call(&router, "iterm-mcp:send_control_character", arguments).await;
+
+    assert_eq!(*backend.targets.lock().unwrap(), vec![target.clone(), target.clone(), target]);
+}
+
+#[tokio::test]
+async fn test_invalid_control_letter_is_a_tool_error() {
+    let backend = Arc::new(RecordingBackend::default());
+    let router = router_with(backend.clone());
+
+    let response = call(&router, "iterm-mcp:send_control_character", json!({ "letter": "?" })).await;
+    assert_eq!(response["result"]["isError"], true);
+    assert!(backend.keys.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_out_of_range_integers_are_invalid_params() {
+    let backend = Arc::new(RecordingBackend::default());
+    let router = router_with(backend.clone());
+
+    // Acima de u32::MAX a validação recusa antes da desserialização
+    let arguments = json!({ "linesOfOutput": u32::MAX as u64 + 1 });
+    let response = call(&router, "iterm-mcp:read_terminal_output", arguments).await;
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(
+        response["error"]["data"]["errors"],
+        json!([{ "field": "linesOfOutput", "message": "valor deve ser <= 4294967295" }])
+    );
+    assert!(backend.targets.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_unsupported_tools_are_hidden_and_backend_is_reported() {
+    // Backend somente leitura: sem escrita nem teclas de controle
+    let backend = Arc::new(RecordingBackend {
+        capabilities: BackendCapabilities {
+            write_text: false,
+            send_control: false,
+            list_sessions: false,
+            ..BackendCapabilities::default()
+        },
+        ..RecordingBackend::default()
+    });
+    let info = TerminalBackendInfo {
+        name: backend.name().to_string(),
+        capabilities: backend.capabilities(),
+    };
+    let router = router_with(backend).with_backend_info(info);
+
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/list" });
+    let response: serde_json::Value =
+        serde_json::from_str(&router.process_message(&request.to_string()).await.unwrap()).unwrap();
+    let names: Vec<&str> = response["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["iterm-mcp:read_terminal_output"]);
+
+    let response = call(&router, "iterm-mcp:write_to_terminal", json!({ "command": "ls" })).await;
+    assert!(response["error"].is_object());
+
+    let request = json!({ "jsonrpc": "2.0", "id": 2, "method": "initialize", "params": {} });
+    let response: serde_json::Value =
+        serde_json::from_str(&router.process_message(&request.to_string()).await.unwrap()).unwrap();
+    let reported = &response["result"]["capabilities"]["experimental"]["terminalBackend"];
+    assert_eq!(reported["name"], "recording");
+    assert_eq!(reported["capabilities"]["readScreen"], true);
+    assert_eq!(reported["capabilities"]["writeText"], false);
+    assert_eq!(reported["capabilities"]["selectSession"], false);
+}
+
+#[tokio::test]
+async fn test_list_sessions_groups_sessions_by_window_and_tab() {
+    let session = |id: &str, window: Option<&str>, tab: Option<&str>, active: bool| TerminalSession {
+        id: id.to_string(),
+        name: format!("shell {}", id),
+        tty: Some(format!("/dev/ttys00{}", id)),
+        active,
+        window: window.map(str::to_string),
+        tab: tab.map(str::to_string),
+        columns: Some(80),
+        rows: Some(24),
+    };
+    let backend = Arc::new(RecordingBackend {
+        sessions: vec![
+            session("1", Some("w1"), Some("1"), false),
+            session("2", Some("w2"), Some("1"), false),
+            session("3", Some("w2"), Some("2"), true),
+            session("4", Some("w1"), Some("1"), false),
+        ],
+        ..RecordingBackend::default()
+    });
+    let router = router_with(backend);
+
+    let response = call(&router, "iterm-mcp:list_sessions", json!({})).await;
+    assert_eq!(response["result"]["isError"], false);
+    let tree: serde_json::Value =
+        serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+
+    let windows = tree["windows"].as_array().unwrap();
+    assert_eq!(windows.len(), 2);
+    assert_eq!(windows[0]["id"], "w1");
+    assert_eq!(windows[0]["current"], false);
+    let ids: Vec<&str> = windows[0]["tabs"][0]["sessions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|session| session["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["1", "4"]);
+
+    assert_eq!(windows[1]["current"], true);
+    let tabs = windows[1]["tabs"].as_array().unwrap();
+    assert_eq!(tabs.len(), 2);
+    assert_eq!(tabs[0]["current"], false);
+    assert_eq!(tabs[1]["current"], true);
+    assert_eq!(
+        tabs[1]["sessions"][0],
+        json!({
+            "id": "3",
+            "name": "shell 3",
+            "tty": "/dev/ttys003",
+            "columns": 80,
+            "rows": 24,
+            "current": true
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_windows_tabs_and_splits_are_created_through_the_backend() {
+    // Sem a capacidade, as ferramentas de criação não são registradas
+    let router = router_with(Arc::new(RecordingBackend::default()));
+    let response = call(&router, "iterm-mcp:create_window", json!({})).await;
+    assert!(response["error"].is_object());
+
+    let backend = Arc::new(RecordingBackend {
+        capabilities: BackendCapabilities {
+            create_session: true,
+            ..BackendCapabilities::default()
+        },
+        ..RecordingBackend::default()
+    });
+    let router = router_with(backend.clone());
+    let session_id = |response: &serde_json::Value| {
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        serde_json::from_str::<serde_json::Value>(text).unwrap()["sessionId"].clone()
+    };
+
+    let response = call(&router, "iterm-mcp:create_window", json!({ "profile": "Agent", "command": "ls" })).await;
+    assert_eq!(session_id(&response), "new-1");
+    let response = call(&router, "iterm-mcp:create_tab", json!({ "session": "w0t0p0" })).await;
+    assert_eq!(session_id(&response), "new-2");
+    let response = call(&router, "iterm-mcp:split_pane", json!({ "direction": "vertical", "command": "htop" })).await;
+    assert_eq!(session_id(&response), "new-3");
+
+    // Direções fora do enum são recusadas pela validação do esquema
+    let response = call(&router, "iterm-mcp:split_pane", json!({ "direction": "diagonal" })).await;
+    assert_eq!(response["error"]["code"], -32602);
+
+    let created = backend.created.lock().unwrap();
+    assert_eq!(
+        *created,
+        vec![
+            NewSession {
+                layout: SessionLayout::Window,
+                profile: Some("Agent".to_string()),
+                command: Some("ls".to_string()),
+            },
+            NewSession {
+                layout: SessionLayout::Tab,
+                profile: None,
+                command: None,
+            },
+            NewSession {
+                layout: SessionLayout::Split { vertical: true },
+                profile: None,
+                command: Some("htop".to_string()),
+            },
+        ]
+    );
+    let targets = backend.targets.lock().unwrap();
+    assert_eq!(targets[1].session.as_deref(), Some("w0t0p0"));
+    assert!(targets[2].is_active());
+}
+
+#[tokio::test]
+async fn test_sessions_are_closed_renamed_and_badged_through_the_backend() {
+    let backend = Arc::new(RecordingBackend {
+        process: Some(ProcessInfo {
+            pid: 42,
+            name: "vim".to_string(),
+            command: "vim notes.txt".to_string(),
+            ppid: Some(1),
+            foreground: true,
+            metrics: None,
+        }),
+        capabilities: BackendCapabilities {
+            manage_sessions: true,
+            ..BackendCapabilities::default()
+        },
+        ..RecordingBackend::default()
+    });
+    let router = router_with(backend.clone());
+
+    // Sem force, uma sessão com um programa em execução não é fechada
+    let response = call(&router, "iterm-mcp:close_session", json!({ "session": "w0t0p1" })).await;
+    assert_eq!(response["result"]["isError"], true);
+    assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("`vim`"));
+    assert!(backend.closed.lock().unwrap().is_empty());
+
+    let response = call(&router, "iterm-mcp:close_session", json!({ "session": "w0t0p1", "force": true })).await;
+    assert_eq!(response["result"]["isError"], false);
+    assert_eq!(backend.closed.lock().unwrap()[0].session.as_deref(), Some("w0t0p1"));
+
+    let response = call(&router, "iterm-mcp:rename_session", json!({ "session": "w0t0p0" })).await;
+    assert_eq!(response["result"]["isError"], true);
+    let response = call(&router, "iterm-mcp:rename_session", json!({ "name": "agente", "tabTitle": "build" })).await;
+    assert_eq!(response["result"]["isError"], false);
+    let response = call(&router, "iterm-mcp:set_badge", json!({ "text": "agente-1", "session": "w0t0p0" })).await;
+    assert_eq!(response["result"]["isError"], false);
+
+    assert_eq!(
+        *backend.updates.lock().unwrap(),
+        vec![
+            SessionUpdate {
+                name: Some("agente".to_string()),
+                tab_title: Some("build".to_string()),
+                badge: None,
+            },
+            SessionUpdate {
+                badge: Some("agente-1".to_string()),
+                ..SessionUpdate::default()
+            },
+        ]
+    );
+    let targets = backend.targets.lock().unwrap();
+    assert!(targets[0].is_active());
+    assert_eq!(targets[1].session.as_deref(), Some("w0t0p0"));
+}
+
+#[tokio::test]
+async fn test_tools_end_to_end_on_pty_backend() {
+    let backend = Arc::new(PtyBackend::with_config(PtyConfig {
+        program: "/bin/sh".to_string(),
+        args: Vec::new(),
+        env: vec![("PS1".to_string(), "$ ".to_string())],
+        scrollback_lines: 100,
+        emulation: Emulation::Plain,
+    }));
+    let router = Router::new();
+    for (_, tool) in register_tools(backend.clone()) {
+        router.register(tool);
+    }
+
+    let response = call(&router, "iterm-mcp:write_to_terminal", json!({ "command": "echo pty-$((2 + 3))" })).await;
+    assert_eq!(response["result"]["isError"], false);
+    wait_for_line(&router, "pty-5").await;
+
+    call(&router, "iterm-mcp:write_to_terminal", json!({ "command": "cat" })).await;
+    wait_for_process(&backend, "cat").await;
+    let response = call(&router, "iterm-mcp:send_control_character", json!({ "letter": "C" })).await;
+    assert_eq!(response["result"]["isError"], false);
+    // O shell descarta o que foi digitado antes de voltar ao prompt
+    wait_for_process(&backend, "sh").await;
+    call(&router, "iterm-mcp:write_to_terminal", json!({ "command": "echo depois-$((1 + 1))" })).await;
+    wait_for_line(&router, "depois-2").await;
+}
+
+#[tokio::test]
+async fn test_tools_render_redraws_on_vt_backend() {
+    let backend = Arc::new(PtyBackend::with_config(PtyConfig {
+        program: "/bin/sh".to_string(),
+        args: Vec::new(),
+        env: vec![
+            ("TERM".to_string(), "xterm".to_string()),
+            ("PS1".to_string(), "$ ".to_string()),
+        ],
+        ..PtyConfig::vt()
+    }));
+    assert_eq!(backend.name(), "vt");
+    let router = Router::new();
+    for (_, tool) in register_tools(backend.clone()) {
+        router.register(tool);
+    }
+
+    // Uma barra de progresso redesenhada com \r e erase-line
+    let command = r"printf 'carregando 10%%\r\033[Kcarregando 100%%\n'";
+    call(&router, "iterm-mcp:write_to_terminal", json!({ "command": command })).await;
+    let screen = wait_for_line(&router, "carregando 100%").await;
+    assert!(screen.lines().any(|line| line == "carregando 100%"), "{}", screen);
+    assert!(!screen.contains('\x1b'), "{:?}", screen);
+
+    // A tela alternativa mostra só o que o programa desenhou
+    let command = r"printf '\033[?1049h\033[H\033[2Jtela cheia\n'; cat";
+    call(&router, "iterm-mcp:write_to_terminal", json!({ "command": command })).await;
+    let screen = wait_for_line(&router, "tela cheia").await;
+    assert_eq!(screen.trim_end(), "tela cheia");
+    wait_for_process(&backend, "cat").await;
+
+    // Control-C encerra o `cat`; ao sair da tela alternativa a tela principal volta
+    call(&router, "iterm-mcp:send_control_character", json!({ "letter": "C" })).await;
+    wait_for_process(&backend, "sh").await;
+    call(&router, "iterm-mcp:write_to_terminal", json!({ "command": r"printf '\033[?1049l'" })).await;
+    let started = Instant::now();
+    let screen = loop {
+        let screen = read_output(&router, 20).await;
+        if !screen.lines().any(|line| line == "tela cheia") {
+            break screen;
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "tela alternativa não saiu:\n{}", screen);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    assert!(screen.lines().any(|line| line == "carregando 100%"), "{}", screen);
+}
@@ -1,202 +1,709 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::Result;
-use serde_json::json;
-use tokio::sync::Mutex;
-use tracing::{debug, info};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 
-use crate::mcp::iterm::{
-    command_executor::CommandExecutor,
-    control_char::ControlCharacterSender,
-    tty_reader::TtyReader,
+use crate::mcp::backend::{
+    is_shell, ControlKey, NewSession, SessionLayout, SessionUpdate, TerminalBackend, TerminalSession,
 };
+use crate::mcp::jsonrpc::JsonRpcNotification;
+use crate::mcp::schema::schema_for;
+use crate::mcp::session::Outgoing;
 use crate::mcp::types::{
-    ReadTerminalOutputParams, ReadTerminalOutputResponse, SendControlCharacterParams,
-    SendControlCharacterResponse, ToolDefinition, WriteToTerminalParams, WriteToTerminalResponse,
+    CloseSessionParams, CreateSessionResponse, CreateTabParams, CreateWindowParams, ListSessionsParams,
+    ListSessionsResponse, ManageSessionResponse, ProgressParams, ProgressToken, ReadTerminalOutputParams,
+    ReadTerminalOutputResponse, RenameSessionParams, SendControlCharacterParams, SendControlCharacterResponse,
+    SessionInfo, SetBadgeParams, SplitDirection, SplitPaneParams, TabInfo, TerminalTarget, ToolDefinition,
+    WindowInfo, WriteToTerminalParams, WriteToTerminalResponse,
 };
 
-pub type ToolHandler = Arc<dyn Fn(serde_json::Value) -> Result<serde_json::Value> + Send + Sync>;
-
-/// Registra todas as ferramentas MCP do iTerm
-pub fn register_tools() -> HashMap<String, (ToolDefinition, ToolHandler)> {
-    let mut tools = HashMap::new();
-    
-    // Registra a ferramenta write_to_terminal
-    register_write_to_terminal(&mut tools);
-    
-    // Registra a ferramenta read_terminal_output
-    register_read_terminal_output(&mut tools);
-    
-    // Registra a ferramenta send_control_character
-    register_send_control_character(&mut tools);
-    
-    info!("Ferramentas MCP do iTerm registradas com sucesso: {}", tools.keys().len());
+/// Contexto de execução de uma chamada de ferramenta
+///
+/// Carrega o token de cancelamento da requisição; handlers de operações longas
+/// devem repassá-lo (ex.: para o runner do osascript) ou consultá-lo com
+/// `is_cancelled`. Quando o cliente envia `_meta.progressToken`, o contexto
+/// também permite reportar progresso com `report_progress`.
+#[derive(Debug, Clone, Default)]
+pub struct ToolContext {
+    cancellation: CancellationToken,
+    progress: Option<ProgressReporter>,
+}
+
+/// Destino das notificações de progresso de uma chamada
+#[derive(Debug, Clone)]
+struct ProgressReporter {
+    token: ProgressToken,
+    outgoing: Outgoing,
+}
+
+impl ToolContext {
+    /// Cria um contexto ligado ao token de cancelamento da requisição
+    pub fn new(cancellation: CancellationToken) -> Self {
+        ToolContext {
+            cancellation,
+            progress: None,
+        }
+    }
+
+    /// Envia notificações de progresso com o token informado pelo canal da sessão
+    pub fn with_progress(mut self, token: ProgressToken, outgoing: Outgoing) -> Self {
+        self.progress = Some(ProgressReporter { token, outgoing });
+        self
+    }
+
+    /// Token cancelado quando o cliente envia `notifications/cancelled`
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Indica se o cliente cancelou a requisição
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Indica se o cliente pediu notificações de progresso
+    pub fn wants_progress(&self) -> bool {
+        self.progress.is_some()
+    }
+
+    /// Envia um `notifications/progress` ao cliente
+    ///
+    /// `progress` deve crescer a cada chamada. Retorna `false` (sem erro) se o
+    /// cliente não pediu progresso, se a requisição foi cancelada ou se o canal
+    /// já foi fechado.
+    pub fn report_progress(&self, progress: f64, total: Option<f64>, message: Option<&str>) -> bool {
+        let reporter = match &self.progress {
+            Some(reporter) if !self.is_cancelled() => reporter,
+            _ => return false,
+        };
+
+        let params = ProgressParams {
+            progress_token: reporter.token.clone(),
+            progress,
+            total,
+            message: message.map(str::to_string),
+        };
+        let notification = JsonRpcNotification::new("notifications/progress", json!(params));
+
+        match serde_json::to_string(&notification) {
+            Ok(message) => reporter.outgoing.send(message).is_ok(),
+            Err(e) => {
+                warn!("Erro ao serializar notificação de progresso: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// Ferramenta MCP
+///
+/// Cada ferramenta expõe seu nome, descrição e esquema de parâmetros, e
+/// executa de forma assíncrona; o router aguarda `call` sem segurar o lock do
+/// registro de ferramentas, de modo que chamadas concorrentes executam em
+/// paralelo.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    /// Nome único da ferramenta (ex.: `iterm-mcp:write_to_terminal`)
+    fn name(&self) -> &str;
+
+    /// Descrição exibida ao cliente em `tools/list`
+    fn description(&self) -> &str;
+
+    /// Esquema JSON dos argumentos, validado pelo router antes de `call`
+    ///
+    /// Ferramentas com parâmetros tipados devem gerá-lo com
+    /// `schema::schema_for::<Params>()`.
+    fn input_schema(&self) -> Value;
+
+    /// Definição anunciada em `tools/list`
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.name().to_string(),
+            description: self.description().to_string(),
+            parameters: serde_json::from_value(self.input_schema()).unwrap_or_default(),
+        }
+    }
+
+    /// Executa a ferramenta com os argumentos recebidos em `tools/call`
+    async fn call(&self, ctx: ToolContext, arguments: Value) -> Result<Value>;
+}
+
+/// Handler síncrono, executado no pool de threads bloqueantes
+pub type BlockingHandler = Arc<dyn Fn(ToolContext, Value) -> Result<Value> + Send + Sync>;
+
+/// Adapta um handler síncrono à trait `Tool`
+///
+/// Útil para ferramentas simples ou que fazem I/O bloqueante; o handler roda
+/// em `spawn_blocking` e deve consultar `ToolContext::is_cancelled` se demorar.
+pub struct BlockingTool {
+    definition: ToolDefinition,
+    handler: BlockingHandler,
+}
+
+impl BlockingTool {
+    /// Cria a ferramenta a partir da definição e do handler
+    pub fn new(definition: ToolDefinition, handler: BlockingHandler) -> Self {
+        BlockingTool { definition, handler }
+    }
+}
+
+#[async_trait]
+impl Tool for BlockingTool {
+    fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    fn description(&self) -> &str {
+        &self.definition.description
+    }
+
+    fn input_schema(&self) -> Value {
+        json!(self.definition.parameters)
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    async fn call(&self, ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let handler = self.handler.clone();
+        tokio::task::spawn_blocking(move || handler(ctx, arguments)).await?
+    }
+}
+
+/// Registra as ferramentas MCP do iTerm sobre o backend informado, indexadas pelo nome
+///
+/// Ferramentas que dependem de operações que o backend não suporta ficam de
+/// fora, para não aparecerem em `tools/list` e falharem só na chamada.
+pub fn register_tools(backend: Arc<dyn TerminalBackend>) -> HashMap<String, Arc<dyn Tool>> {
+    let capabilities = backend.capabilities();
+    info!("Backend de terminal: {} ({:?})", backend.name(), capabilities);
+
+    let mut tools: Vec<Arc<dyn Tool>> = Vec::new();
+    if capabilities.write_text {
+        tools.push(Arc::new(WriteToTerminalTool::new(backend.clone())));
+    }
+    if capabilities.read_screen {
+        tools.push(Arc::new(ReadTerminalOutputTool::new(backend.clone())));
+    }
+    if capabilities.send_control {
+        tools.push(Arc::new(SendControlCharacterTool::new(backend.clone())));
+    }
+    if capabilities.list_sessions {
+        tools.push(Arc::new(ListSessionsTool::new(backend.clone())));
+    }
+    if capabilities.create_session {
+        tools.push(Arc::new(CreateWindowTool::new(backend.clone())));
+        tools.push(Arc::new(CreateTabTool::new(backend.clone())));
+        tools.push(Arc::new(SplitPaneTool::new(backend.clone())));
+    }
+    if capabilities.manage_sessions {
+        tools.push(Arc::new(CloseSessionTool::new(backend.clone())));
+        tools.push(Arc::new(RenameSessionTool::new(backend.clone())));
+        tools.push(Arc::new(SetBadgeTool::new(backend)));
+    }
+
+    let tools: HashMap<String, Arc<dyn Tool>> = tools
+        .into_iter()
+        .map(|tool| (tool.name().to_string(), tool))
+        .collect();
+
+    info!("Ferramentas MCP do iTerm registradas com sucesso: {}", tools.len());
     tools
 }
 
-/// Registra a ferramenta write_to_terminal
-fn register_write_to_terminal(tools: &mut HashMap<String, (ToolDefinition, ToolHandler)>) {
-    let tool_name = "iterm-mcp:write_to_terminal".to_string();
-    
-    let schema = json!({
-        "properties": {
-            "command": {
-                "type": "string",
-                "description": "O comando a ser executado ou texto a ser escrito no terminal"
+/// Intervalo entre as notificações de progresso enquanto o terminal não aceita
+/// todo o comando
+const WRITE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Ferramenta write_to_terminal
+pub struct WriteToTerminalTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl WriteToTerminalTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        WriteToTerminalTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for WriteToTerminalTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:write_to_terminal"
+    }
+
+    fn description(&self) -> &str {
+        "Escreve texto no terminal iTerm ativo - frequentemente usado para executar um comando no terminal"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<WriteToTerminalParams>()
+    }
+
+    async fn call(&self, ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: WriteToTerminalParams = serde_json::from_value(arguments)?;
+
+        debug!("Executando comando no terminal: {}", params.command);
+
+        ctx.report_progress(0.0, None, Some("Enviando comando ao terminal"));
+
+        // Um terminal que não lê a entrada atrasa a escrita; o cliente recebe
+        // um progresso a cada segundo para saber que a chamada segue viva
+        let write = self.backend.write_text(&params.target, &params.command, ctx.cancellation());
+        tokio::pin!(write);
+        let start = tokio::time::Instant::now() + WRITE_HEARTBEAT_INTERVAL;
+        let mut heartbeat = tokio::time::interval_at(start, WRITE_HEARTBEAT_INTERVAL);
+        let mut seconds = 0;
+        let written = loop {
+            tokio::select! {
+                written = &mut write => break written,
+                _ = heartbeat.tick() => {
+                    seconds += 1;
+                    let message = format!("Aguardando o terminal há {}s", seconds);
+                    ctx.report_progress(seconds as f64, None, Some(&message));
+                }
             }
-        },
-        "required": ["command"],
-        "type": "object"
-    });
-    
-    let tool_def = ToolDefinition {
-        name: tool_name.clone(),
-        description: "Escreve texto no terminal iTerm ativo - frequentemente usado para executar um comando no terminal".to_string(),
-        parameters: serde_json::from_value(schema).unwrap(),
-    };
-    
-    // Cria um executor de comandos compartilhado
-    let executor = Arc::new(Mutex::new(CommandExecutor::new()));
-    
-    let handler: ToolHandler = Arc::new(move |params| {
-        let executor = executor.clone();
-        
-        // Clone para usar dentro do bloco async
-        let params_clone = params.clone();
-        
-        // Executar de forma síncrona (conversão para async será feita mais tarde)
-        let result = tokio::task::block_in_place(move || {
-            let rt = tokio::runtime::Handle::current();
-            
-            rt.block_on(async move {
-                let params: WriteToTerminalParams = serde_json::from_value(params_clone)?;
-                
-                debug!("Executando comando no terminal: {}", params.command);
-                
-                let mut executor = executor.lock().await;
-                executor.execute_command(&params.command).await?;
-                
-                Ok(json!(WriteToTerminalResponse {
-                    success: true,
-                    error: None,
-                    data: None,
-                }))
-            })
-        });
-        
-        result
-    });
-    
-    tools.insert(tool_name, (tool_def, handler));
-}
-
-/// Registra a ferramenta read_terminal_output
-fn register_read_terminal_output(tools: &mut HashMap<String, (ToolDefinition, ToolHandler)>) {
-    let tool_name = "iterm-mcp:read_terminal_output".to_string();
-    
-    let schema = json!({
-        "properties": {
-            "linesOfOutput": {
-                "type": "integer",
-                "description": "O número de linhas de saída a serem lidas"
+        };
+        written.context("Falha ao escrever no terminal")?;
+        let done = (seconds + 1) as f64;
+        ctx.report_progress(done, Some(done), Some("Comando concluído"));
+
+        Ok(json!(WriteToTerminalResponse {
+            success: true,
+            error: None,
+            data: None,
+        }))
+    }
+}
+
+/// Ferramenta read_terminal_output
+pub struct ReadTerminalOutputTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl ReadTerminalOutputTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        ReadTerminalOutputTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadTerminalOutputTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:read_terminal_output"
+    }
+
+    fn description(&self) -> &str {
+        "Lê a saída do terminal iTerm ativo"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<ReadTerminalOutputParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: ReadTerminalOutputParams = serde_json::from_value(arguments)?;
+
+        debug!("Lendo {} linhas de saída do terminal", params.lines_of_output);
+
+        let output = self.backend.read_screen(&params.target, params.lines_of_output as usize).await?;
+
+        Ok(json!(ReadTerminalOutputResponse { output }))
+    }
+}
+
+/// Ferramenta send_control_character
+pub struct SendControlCharacterTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl SendControlCharacterTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        SendControlCharacterTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for SendControlCharacterTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:send_control_character"
+    }
+
+    fn description(&self) -> &str {
+        "Envia um caractere de controle para o terminal iTerm ativo (ex: Control-C, ou sequências especiais como ']' para telnet escape)"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<SendControlCharacterParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: SendControlCharacterParams = serde_json::from_value(arguments)?;
+
+        debug!("Enviando caractere de controle: {}", params.letter);
+
+        let key = ControlKey::parse(&params.letter)?;
+        self.backend.send_control(&params.target, key).await?;
+
+        Ok(json!(SendControlCharacterResponse {
+            success: true,
+            error: None,
+            data: None,
+        }))
+    }
+}
+
+/// Ferramenta list_sessions
+pub struct ListSessionsTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl ListSessionsTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        ListSessionsTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for ListSessionsTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:list_sessions"
+    }
+
+    fn description(&self) -> &str {
+        "Lista as janelas, abas e sessões do terminal, com ids únicos, nomes, TTYs, tamanho e qual é a sessão atual"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<ListSessionsParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, _arguments: Value) -> Result<Value> {
+        debug!("Listando as sessões do terminal");
+
+        let sessions = self
+            .backend
+            .list_sessions()
+            .await
+            .context("Falha ao listar as sessões do terminal")?;
+
+        Ok(json!(session_tree(sessions)))
+    }
+}
+
+/// Agrupa as sessões por janela e aba, preservando a ordem do backend
+fn session_tree(sessions: Vec<TerminalSession>) -> ListSessionsResponse {
+    let mut tree = ListSessionsResponse::default();
+    for session in sessions {
+        let window = match tree.windows.iter().position(|window| window.id == session.window) {
+            Some(index) => &mut tree.windows[index],
+            None => {
+                tree.windows.push(WindowInfo {
+                    id: session.window.clone(),
+                    current: false,
+                    tabs: Vec::new(),
+                });
+                tree.windows.last_mut().expect("janela recém-inserida")
             }
-        },
-        "required": ["linesOfOutput"],
-        "type": "object"
-    });
-    
-    let tool_def = ToolDefinition {
-        name: tool_name.clone(),
-        description: "Lê a saída do terminal iTerm ativo".to_string(),
-        parameters: serde_json::from_value(schema).unwrap(),
-    };
-    
-    // Cria um leitor TTY compartilhado
-    let reader = Arc::new(Mutex::new(TtyReader::new()));
-    
-    let handler: ToolHandler = Arc::new(move |params| {
-        let reader = reader.clone();
-        
-        // Clone para usar dentro do bloco async
-        let params_clone = params.clone();
-        
-        // Executar de forma síncrona (conversão para async será feita mais tarde)
-        let result = tokio::task::block_in_place(move || {
-            let rt = tokio::runtime::Handle::current();
-            
-            rt.block_on(async move {
-                let params: ReadTerminalOutputParams = serde_json::from_value(params_clone)?;
-                
-                debug!("Lendo {} linhas de saída do terminal", params.lines_of_output);
-                
-                let mut reader = reader.lock().await;
-                let output = reader.read_lines(params.lines_of_output as usize).await?;
-                
-                Ok(json!({
-                    "output": output
-                }))
-            })
-        });
-        
-        result
-    });
-    
-    tools.insert(tool_name, (tool_def, handler));
-}
-
-/// Registra a ferramenta send_control_character
-fn register_send_control_character(tools: &mut HashMap<String, (ToolDefinition, ToolHandler)>) {
-    let tool_name = "iterm-mcp:send_control_character".to_string();
-    
-    let schema = json!({
-        "properties": {
-            "letter": {
-                "type": "string",
-                "description": "A letra correspondente ao caractere de controle (ex: 'C' para Control-C, ']' para telnet escape)"
+        };
+        window.current |= session.active;
+
+        let tab = match window.tabs.iter().position(|tab| tab.id == session.tab) {
+            Some(index) => &mut window.tabs[index],
+            None => {
+                window.tabs.push(TabInfo {
+                    id: session.tab.clone(),
+                    current: false,
+                    sessions: Vec::new(),
+                });
+                window.tabs.last_mut().expect("aba recém-inserida")
             }
-        },
-        "required": ["letter"],
-        "type": "object"
-    });
-    
-    let tool_def = ToolDefinition {
-        name: tool_name.clone(),
-        description: "Envia um caractere de controle para o terminal iTerm ativo (ex: Control-C, ou sequências especiais como ']' para telnet escape)".to_string(),
-        parameters: serde_json::from_value(schema).unwrap(),
-    };
-    
-    // Cria um sender de caracteres de controle compartilhado
-    let control_sender = Arc::new(Mutex::new(ControlCharacterSender::new()));
-    
-    let handler: ToolHandler = Arc::new(move |params| {
-        let control_sender = control_sender.clone();
-        
-        // Clone para usar dentro do bloco async
-        let params_clone = params.clone();
-        
-        // Executar de forma síncrona (conversão para async será feita mais tarde)
-        let result = tokio::task::block_in_place(move || {
-            let rt = tokio::runtime::Handle::current();
-            
-            rt.block_on(async move {
-                let params: SendControlCharacterParams = serde_json::from_value(params_clone)?;
-                
-                debug!("Enviando caractere de controle: {}", params.letter);
-                
-                let mut sender = control_sender.lock().await;
-                sender.send_control_character(&params.letter).await?;
-                
-                Ok(json!(SendControlCharacterResponse {
-                    success: true,
-                    error: None,
-                    data: None,
-                }))
-            })
+        };
+        tab.current |= session.active;
+
+        tab.sessions.push(SessionInfo {
+            id: session.id,
+            name: session.name,
+            tty: session.tty,
+            columns: session.columns,
+            rows: session.rows,
+            current: session.active,
         });
-        
-        result
-    });
-    
-    tools.insert(tool_name, (tool_def, handler));
+    }
+    tree
+}
+
+/// Cria a sessão pedida e responde com o id dela
+async fn create_session(backend: &dyn TerminalBackend, target: &TerminalTarget, new: NewSession) -> Result<Value> {
+    debug!("Criando sessão: {:?}", new);
+
+    let session_id = backend
+        .create_session(target, &new)
+        .await
+        .context("Falha ao criar a sessão")?;
+
+    Ok(json!(CreateSessionResponse { session_id }))
+}
+
+/// Ferramenta create_window
+pub struct CreateWindowTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl CreateWindowTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        CreateWindowTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for CreateWindowTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:create_window"
+    }
+
+    fn description(&self) -> &str {
+        "Abre uma nova janela do terminal, opcionalmente com um perfil e um comando inicial, e retorna o id da nova sessão"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<CreateWindowParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: CreateWindowParams = serde_json::from_value(arguments)?;
+        let new = NewSession {
+            layout: SessionLayout::Window,
+            profile: params.profile,
+            command: params.command,
+        };
+        create_session(self.backend.as_ref(), &TerminalTarget::default(), new).await
+    }
+}
+
+/// Ferramenta create_tab
+pub struct CreateTabTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl CreateTabTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        CreateTabTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for CreateTabTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:create_tab"
+    }
+
+    fn description(&self) -> &str {
+        "Abre uma nova aba na janela da sessão alvo (ou da atual), opcionalmente com um perfil e um comando inicial, e retorna o id da nova sessão"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<CreateTabParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: CreateTabParams = serde_json::from_value(arguments)?;
+        let new = NewSession {
+            layout: SessionLayout::Tab,
+            profile: params.profile,
+            command: params.command,
+        };
+        create_session(self.backend.as_ref(), &params.target, new).await
+    }
+}
+
+/// Ferramenta split_pane
+pub struct SplitPaneTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl SplitPaneTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        SplitPaneTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for SplitPaneTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:split_pane"
+    }
+
+    fn description(&self) -> &str {
+        "Divide o painel da sessão alvo (ou da atual) na vertical ou na horizontal, opcionalmente com um perfil e um comando inicial, e retorna o id da nova sessão"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<SplitPaneParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: SplitPaneParams = serde_json::from_value(arguments)?;
+        let new = NewSession {
+            layout: SessionLayout::Split {
+                vertical: params.direction == SplitDirection::Vertical,
+            },
+            profile: params.profile,
+            command: params.command,
+        };
+        create_session(self.backend.as_ref(), &params.target, new).await
+    }
+}
+
+/// Aplica a alteração pedida à sessão alvo
+async fn update_session(backend: &dyn TerminalBackend, target: &TerminalTarget, update: SessionUpdate) -> Result<Value> {
+    debug!("Alterando sessão: {:?}", update);
+
+    backend
+        .update_session(target, &update)
+        .await
+        .context("Falha ao alterar a sessão")?;
+
+    Ok(json!(ManageSessionResponse {
+        success: true,
+        error: None,
+        data: None,
+    }))
+}
+
+/// Ferramenta close_session
+pub struct CloseSessionTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl CloseSessionTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        CloseSessionTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for CloseSessionTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:close_session"
+    }
+
+    fn description(&self) -> &str {
+        "Fecha a sessão alvo (ou a atual); recusa se um programa além do shell estiver em execução, a menos que force seja verdadeiro"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<CloseSessionParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: CloseSessionParams = serde_json::from_value(arguments)?;
+
+        if !params.force {
+            let process = self
+                .backend
+                .query_process(&params.target)
+                .await
+                .context("Falha ao consultar o processo da sessão")?;
+            if let Some(process) = process.filter(|process| !is_shell(&process.name)) {
+                return Err(anyhow!(
+                    "A sessão está executando `{}`; use force para fechá-la mesmo assim",
+                    process.name
+                ));
+            }
+        }
+
+        debug!("Fechando sessão: {:?}", params.target);
+        self.backend
+            .close_session(&params.target)
+            .await
+            .context("Falha ao fechar a sessão")?;
+
+        Ok(json!(ManageSessionResponse {
+            success: true,
+            error: None,
+            data: None,
+        }))
+    }
+}
+
+/// Ferramenta rename_session
+pub struct RenameSessionTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl RenameSessionTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        RenameSessionTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for RenameSessionTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:rename_session"
+    }
+
+    fn description(&self) -> &str {
+        "Renomeia a sessão alvo (ou a atual) e/ou define o título da aba que a contém"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<RenameSessionParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: RenameSessionParams = serde_json::from_value(arguments)?;
+        if params.name.is_none() && params.tab_title.is_none() {
+            return Err(anyhow!("Informe name, tabTitle ou ambos"));
+        }
+
+        let update = SessionUpdate {
+            name: params.name,
+            tab_title: params.tab_title,
+            badge: None,
+        };
+        update_session(self.backend.as_ref(), &params.target, update).await
+    }
+}
+
+/// Ferramenta set_badge
+pub struct SetBadgeTool {
+    backend: Arc<dyn TerminalBackend>,
+}
+
+impl SetBadgeTool {
+    pub fn new(backend: Arc<dyn TerminalBackend>) -> Self {
+        SetBadgeTool { backend }
+    }
+}
+
+#[async_trait]
+impl Tool for SetBadgeTool {
+    fn name(&self) -> &str {
+        "iterm-mcp:set_badge"
+    }
+
+    fn description(&self) -> &str {
+        "Define o badge da sessão alvo (ou da atual), o texto exibido sobre o painel - útil para indicar qual agente o usa"
+    }
+
+    fn input_schema(&self) -> Value {
+        schema_for::<SetBadgeParams>()
+    }
+
+    async fn call(&self, _ctx: ToolContext, arguments: Value) -> Result<Value> {
+        let params: SetBadgeParams = serde_json::from_value(arguments)?;
+        let update = SessionUpdate {
+            badge: Some(params.text),
+            ..SessionUpdate::default()
+        };
+        update_session(self.backend.as_ref(), &params.target, update).await
+    }
 }
@@ -1,25 +1,162 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::mcp::backend::kitty::KittyConfig;
+use crate::mcp::backend::BackendCapabilities;
+use crate::mcp::backend::screen::ScreenConfig;
+use crate::mcp::backend::tmux::TmuxConfig;
+use crate::mcp::backend::wezterm::WeztermConfig;
+use crate::mcp::framing::DEFAULT_MAX_MESSAGE_SIZE;
+use crate::mcp::iterm::api::ApiConfig;
+use crate::mcp::jsonrpc::RequestId;
 
 /// Parâmetros para escrever no terminal
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WriteToTerminalParams {
     /// O comando ou texto a ser escrito no terminal
     pub command: String,
+
+    #[serde(flatten)]
+    pub target: TerminalTarget,
 }
 
 /// Parâmetros para ler a saída do terminal
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct ReadTerminalOutputParams {
     /// O número de linhas de saída a serem lidas
     pub lines_of_output: u32,
+
+    #[serde(flatten)]
+    pub target: TerminalTarget,
 }
 
 /// Parâmetros para enviar um caractere de controle para o terminal
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SendControlCharacterParams {
     /// A letra correspondente ao caractere de controle (ex: 'C' para Control-C)
     pub letter: String,
+
+    #[serde(flatten)]
+    pub target: TerminalTarget,
+}
+
+/// Parâmetros para criar uma janela
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CreateWindowParams {
+    /// Perfil do terminal; o perfil padrão se omitido
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// Comando digitado na nova sessão
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+}
+
+/// Parâmetros para criar uma aba na janela da sessão alvo
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CreateTabParams {
+    /// Perfil do terminal; o perfil padrão se omitido
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// Comando digitado na nova sessão
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+
+    #[serde(flatten)]
+    pub target: TerminalTarget,
+}
+
+/// Direção da divisão de um painel: `vertical` deixa os painéis lado a lado,
+/// `horizontal` um sobre o outro
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+#[schemars(inline)]
+pub enum SplitDirection {
+    Vertical,
+    Horizontal,
+}
+
+/// Parâmetros para dividir o painel da sessão alvo
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SplitPaneParams {
+    /// Direção da divisão
+    pub direction: SplitDirection,
+
+    /// Perfil do terminal; o perfil padrão se omitido
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// Comando digitado na nova sessão
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+
+    #[serde(flatten)]
+    pub target: TerminalTarget,
+}
+
+/// Parâmetros para fechar a sessão alvo
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CloseSessionParams {
+    /// Fecha a sessão mesmo que um programa além do shell esteja em execução nela
+    #[serde(default)]
+    pub force: bool,
+
+    #[serde(flatten)]
+    pub target: TerminalTarget,
+}
+
+/// Parâmetros para renomear a sessão alvo e/ou a aba que a contém
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameSessionParams {
+    /// Novo nome da sessão
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Novo título da aba
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tab_title: Option<String>,
+
+    #[serde(flatten)]
+    pub target: TerminalTarget,
+}
+
+/// Parâmetros para definir o badge da sessão alvo
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct SetBadgeParams {
+    /// Texto exibido sobre a sessão; vazio remove o badge
+    pub text: String,
+
+    #[serde(flatten)]
+    pub target: TerminalTarget,
+}
+
+/// Parâmetros para listar as sessões do terminal (nenhum)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ListSessionsParams {}
+
+/// Sessão e janela em que uma ferramenta age; omitidas, vale a sessão ativa do backend
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TerminalTarget {
+    /// Sessão alvo (no iTerm2: id único, caminho do TTY ou padrão de nome com `*`/`?`;
+    /// nome da sessão do screen/tmux, id `pty-N`, ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+
+    /// Janela alvo dentro da sessão (número ou título)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<String>,
+}
+
+impl TerminalTarget {
+    /// Se nenhuma sessão ou janela foi escolhida
+    pub fn is_active(&self) -> bool {
+        self.session.is_none() && self.window.is_none()
+    }
 }
 
 /// Informações sobre um processo em execução
@@ -83,6 +220,75 @@ pub struct ReadTerminalOutputResponse {
 /// Tipo de resposta para o comando send_control_character
 pub type SendControlCharacterResponse = McpResponse<()>;
 
+/// Tipo de resposta para as ferramentas close_session, rename_session e set_badge
+pub type ManageSessionResponse = McpResponse<()>;
+
+/// Resposta das ferramentas que criam janelas, abas e painéis
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSessionResponse {
+    /// Id da nova sessão, aceito no parâmetro `session` das demais ferramentas
+    pub session_id: String,
+}
+
+/// Resposta do list_sessions: árvore de janelas → abas → sessões
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListSessionsResponse {
+    /// Janelas na ordem informada pelo backend
+    pub windows: Vec<WindowInfo>,
+}
+
+/// Janela do terminal; `id` é nulo em backends sem janelas
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowInfo {
+    /// Identificador da janela
+    pub id: Option<String>,
+
+    /// Indica se a janela contém a sessão em que as ferramentas agem
+    pub current: bool,
+
+    /// Abas da janela
+    pub tabs: Vec<TabInfo>,
+}
+
+/// Aba de uma janela; `id` é nulo em backends sem abas
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabInfo {
+    /// Identificador da aba
+    pub id: Option<String>,
+
+    /// Indica se a aba contém a sessão em que as ferramentas agem
+    pub current: bool,
+
+    /// Sessões (painéis) da aba
+    pub sessions: Vec<SessionInfo>,
+}
+
+/// Sessão de uma aba
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    /// Identificador único, aceito no parâmetro `session` das ferramentas
+    pub id: String,
+
+    /// Nome ou título da sessão
+    pub name: String,
+
+    /// Dispositivo TTY da sessão, se conhecido
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tty: Option<String>,
+
+    /// Largura em colunas, se conhecida
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<u16>,
+
+    /// Altura em linhas, se conhecida
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u16>,
+
+    /// Indica se é a sessão em que as ferramentas agem
+    pub current: bool,
+}
+
 /// Definição de uma ferramenta MCP
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolDefinition {
@@ -92,19 +298,321 @@ pub struct ToolDefinition {
     /// Descrição da ferramenta
     pub description: String,
     
-    /// Esquema de parâmetros em formato JSON Schema
+    /// Esquema de parâmetros em formato JSON Schema (exposto como `inputSchema` no MCP)
+    #[serde(rename = "inputSchema", alias = "parameters")]
     pub parameters: HashMap<String, serde_json::Value>,
 }
 
+/// Versão mais recente do protocolo MCP implementada pelo servidor
+pub const MCP_PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Versões do protocolo MCP aceitas na negociação do `initialize`
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
+/// Parâmetros da requisição `initialize`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeParams {
+    /// Versão do protocolo solicitada pelo cliente
+    #[serde(default)]
+    pub protocol_version: Option<String>,
+
+    /// Capacidades declaradas pelo cliente
+    #[serde(default)]
+    pub capabilities: serde_json::Value,
+
+    /// Identificação do cliente
+    #[serde(default)]
+    pub client_info: Option<Implementation>,
+}
+
+/// Nome e versão de um cliente ou servidor MCP
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Implementation {
+    /// Nome da implementação
+    pub name: String,
+
+    /// Versão da implementação
+    pub version: String,
+}
+
+/// Resultado da requisição `initialize`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    /// Versão do protocolo negociada
+    pub protocol_version: String,
+
+    /// Capacidades oferecidas pelo servidor
+    pub capabilities: ServerCapabilities,
+
+    /// Identificação do servidor
+    pub server_info: Implementation,
+}
+
+/// Capacidades oferecidas pelo servidor
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerCapabilities {
+    /// Suporte a ferramentas
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<ToolsCapability>,
+
+    /// Capacidades fora da especificação; `terminalBackend` descreve o backend ativo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub experimental: Option<serde_json::Value>,
+}
+
+/// Backend de terminal ativo, informado no `initialize`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TerminalBackendInfo {
+    /// Nome curto do backend (`tmux`, `pty`, ...)
+    pub name: String,
+
+    /// Operações e seleções suportadas
+    pub capabilities: BackendCapabilities,
+}
+
+/// Capacidade de ferramentas
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolsCapability {
+    /// Se o servidor notifica mudanças na lista de ferramentas
+    pub list_changed: bool,
+}
+
+/// Parâmetros da requisição `tools/call`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallToolParams {
+    /// Nome da ferramenta a ser invocada
+    pub name: String,
+
+    /// Argumentos da ferramenta
+    #[serde(default)]
+    pub arguments: Option<serde_json::Value>,
+
+    /// Metadados da requisição (ex.: `progressToken`)
+    #[serde(rename = "_meta", default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<RequestMeta>,
+}
+
+/// Metadados `_meta` enviados pelo cliente junto aos parâmetros
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMeta {
+    /// Token que associa notificações de progresso à requisição
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress_token: Option<ProgressToken>,
+}
+
+/// Token de progresso escolhido pelo cliente: string ou número
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProgressToken {
+    /// Token numérico
+    Number(i64),
+    /// Token em string
+    String(String),
+}
+
+/// Parâmetros de `notifications/progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressParams {
+    /// Token recebido em `_meta.progressToken`
+    pub progress_token: ProgressToken,
+
+    /// Progresso atual (deve crescer a cada notificação)
+    pub progress: f64,
+
+    /// Total esperado, se conhecido
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<f64>,
+
+    /// Descrição legível da etapa atual
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Parâmetros de `notifications/cancelled`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelledParams {
+    /// ID da requisição a cancelar
+    pub request_id: RequestId,
+
+    /// Motivo informado pelo cliente (opcional)
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Conteúdo retornado por uma ferramenta
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ToolContent {
+    /// Conteúdo textual
+    Text {
+        /// Texto do conteúdo
+        text: String,
+    },
+}
+
+/// Resultado da requisição `tools/call`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallToolResult {
+    /// Conteúdo produzido pela ferramenta
+    pub content: Vec<ToolContent>,
+
+    /// Indica se a execução da ferramenta falhou
+    pub is_error: bool,
+}
+
+impl CallToolResult {
+    /// Cria um resultado de sucesso a partir do valor retornado pelo handler
+    pub fn success(value: serde_json::Value) -> Self {
+        let text = match value {
+            serde_json::Value::String(text) => text,
+            other => other.to_string(),
+        };
+
+        CallToolResult {
+            content: vec![ToolContent::Text { text }],
+            is_error: false,
+        }
+    }
+
+    /// Cria um resultado de erro com a mensagem informada
+    pub fn error(message: impl Into<String>) -> Self {
+        CallToolResult {
+            content: vec![ToolContent::Text { text: message.into() }],
+            is_error: true,
+        }
+    }
+}
+
+/// Transporte usado para receber mensagens MCP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    /// Listener TCP em `address:port`
+    #[default]
+    Tcp,
+    /// Mensagens delimitadas por newline em stdin/stdout
+    Stdio,
+    /// Socket Unix em `unix_socket`, acessível apenas pelo dono
+    Unix,
+    /// MCP "Streamable HTTP" (POST + SSE) em `address:port`
+    Http,
+}
+
+/// Backend de terminal usado pelas ferramentas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Detecta o terminal em que o servidor foi iniciado (tmux, screen,
+    /// kitty, WezTerm, iTerm2 em execução); PTY quando nenhum é encontrado
+    #[default]
+    Auto,
+    /// iTerm2 via AppleScript (apenas macOS)
+    ITerm2,
+    /// iTerm2 via API websocket/protobuf (a mesma da API Python)
+    ITerm2Api,
+    /// Shells próprios em pseudo-terminais
+    Pty,
+    /// Shells próprios com a saída interpretada por um emulador VT100/xterm
+    Vt,
+    /// Painel de um servidor tmux
+    Tmux,
+    /// Janela de uma sessão do GNU screen
+    Screen,
+    /// Painel do WezTerm via `wezterm cli`
+    Wezterm,
+    /// Janela do kitty via controle remoto (`kitty @`)
+    Kitty,
+}
+
+/// Enquadramento das mensagens nos transportes de stream (TCP, Unix, stdio)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// Detecta o formato pela primeira mensagem da conexão
+    #[default]
+    Auto,
+    /// Um JSON por linha, terminado por `\n`
+    Newline,
+    /// Cabeçalho `Content-Length: N` seguido de `N` bytes (estilo LSP)
+    ContentLength,
+}
+
 /// Configuração do servidor MCP
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
+    /// Transporte das mensagens
+    pub transport: Transport,
+
     /// Endereço para bind
     pub address: String,
     
     /// Porta para escutar
     pub port: u16,
+
+    /// Caminho do socket Unix (transporte `Unix`)
+    pub unix_socket: Option<PathBuf>,
+
+    /// Recusa conexões Unix de usuários diferentes do dono do servidor
+    pub require_same_uid: bool,
     
     /// Nível de log
     pub log_level: String,
+
+    /// Aceita o envelope legado `{id, function, arguments}` em vez de JSON-RPC 2.0
+    pub legacy_protocol: bool,
+
+    /// Enquadramento das mensagens nos transportes de stream
+    pub framing: Framing,
+
+    /// Tamanho máximo de uma mensagem em bytes
+    pub max_message_size: usize,
+
+    /// Backend de terminal das ferramentas
+    pub backend: Backend,
+
+    /// Servidor e painel alvo do backend tmux
+    pub tmux: TmuxConfig,
+
+    /// Sessão e janela alvo do backend screen
+    pub screen: ScreenConfig,
+
+    /// Endereço e credenciais da API do iTerm2 (backend `ITerm2Api`)
+    pub iterm2_api: ApiConfig,
+
+    /// Painel alvo do backend WezTerm
+    pub wezterm: WeztermConfig,
+
+    /// Endereço de controle remoto e janela alvo do backend kitty
+    pub kitty: KittyConfig,
+
+    /// Grava os scripts AppleScript do backend iTerm2 neste cassete (veja
+    /// [`crate::mcp::iterm::cassette`])
+    pub record_applescript: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            transport: Transport::Tcp,
+            address: "127.0.0.1".to_string(),
+            port: 3000,
+            unix_socket: None,
+            require_same_uid: false,
+            log_level: "info".to_string(),
+            legacy_protocol: false,
+            framing: Framing::Auto,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            backend: Backend::default(),
+            tmux: TmuxConfig::default(),
+            screen: ScreenConfig::default(),
+            iterm2_api: ApiConfig::default(),
+            wezterm: WeztermConfig::default(),
+            kitty: KittyConfig::default(),
+            record_applescript: None,
+        }
+    }
 }
@@ -22,6 +22,12 @@ pub fn check_iterm_availability() -> bool {
     }
 }
 
+/// Obtém o UID real do processo atual
+pub fn current_uid() -> u32 {
+    // getuid nunca falha
+    unsafe { libc::getuid() }
+}
+
 /// Obtém o caminho TTY do terminal ativo
 pub fn get_active_tty() -> Result<String> {
     // Executa o comando 'tty' para obter o caminho do TTY atual
@@ -67,7 +73,7 @@ pub fn letter_to_control_char(letter: &str) -> Result<u8> {
         ']' => Ok(29), // GS
         '^' => Ok(30), // RS
         '_' => Ok(31), // US
-        _ if c >= 'A' && c <= 'Z' => Ok((c as u8) - b'A' + 1),
+        _ if c.is_ascii_uppercase() => Ok((c as u8) - b'A' + 1),
         _ => Err(anyhow::anyhow!("Caractere de controle inválido: {}", letter)),
     }
 }
@@ -1,5 +1,8 @@
 #!/usr/bin/env python3
-"""Script de teste para o servidor MCP do iTerm"""
+"""Script de teste para o servidor MCP do iTerm
+
+Usa o envelope legado {id, function, arguments}; inicie o servidor com --legacy-protocol.
+"""
 
 import json
 import socket
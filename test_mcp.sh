@@ -1,32 +1,40 @@
 #!/bin/bash
-# Script de teste simples para o servidor MCP
+# Script de teste simples para o servidor MCP (JSON-RPC 2.0)
 
 echo "Testando servidor MCP do iTerm..."
 echo "================================"
 
-# Teste 1: write_to_terminal
-echo -e "\n1. Testando write_to_terminal..."
-echo '{"id":"test-1","function":"iterm-mcp:write_to_terminal","arguments":{"command":"echo Hello MCP!"}}' | nc localhost 3333
+# Teste 1: initialize
+echo -e "\n1. Testando initialize..."
+echo '{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test_mcp.sh","version":"0.1.0"}}}' | nc localhost 3333
+
+# Teste 2: tools/list
+echo -e "\n\n2. Testando tools/list..."
+echo '{"jsonrpc":"2.0","id":2,"method":"tools/list"}' | nc localhost 3333
+
+# Teste 3: write_to_terminal
+echo -e "\n\n3. Testando write_to_terminal..."
+echo '{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"iterm-mcp:write_to_terminal","arguments":{"command":"echo Hello MCP!"}}}' | nc localhost 3333
 
 # Aguarda um pouco
 sleep 1
 
-# Teste 2: read_terminal_output
-echo -e "\n\n2. Testando read_terminal_output..."
-echo '{"id":"test-2","function":"iterm-mcp:read_terminal_output","arguments":{"linesOfOutput":5}}' | nc localhost 3333
+# Teste 4: read_terminal_output
+echo -e "\n\n4. Testando read_terminal_output..."
+echo '{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"iterm-mcp:read_terminal_output","arguments":{"linesOfOutput":5}}}' | nc localhost 3333
 
 # Aguarda um pouco
 sleep 1
 
-# Teste 3: send_control_character
-echo -e "\n\n3. Testando send_control_character..."
-echo '{"id":"test-3","function":"iterm-mcp:send_control_character","arguments":{"letter":"C"}}' | nc localhost 3333
+# Teste 5: send_control_character
+echo -e "\n\n5. Testando send_control_character..."
+echo '{"jsonrpc":"2.0","id":5,"method":"tools/call","params":{"name":"iterm-mcp:send_control_character","arguments":{"letter":"C"}}}' | nc localhost 3333
 
 # Aguarda um pouco
 sleep 1
 
-# Teste 4: Função inexistente
-echo -e "\n\n4. Testando função inexistente..."
-echo '{"id":"test-4","function":"unknown:function","arguments":{}}' | nc localhost 3333
+# Teste 6: Ferramenta inexistente
+echo -e "\n\n6. Testando ferramenta inexistente..."
+echo '{"jsonrpc":"2.0","id":6,"method":"tools/call","params":{"name":"unknown:function","arguments":{}}}' | nc localhost 3333
 
 echo -e "\n\nTestes concluídos!"
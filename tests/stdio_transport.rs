@@ -0,0 +1,70 @@
+//! Testes de integração do transporte stdio
+//!
+//! Executa o binário com `--transport stdio`, envia uma sessão MCP roteirizada
+//! por stdin e verifica que stdout contém apenas respostas JSON-RPC e que o
+//! processo termina ao receber EOF.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+
+#[test]
+fn test_stdio_scripted_session() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rs_iterm"))
+        .args(["--transport", "stdio", "--log-level", "debug"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("falha ao iniciar o binário");
+
+    let session = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"stdio-test","version":"0.0.1"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"ping"}"#,
+    ];
+
+    {
+        // Fecha stdin ao sair do escopo para sinalizar EOF
+        let mut stdin = child.stdin.take().unwrap();
+        for message in session {
+            writeln!(stdin, "{}", message).unwrap();
+        }
+    }
+
+    let output = child.wait_with_output().expect("falha ao aguardar o binário");
+    assert!(
+        output.status.success(),
+        "o servidor deveria encerrar normalmente no EOF; stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Toda linha de stdout deve ser uma resposta JSON-RPC
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut responses: Vec<Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("stdout deve conter apenas JSON"))
+        .collect();
+    assert_eq!(responses.len(), 3, "stdout: {}", stdout);
+
+    // As requisições são processadas concorrentemente; ordena pelas IDs
+    responses.sort_by_key(|response| response["id"].as_i64());
+
+    assert_eq!(responses[0]["id"], 1);
+    assert_eq!(responses[0]["result"]["serverInfo"]["name"], "rs_iterm");
+
+    assert_eq!(responses[1]["id"], 2);
+    let tools = responses[1]["result"]["tools"].as_array().unwrap();
+    assert!(tools
+        .iter()
+        .any(|tool| tool["name"] == "iterm-mcp:write_to_terminal"));
+
+    assert_eq!(responses[2]["id"], 3);
+    assert!(responses[2]["result"].is_object());
+
+    // Os logs vão para stderr
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("stdio"), "stderr: {}", stderr);
+}